@@ -2,7 +2,7 @@
 
 use derive_more::derive::Display;
 
-use super::Expression;
+use super::{Expression, Span};
 
 /// An unary operator
 #[derive(Debug, Display, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -30,6 +30,18 @@ pub enum BinOp {
     RemoveHigh,
     /// `rl`: keep the lowest n values of a list or map
     RemoveLow,
+    /// `&`: bitwise and of two numbers
+    BitAnd,
+    /// `|`: bitwise or of two numbers
+    BitOr,
+    /// `xor`: bitwise exclusive or of two numbers
+    BitXor,
+    /// `<<`: shift a number to the left
+    Shl,
+    /// `>>`: shift a number to the right
+    Shr,
+    /// `r`: roll again the dice matching a value, a list of values or a predicate
+    Reroll,
 }
 
 impl BinOp {
@@ -38,10 +50,18 @@ impl BinOp {
     #[inline(always)]
     pub const fn eval_order(&self) -> Option<EvalOrder> {
         match self {
-            BinOp::Add | BinOp::Sub | BinOp::Join | BinOp::Mult | BinOp::Rem | BinOp::Div => {
-                Some(EvalOrder::AB)
-            }
-            BinOp::Repeat => None,
+            BinOp::Add
+            | BinOp::Sub
+            | BinOp::Join
+            | BinOp::Mult
+            | BinOp::Rem
+            | BinOp::Div
+            | BinOp::BitAnd
+            | BinOp::BitOr
+            | BinOp::BitXor
+            | BinOp::Shl
+            | BinOp::Shr => Some(EvalOrder::AB),
+            BinOp::Repeat | BinOp::Reroll => None,
             BinOp::KeepHigh | BinOp::KeepLow | BinOp::RemoveHigh | BinOp::RemoveLow => {
                 Some(EvalOrder::BA)
             }
@@ -59,6 +79,8 @@ impl BinOp {
 pub struct ExpressionBinOp<InjectedIntrisic> {
     pub op: BinOp,
     pub expressions: Box<[Expression<InjectedIntrisic>; 2]>,
+    /// Where the operation is in the source
+    pub span: Span,
 }
 
 impl<InjectedIntrisic> ExpressionBinOp<InjectedIntrisic> {
@@ -70,6 +92,7 @@ impl<InjectedIntrisic> ExpressionBinOp<InjectedIntrisic> {
         Self {
             op,
             expressions: Box::new([a, b]),
+            span: Span::NONE,
         }
     }
 }
@@ -1,4 +1,4 @@
-use super::Expression;
+use super::{Expression, Span};
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[cfg_attr(
@@ -11,6 +11,8 @@ pub struct ExpressionCall<InjectedIntrisic> {
     pub called: Box<Expression<InjectedIntrisic>>,
     /// the params of the call
     pub params: Box<[Expression<InjectedIntrisic>]>,
+    /// Where the call is in the source
+    pub span: Span,
 }
 
 impl<InjectedIntrisic> ExpressionCall<InjectedIntrisic> {
@@ -21,6 +23,7 @@ impl<InjectedIntrisic> ExpressionCall<InjectedIntrisic> {
         Self {
             called: Box::new(called),
             params,
+            span: Span::NONE,
         }
     }
 }
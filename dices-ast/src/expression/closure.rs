@@ -1,6 +1,6 @@
 //! Value enclosing an expression
 
-use super::Expression;
+use super::{Expression, Span};
 use crate::ident::IdentStr;
 
 #[derive(
@@ -23,6 +23,8 @@ use crate::ident::IdentStr;
 pub struct ExpressionClosure<InjectedIntrisic> {
     pub params: Box<[Box<IdentStr>]>,
     pub body: Box<Expression<InjectedIntrisic>>,
+    /// Where the closure is in the source
+    pub span: Span,
 }
 
 impl<InjectedIntrisic> ExpressionClosure<InjectedIntrisic> {
@@ -30,6 +32,7 @@ impl<InjectedIntrisic> ExpressionClosure<InjectedIntrisic> {
         Self {
             params,
             body: Box::new(body),
+            span: Span::NONE,
         }
     }
 }
@@ -0,0 +1,440 @@
+//! Formatting of expressions back into source
+//!
+//! The output is in a canonical style, and parsing it gives back the same expression.
+//! Parentheses are added only where the precedence of the operators requires them.
+//! The intrisics are written as paths into the std library, named `std` unless otherwise given.
+
+use std::fmt::{Display, Formatter, Result, Write};
+
+use itertools::Itertools;
+
+use crate::{
+    fmt::quoted_if_not_ident,
+    ident::IdentStr,
+    intrisics::{InjectedIntr, Intrisic},
+    value::{Value, ValueIntrisic, ValueNumber},
+};
+
+use super::{
+    bin_ops::BinOp, map::MapKey, set::MemberReceiver, un_ops::UnOp, Expression, ExpressionBinOp,
+    ExpressionCall, ExpressionClosure, ExpressionUnOp, Receiver,
+};
+
+/// Precedence of the set expression
+const SET: u8 = 0;
+/// Precedence of closures and loops
+const CLOSURE: u8 = 1;
+/// Precedence of the `is` operator
+const IS: u8 = 2;
+/// Precedence of the unary operators
+const UNARY: u8 = 11;
+/// Precedence of the dice operator
+const DICE: u8 = 12;
+/// Precedence of calls and member accesses
+const POSTFIX: u8 = 13;
+/// Precedence of atoms, that never need parentheses
+const ATOM: u8 = 14;
+
+impl BinOp {
+    /// The symbol used for this operator
+    fn symbol(&self) -> &'static str {
+        match self {
+            BinOp::Add => "+",
+            BinOp::Sub => "-",
+            BinOp::Join => "~",
+            BinOp::Repeat => "^",
+            BinOp::Mult => "*",
+            BinOp::Rem => "%",
+            BinOp::Div => "/",
+            BinOp::KeepHigh => "kh",
+            BinOp::KeepLow => "kl",
+            BinOp::RemoveHigh => "rh",
+            BinOp::RemoveLow => "rl",
+            BinOp::BitAnd => "&",
+            BinOp::BitOr => "|",
+            BinOp::BitXor => "xor",
+            BinOp::Shl => "<<",
+            BinOp::Shr => ">>",
+            BinOp::Reroll => "r",
+        }
+    }
+
+    /// The precedence of this operator
+    fn precedence(&self) -> u8 {
+        match self {
+            BinOp::BitOr => 3,
+            BinOp::BitXor => 4,
+            BinOp::BitAnd => 5,
+            BinOp::Shl | BinOp::Shr => 6,
+            BinOp::Add | BinOp::Sub => 7,
+            BinOp::Join => 8,
+            BinOp::Mult | BinOp::Div | BinOp::Rem => 9,
+            BinOp::Repeat
+            | BinOp::KeepHigh
+            | BinOp::KeepLow
+            | BinOp::RemoveHigh
+            | BinOp::RemoveLow
+            | BinOp::Reroll => 10,
+        }
+    }
+}
+
+impl UnOp {
+    /// The symbol used for this operator
+    fn symbol(&self) -> &'static str {
+        match self {
+            UnOp::Plus => "+",
+            UnOp::Neg => "-",
+            UnOp::Dice => "d",
+            UnOp::BitNot => "~",
+            UnOp::Explode => "d!",
+        }
+    }
+
+    /// If this operator throws a dice
+    fn is_dice(&self) -> bool {
+        matches!(self, UnOp::Dice | UnOp::Explode)
+    }
+}
+
+impl<II> Expression<II> {
+    /// The precedence of the outermost operation of this expression
+    fn precedence(&self) -> u8 {
+        match self {
+            Expression::Set(_) => SET,
+            Expression::Closure(_) | Expression::RepeatUntil(_) => CLOSURE,
+            Expression::BinOp(ExpressionBinOp {
+                op: BinOp::Repeat,
+                expressions: box [Expression::UnOp(ExpressionUnOp { op, .. }), _],
+                ..
+            }) if op.is_dice() => DICE,
+            Expression::BinOp(bin_op) => bin_op.op.precedence(),
+            Expression::UnOp(ExpressionUnOp { op, .. }) if op.is_dice() => DICE,
+            Expression::UnOp(_) => UNARY,
+            Expression::Is(_) => IS,
+            Expression::Call(_) | Expression::MemberAccess(_) => POSTFIX,
+            Expression::Const(Value::Number(n), _) if *n < ValueNumber::ZERO => UNARY,
+            Expression::Const(Value::Closure(closure), _) if closure.captures.is_empty() => CLOSURE,
+            Expression::Const(_, _)
+            | Expression::Ref(_)
+            | Expression::List(_)
+            | Expression::Map(_)
+            | Expression::Scope(_) => ATOM,
+        }
+    }
+}
+
+/// Something formatted as source, with the intrisics reached through a std library with the given name
+pub struct Source<'s, T: ?Sized> {
+    item: &'s T,
+    std: &'s IdentStr,
+}
+
+impl<II> Expression<II> {
+    /// Format this expression as source, reaching the intrisics through the std library with the given name
+    pub fn source<'s>(&'s self, std: &'s IdentStr) -> Source<'s, Self> {
+        Source { item: self, std }
+    }
+}
+
+impl<'s, T: ?Sized> Source<'s, T> {
+    /// Format something else, with the same std library
+    fn with<U: ?Sized>(&self, item: &'s U) -> Source<'s, U> {
+        Source {
+            item,
+            std: self.std,
+        }
+    }
+}
+
+/// An expression, that will be put in parentheses if its precedence is lower than the minimum
+struct Operand<'e, II>(Source<'e, Expression<II>>, u8);
+
+impl<II: InjectedIntr> Display for Operand<'_, II> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let Operand(expr, min) = self;
+        if expr.item.precedence() < *min {
+            write!(f, "({expr})")
+        } else {
+            write!(f, "{expr}")
+        }
+    }
+}
+
+/// Write the operand of a `d`, separating it if it would merge with the `d`
+fn dice_operand<II: InjectedIntr>(
+    f: &mut Formatter<'_>,
+    faces: Source<'_, Expression<II>>,
+) -> Result {
+    let faces = Operand(faces, POSTFIX).to_string();
+    if faces.starts_with(|ch: char| ch.is_alphabetic() || ch == '_') {
+        f.write_char(' ')?;
+    }
+    f.write_str(&faces)
+}
+
+impl<II: InjectedIntr> Display for Expression<II> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        self.source(IdentStr::new("std").unwrap()).fmt(f)
+    }
+}
+
+impl<II: InjectedIntr> Display for Source<'_, Expression<II>> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let src = |expr| self.with(expr);
+        let operand = |expr, min| Operand(self.with(expr), min);
+        match self.item {
+            Expression::Const(value, _) => write!(f, "{}", self.with(value)),
+            Expression::List(list) => write!(f, "[{}]", list.iter().map(src).format(", ")),
+            Expression::Map(map) => {
+                f.write_str("<|")?;
+                for (i, (key, value)) in map.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    match key {
+                        MapKey::Static(key) => quoted_if_not_ident(key, f)?,
+                        MapKey::Computed(key) => write!(f, "({})", src(key))?,
+                    }
+                    write!(f, ": {}", src(value))?;
+                }
+                f.write_str("|>")
+            }
+            Expression::Closure(closure) => write!(
+                f,
+                "|{}| {}",
+                closure.params.iter().format(", "),
+                operand(&closure.body, CLOSURE)
+            ),
+            Expression::RepeatUntil(repeat) => write!(
+                f,
+                "repeat {} until {}",
+                src(&repeat.body),
+                operand(&repeat.condition, CLOSURE)
+            ),
+            Expression::UnOp(ExpressionUnOp {
+                op,
+                expression: faces,
+                ..
+            }) if op.is_dice() => {
+                f.write_str(op.symbol())?;
+                dice_operand(f, src(faces))
+            }
+            Expression::UnOp(ExpressionUnOp { op, expression, .. }) => {
+                write!(f, "{}{}", op.symbol(), operand(expression, UNARY))
+            }
+            Expression::BinOp(ExpressionBinOp {
+                op: BinOp::Repeat,
+                expressions:
+                    box [Expression::UnOp(ExpressionUnOp {
+                        op: dice,
+                        expression: faces,
+                        ..
+                    }), n],
+                ..
+            }) if dice.is_dice() => {
+                // `XdY` is a shorthand for `dY ^ X`
+                match n {
+                    Expression::Const(Value::Number(n), _) if *n >= ValueNumber::ZERO => {
+                        write!(f, "{n}")?
+                    }
+                    n => write!(f, "{} ", operand(n, POSTFIX))?,
+                }
+                f.write_str(dice.symbol())?;
+                dice_operand(f, src(faces))
+            }
+            Expression::BinOp(ExpressionBinOp {
+                op,
+                expressions: box [a, b],
+                ..
+            }) => write!(
+                f,
+                "{} {} {}",
+                operand(a, op.precedence()),
+                op.symbol(),
+                operand(b, op.precedence() + 1)
+            ),
+            Expression::Is(is) => write!(f, "{} is {}", operand(&is.value, IS), is.pattern),
+            Expression::Call(call) if list_conversion(call).is_some() => {
+                // the name of the variable is lost, but any name gives back the same expression
+                let list = list_conversion(call).unwrap();
+                write!(f, "[x for x in {}]", src(list))
+            }
+            Expression::Call(call) if comprehension(call).is_some() => {
+                let (body, var, list, condition) = comprehension(call).unwrap();
+                match body {
+                    Some(body) => write!(f, "[{} for {var} in {}", src(body), src(list))?,
+                    None => write!(f, "[{var} for {var} in {}", src(list))?,
+                }
+                if let Some(condition) = condition {
+                    write!(f, " if {}", src(condition))?;
+                }
+                f.write_char(']')
+            }
+            Expression::Call(call) => write!(
+                f,
+                "{}({})",
+                operand(&call.called, POSTFIX),
+                call.params.iter().map(src).format(", ")
+            ),
+            Expression::MemberAccess(access) => {
+                write!(f, "{}", operand(&access.accessed, POSTFIX))?;
+                index(f, src(&access.index))
+            }
+            Expression::Scope(scope) => write!(f, "{{{}}}", scope.iter().map(src).format("; ")),
+            Expression::Set(set) => {
+                match &set.receiver {
+                    Receiver::Ignore => f.write_char('_')?,
+                    Receiver::Let(name) => write!(f, "let {name}")?,
+                    Receiver::Set(MemberReceiver { root, indices }) => {
+                        write!(f, "{root}")?;
+                        for idx in indices {
+                            index(f, src(idx))?
+                        }
+                    }
+                }
+                write!(f, " = {}", src(&set.value))
+            }
+            Expression::Ref(r) => write!(f, "{}", r.name),
+        }
+    }
+}
+
+impl<II> Value<II> {
+    /// Format this value as source evaluating back to it, reaching the intrisics through the std library
+    /// with the given name
+    ///
+    /// External values cannot be written in source, and are given with their display.
+    pub fn source<'s>(&'s self, std: &'s IdentStr) -> Source<'s, Self> {
+        Source { item: self, std }
+    }
+}
+
+impl<II: InjectedIntr> Display for Source<'_, Value<II>> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self.item {
+            Value::List(list) => write!(f, "[{}]", list.iter().map(|v| self.with(v)).format(", ")),
+            Value::Map(map) => {
+                if map.is_frozen() {
+                    write!(f, "{}.intrisics.freeze(", self.std)?;
+                }
+                f.write_str("<|")?;
+                for (i, (key, value)) in map.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    quoted_if_not_ident(key, f)?;
+                    write!(f, ": {}", self.with(value))?;
+                }
+                f.write_str("|>")?;
+                if map.is_frozen() {
+                    f.write_char(')')?;
+                }
+                Ok(())
+            }
+            Value::Intrisic(intrisic) => {
+                // intrisics are reachable from the standard library
+                write!(f, "{}.intrisics.{}", self.std, intrisic.0.name())
+            }
+            Value::Closure(closure) => {
+                // the captured values are bound in a scope, where the closure will capture them again
+                if !closure.captures.is_empty() {
+                    f.write_char('{')?;
+                    for (name, value) in &closure.captures {
+                        write!(f, "let {name} = {}; ", self.with(value))?;
+                    }
+                }
+                write!(
+                    f,
+                    "|{}| {}",
+                    closure.params.iter().format(", "),
+                    Operand(self.with(&closure.body), CLOSURE)
+                )?;
+                if !closure.captures.is_empty() {
+                    f.write_char('}')?;
+                }
+                Ok(())
+            }
+            value => write!(f, "{value}"),
+        }
+    }
+}
+
+/// The parts of a list comprehension: the body (`None` if it is the variable itself),
+/// the variable, the list and the condition
+pub(crate) type Comprehension<'e, II> = (
+    Option<&'e Expression<II>>,
+    &'e IdentStr,
+    &'e Expression<II>,
+    Option<&'e Expression<II>>,
+);
+
+/// Recognize the calls generated by desugaring a list comprehension
+pub(crate) fn comprehension<II>(call: &ExpressionCall<II>) -> Option<Comprehension<'_, II>> {
+    /// Split a call to an intrisic with a list and a closure with a single parameter
+    fn intrisic_call<II>(
+        call: &ExpressionCall<II>,
+    ) -> Option<(&Intrisic<II>, Comprehension<'_, II>)> {
+        match call {
+            ExpressionCall {
+                called: box Expression::Const(Value::Intrisic(ValueIntrisic(intrisic)), _),
+                params: box [list, Expression::Closure(ExpressionClosure { params, body, .. })],
+                ..
+            } => match &**params {
+                [var] => Some((intrisic, (Some(&**body), var, list, None))),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    match intrisic_call(call)? {
+        (Intrisic::Map, (body, var, list, _)) => {
+            if let Expression::Call(inner) = list {
+                if let Some((Intrisic::Filter, (condition, filter_var, list, _))) =
+                    intrisic_call(inner)
+                {
+                    if filter_var == var {
+                        return Some((body, var, list, condition));
+                    }
+                }
+            }
+            Some((body, var, list, None))
+        }
+        (Intrisic::Filter, (condition, var, list, _)) => Some((None, var, list, condition)),
+        _ => None,
+    }
+}
+
+/// Recognize the call generated by desugaring `[x for x in list]`, giving the list
+pub(crate) fn list_conversion<II>(call: &ExpressionCall<II>) -> Option<&Expression<II>> {
+    match call {
+        ExpressionCall {
+            called: box Expression::Const(Value::Intrisic(ValueIntrisic(Intrisic::ToList)), _),
+            params: box [list],
+            ..
+        } => Some(list),
+        _ => None,
+    }
+}
+
+/// Write an index, using the `.` notation when possible
+fn index<II: InjectedIntr>(f: &mut Formatter<'_>, index: Source<'_, Expression<II>>) -> Result {
+    match index.item {
+        Expression::Const(Value::String(s), _) => {
+            f.write_char('.')?;
+            quoted_if_not_ident(s, f)
+        }
+        Expression::Const(Value::Number(n), _) if *n >= ValueNumber::ZERO => write!(f, ".{n}"),
+        _ => write!(f, "[{index}]"),
+    }
+}
+
+/// Reformat a source in the canonical style, reaching the intrisics through the std library with the given name
+///
+/// Formatting an already formatted source does not change it.
+#[cfg(feature = "parse_expression")]
+pub fn format_source(src: &str, std: &IdentStr) -> std::result::Result<String, super::ParseError> {
+    let exprs = super::parse_file::<crate::intrisics::NoInjectedIntrisics>(src)?;
+    Ok(exprs.iter().map(|expr| expr.source(std)).join("; "))
+}
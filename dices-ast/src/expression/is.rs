@@ -0,0 +1,21 @@
+//! Expression checking if a value matches a pattern
+
+use crate::matcher::Matcher;
+
+use super::{Expression, Span};
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "bincode",
+    derive(bincode::Decode, bincode::Encode,),
+    bincode(bounds = "InjectedIntrisic: crate::intrisics::InjectedIntr")
+)]
+/// Check if `value` matches `pattern`, giving a boolean
+///
+/// The pattern is parsed together with the expression, so it is not a value and cannot be computed.
+pub struct ExpressionIs<InjectedIntrisic> {
+    pub value: Box<Expression<InjectedIntrisic>>,
+    pub pattern: Matcher<InjectedIntrisic>,
+    /// Where the expression is in the source
+    pub span: Span,
+}
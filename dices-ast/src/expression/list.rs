@@ -1,4 +1,4 @@
-use super::Expression;
+use super::{Expression, Span};
 
 #[derive(
     // display helper
@@ -17,7 +17,11 @@ use super::Expression;
     derive(bincode::Decode, bincode::Encode,),
     bincode(bounds = "InjectedIntrisic: crate::intrisics::InjectedIntr")
 )]
-pub struct ExpressionList<InjectedIntrisic>(Box<[Expression<InjectedIntrisic>]>);
+pub struct ExpressionList<InjectedIntrisic>(
+    Box<[Expression<InjectedIntrisic>]>,
+    /// Where the list is in the source
+    pub(crate) Span,
+);
 impl<InjectedIntrisic> ExpressionList<InjectedIntrisic> {
     pub fn iter(&self) -> impl Iterator<Item = &Expression<InjectedIntrisic>> {
         self.0.iter()
@@ -28,6 +32,6 @@ impl<InjectedIntrisic> FromIterator<Expression<InjectedIntrisic>>
     for ExpressionList<InjectedIntrisic>
 {
     fn from_iter<T: IntoIterator<Item = Expression<InjectedIntrisic>>>(iter: T) -> Self {
-        Self(FromIterator::from_iter(iter))
+        Self(FromIterator::from_iter(iter), Span::NONE)
     }
 }
@@ -1,6 +1,8 @@
+use derive_more::derive::From;
+
 use crate::value::string::ValueString;
 
-use super::Expression;
+use super::{Expression, Span};
 
 #[derive(
     // display helper
@@ -19,19 +21,41 @@ use super::Expression;
     derive(bincode::Decode, bincode::Encode,),
     bincode(bounds = "InjectedIntrisic: crate::intrisics::InjectedIntr")
 )]
-pub struct ExpressionMap<InjectedIntrisic>(Box<[(ValueString, Expression<InjectedIntrisic>)]>);
+pub struct ExpressionMap<InjectedIntrisic>(
+    Box<[(MapKey<InjectedIntrisic>, Expression<InjectedIntrisic>)]>,
+    /// Where the map is in the source
+    pub(crate) Span,
+);
 impl<InjectedIntrisic> ExpressionMap<InjectedIntrisic> {
-    pub fn iter(&self) -> impl Iterator<Item = (&ValueString, &Expression<InjectedIntrisic>)> {
+    pub fn iter(
+        &self,
+    ) -> impl Iterator<Item = (&MapKey<InjectedIntrisic>, &Expression<InjectedIntrisic>)> {
         self.0.iter().map(|(a, b)| (a, b))
     }
 }
 
-impl<InjectedIntrisic> FromIterator<(ValueString, Expression<InjectedIntrisic>)>
+impl<InjectedIntrisic> FromIterator<(MapKey<InjectedIntrisic>, Expression<InjectedIntrisic>)>
     for ExpressionMap<InjectedIntrisic>
 {
-    fn from_iter<T: IntoIterator<Item = (ValueString, Expression<InjectedIntrisic>)>>(
+    fn from_iter<
+        T: IntoIterator<Item = (MapKey<InjectedIntrisic>, Expression<InjectedIntrisic>)>,
+    >(
         iter: T,
     ) -> Self {
-        Self(FromIterator::from_iter(iter))
+        Self(FromIterator::from_iter(iter), Span::NONE)
     }
 }
+
+/// The key of an element of a map literal
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, From)]
+#[cfg_attr(
+    feature = "bincode",
+    derive(bincode::Decode, bincode::Encode,),
+    bincode(bounds = "InjectedIntrisic: crate::intrisics::InjectedIntr")
+)]
+pub enum MapKey<InjectedIntrisic> {
+    /// A key known when parsing: `<|a: 1|>` or `<|"a": 1|>`
+    Static(ValueString),
+    /// A key computed when the map is evaluated: `<|(expr): 1|>`
+    Computed(Expression<InjectedIntrisic>),
+}
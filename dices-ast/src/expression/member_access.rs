@@ -1,6 +1,6 @@
 //! Expression to read the members of a composite
 
-use super::Expression;
+use super::{Expression, Span};
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[cfg_attr(
@@ -12,4 +12,6 @@ use super::Expression;
 pub struct ExpressionMemberAccess<InjectedIntrisic> {
     pub accessed: Box<Expression<InjectedIntrisic>>,
     pub index: Box<Expression<InjectedIntrisic>>,
+    /// Where the access is in the source
+    pub span: Span,
 }
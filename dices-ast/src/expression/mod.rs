@@ -6,29 +6,47 @@ use crate::{intrisics::NoInjectedIntrisics, value::Value};
 pub use bin_ops::ExpressionBinOp;
 pub use call::ExpressionCall;
 pub use closure::ExpressionClosure;
+pub use is::ExpressionIs;
 pub use list::ExpressionList;
-pub use map::ExpressionMap;
+pub use map::{ExpressionMap, MapKey};
 pub use member_access::ExpressionMemberAccess;
 pub use ref_::ExpressionRef;
+pub use repeat_until::ExpressionRepeatUntil;
 pub use scope::ExpressionScope;
 pub use set::{ExpressionSet, Receiver};
+pub use span::{Position, Span};
 pub use un_ops::ExpressionUnOp;
 
 pub mod bin_ops;
 pub mod call;
 pub mod closure;
+pub mod is;
 pub mod list;
 pub mod map;
 pub mod member_access;
 pub mod ref_;
+pub mod repeat_until;
 pub mod scope;
 pub mod set;
+mod size;
+pub mod span;
 pub mod un_ops;
 
+mod display;
+#[cfg(feature = "parse_expression")]
+pub use display::format_source;
+pub use display::Source;
+#[cfg(feature = "parse_expression")]
+pub(crate) use display::{comprehension, list_conversion};
 #[cfg(feature = "parse_expression")]
 mod parse;
+#[cfg(test)]
+mod tests;
 #[cfg(feature = "parse_expression")]
-pub use parse::{parse_file, Error as ParseError};
+pub use parse::{
+    parse_file, parse_file_with_operators, parse_file_with_operators_at, CustomOperators,
+    Error as ParseError, InvalidOperator,
+};
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, From)]
 #[cfg_attr(
@@ -38,7 +56,8 @@ pub use parse::{parse_file, Error as ParseError};
 )]
 pub enum Expression<InjectedIntrisic> {
     /// Expression returning a constant value
-    Const(Value<InjectedIntrisic>),
+    #[from(ignore)]
+    Const(Value<InjectedIntrisic>, Span),
 
     /// List literal
     List(ExpressionList<InjectedIntrisic>),
@@ -59,6 +78,9 @@ pub enum Expression<InjectedIntrisic> {
     /// Member access
     MemberAccess(ExpressionMemberAccess<InjectedIntrisic>),
 
+    /// Repeat until a condition is met
+    RepeatUntil(ExpressionRepeatUntil<InjectedIntrisic>),
+
     /// Scoping expression
     Scope(ExpressionScope<InjectedIntrisic>),
 
@@ -66,6 +88,70 @@ pub enum Expression<InjectedIntrisic> {
     Set(ExpressionSet<InjectedIntrisic>),
     /// Ref expression
     Ref(ExpressionRef),
+
+    /// Check if a value matches a pattern
+    ///
+    /// This is the last variant, so adding it did not change how the others are encoded
+    Is(ExpressionIs<InjectedIntrisic>),
+}
+
+impl<InjectedIntrisic> From<Value<InjectedIntrisic>> for Expression<InjectedIntrisic> {
+    fn from(value: Value<InjectedIntrisic>) -> Self {
+        Expression::Const(value, Span::NONE)
+    }
+}
+
+impl<InjectedIntrisic> Expression<InjectedIntrisic> {
+    /// Where the expression is in the source
+    ///
+    /// Expressions that were not parsed have an empty span.
+    pub fn span(&self) -> &Span {
+        match self {
+            Expression::Const(_, span) => span,
+            Expression::List(list) => &list.1,
+            Expression::Map(map) => &map.1,
+            Expression::Closure(ExpressionClosure { span, .. }) => span,
+            Expression::UnOp(ExpressionUnOp { span, .. }) => span,
+            Expression::BinOp(ExpressionBinOp { span, .. }) => span,
+            Expression::Call(ExpressionCall { span, .. }) => span,
+            Expression::MemberAccess(ExpressionMemberAccess { span, .. }) => span,
+            Expression::RepeatUntil(ExpressionRepeatUntil { span, .. }) => span,
+            Expression::Is(ExpressionIs { span, .. }) => span,
+            Expression::Scope(scope) => &scope.1,
+            Expression::Set(ExpressionSet { span, .. }) => span,
+            Expression::Ref(ExpressionRef { span, .. }) => span,
+        }
+    }
+
+    /// Mutable access to the span of the expression
+    pub fn span_mut(&mut self) -> &mut Span {
+        match self {
+            Expression::Const(_, span) => span,
+            Expression::List(list) => &mut list.1,
+            Expression::Map(map) => &mut map.1,
+            Expression::Closure(ExpressionClosure { span, .. }) => span,
+            Expression::UnOp(ExpressionUnOp { span, .. }) => span,
+            Expression::BinOp(ExpressionBinOp { span, .. }) => span,
+            Expression::Call(ExpressionCall { span, .. }) => span,
+            Expression::MemberAccess(ExpressionMemberAccess { span, .. }) => span,
+            Expression::RepeatUntil(ExpressionRepeatUntil { span, .. }) => span,
+            Expression::Is(ExpressionIs { span, .. }) => span,
+            Expression::Scope(scope) => &mut scope.1,
+            Expression::Set(ExpressionSet { span, .. }) => span,
+            Expression::Ref(ExpressionRef { span, .. }) => span,
+        }
+    }
+
+    /// Give a span to the expression, if it does not have one yet
+    ///
+    /// Parenthesized expressions keep the inner span, without the parentheses.
+    pub fn with_span(mut self, span: Span) -> Self {
+        let s = self.span_mut();
+        if s.range().is_none() {
+            *s = span
+        }
+        self
+    }
 }
 
 impl Expression<NoInjectedIntrisics> {
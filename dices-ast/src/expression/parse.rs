@@ -1,13 +1,18 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, collections::BTreeMap, ops::Range};
 
 use either::Either::{Left, Right};
+use num_bigint::BigInt;
 use nunny::NonEmpty;
-use peg::{error::ParseError, str::LineCol};
+use peg::{error::ParseError, str::LineCol, RuleResult};
 use set::MemberReceiver;
 
+use derive_more::derive::{Display, Error};
+
 use crate::{
     expression::{bin_ops::BinOp, un_ops::UnOp, *},
     ident::IdentStr,
+    intrisics::{InjectedIntr, Intrisic},
+    matcher::{parse_prefix, Matcher},
     value::*,
 };
 
@@ -16,71 +21,115 @@ peg::parser! {
         # `dices` expressions
 
         This is the full grammar for a `dices` expression.
+        The operators in `ops` are recognized in addition to the builtin ones.
     */
-    pub grammar expression() for str {
+    pub grammar expression<'o>(ops: &'o CustomOperators, locator: &'o Locator<'o>) for str {
+
+        /// An expression, that can span multiple lines
+        rule expr<InjectedIntrisic: InjectedIntr>() -> Expression<InjectedIntrisic>
+            = e:expr_sep(<_>) { e }
+
+        /// An expression that ends at the end of the line, unless the line ends with an operator
+        rule statement<InjectedIntrisic: InjectedIntr>() -> Expression<InjectedIntrisic>
+            = e:expr_sep(<inline_()>) { e }
 
-        rule expr<InjectedIntrisic>() -> Expression<InjectedIntrisic>
+        /// An expression, using `sep` for the whitespace before the operators
+        ///
+        /// The whitespace after the operators can always contain newlines.
+        rule expr_sep<InjectedIntrisic: InjectedIntr>(sep: rule<()>) -> Expression<InjectedIntrisic>
             = precedence!{
-                receiver:receiver() _ "=" _ value:@ { ExpressionSet{ receiver, value: Box::new(value) }.into()}
+                start:position!() node:@ end:position!() { Expression::with_span(node, locator.span(start..end)) }
+                --
+                receiver:receiver() sep() "=" _ value:@ { ExpressionSet{ receiver, value: Box::new(value), span: Span::NONE }.into()}
                 --
                 "|" _ p:( ident()  ** ( _ "," _ ) ) _ "|" _ body:@ {
                     ExpressionClosure::new(p.into_iter().map(|p| p.to_owned()).collect(), body).into()
                 }
+                "repeat" !ident() _ body:expr() _ "until" !ident() _ condition:@ {
+                    ExpressionRepeatUntil { body: Box::new(body), condition: Box::new(condition), span: Span::NONE }.into()
+                }
                 --
-                a:(@) _ "+" _ b:@ { ExpressionBinOp::new(BinOp::Add, a,b).into() }
-                a:(@) _ "-" _ b:@ { ExpressionBinOp::new(BinOp::Sub, a,b).into() }
+                a:@ sep() "is" !ident() _ pattern:pattern() {
+                    ExpressionIs { value: Box::new(a), pattern, span: Span::NONE }.into()
+                }
+                --
+                a:(@) sep() "|" !">" _ b:@ { ExpressionBinOp::new(BinOp::BitOr, a,b).into() }
                 --
-                a:(@) _ "~" _ b:@ { ExpressionBinOp::new(BinOp::Join, a,b).into() }
+                a:(@) sep() "xor" !ident() _ b:@ { ExpressionBinOp::new(BinOp::BitXor, a,b).into() }
                 --
-                a:(@) _ "*" _ b:@ { ExpressionBinOp::new(BinOp::Mult, a,b).into() }
-                a:(@) _ "/" _ b:@ { ExpressionBinOp::new(BinOp::Div, a,b).into() }
-                a:(@) _ "%" _ b:@ { ExpressionBinOp::new(BinOp::Rem, a,b).into() }
+                a:(@) sep() "&" _ b:@ { ExpressionBinOp::new(BinOp::BitAnd, a,b).into() }
                 --
-                a:(@) _ "^" _ b:@  { ExpressionBinOp::new(BinOp::Repeat, a,b).into() }
-                a:(@) _ "kh" !ident() _ b:@ { ExpressionBinOp::new(BinOp::KeepHigh, a,b).into() }
-                a:(@) _ "kl" !ident() _ b:@ { ExpressionBinOp::new(BinOp::KeepLow, a,b).into() }
-                a:(@) _ "rh" !ident() _ b:@ { ExpressionBinOp::new(BinOp::RemoveHigh, a,b).into() }
-                a:(@) _ "rl" !ident() _ b:@ { ExpressionBinOp::new(BinOp::RemoveLow, a,b).into() }
+                a:(@) sep() "<<" _ b:@ { ExpressionBinOp::new(BinOp::Shl, a,b).into() }
+                a:(@) sep() ">>" _ b:@ { ExpressionBinOp::new(BinOp::Shr, a,b).into() }
+                --
+                a:(@) sep() op:custom_operator() _ b:@ {
+                    ExpressionCall::new(Expression::Const(Value::Intrisic(ValueIntrisic(op)), Span::NONE), Box::new([a, b])).into()
+                }
+                --
+                a:(@) sep() "+" _ b:@ { ExpressionBinOp::new(BinOp::Add, a,b).into() }
+                a:(@) sep() "-" _ b:@ { ExpressionBinOp::new(BinOp::Sub, a,b).into() }
+                --
+                a:(@) sep() "~" _ b:@ { ExpressionBinOp::new(BinOp::Join, a,b).into() }
+                --
+                a:(@) sep() "*" _ b:@ { ExpressionBinOp::new(BinOp::Mult, a,b).into() }
+                a:(@) sep() "/" _ b:@ { ExpressionBinOp::new(BinOp::Div, a,b).into() }
+                a:(@) sep() "%" _ b:@ { ExpressionBinOp::new(BinOp::Rem, a,b).into() }
+                --
+                a:(@) sep() "^" _ b:@  { ExpressionBinOp::new(BinOp::Repeat, a,b).into() }
+                a:(@) sep() "kh" !ident() _ b:@ { ExpressionBinOp::new(BinOp::KeepHigh, a,b).into() }
+                a:(@) sep() "kl" !ident() _ b:@ { ExpressionBinOp::new(BinOp::KeepLow, a,b).into() }
+                a:(@) sep() "rh" !ident() _ b:@ { ExpressionBinOp::new(BinOp::RemoveHigh, a,b).into() }
+                a:(@) sep() "rl" !ident() _ b:@ { ExpressionBinOp::new(BinOp::RemoveLow, a,b).into() }
+                a:(@) sep() "r" !ident() _ b:@ { ExpressionBinOp::new(BinOp::Reroll, a,b).into() }
                  --
                 "+" _ a:@ { ExpressionUnOp::new(UnOp::Plus, a).into() }
                 "-" _ a:@ { ExpressionUnOp::new(UnOp::Neg, a).into() }
+                "~" _ a:@ { ExpressionUnOp::new(UnOp::BitNot, a).into() }
                 --
+                "d!" _ f:@ { ExpressionUnOp::new(UnOp::Explode, f).into() }
                 "d" !ident() _ f:@ { ExpressionUnOp::new(UnOp::Dice, f).into() }
-                n:@ _ "d" !ident() _ f:(@) { ExpressionBinOp::new(BinOp::Repeat, ExpressionUnOp::new(UnOp::Dice, f).into(), n).into() }
+                n:@ sep() "d!" _ f:(@) { ExpressionBinOp::new(BinOp::Repeat, ExpressionUnOp::new(UnOp::Explode, f).into(), n).into() }
+                n:@ sep() "d" !ident() _ f:(@) { ExpressionBinOp::new(BinOp::Repeat, ExpressionUnOp::new(UnOp::Dice, f).into(), n).into() }
                 --
-                f:@ _ "(" _ p:(expr() ** (_ "," _)) _ ")" {
+                f:@ sep() "(" _ p:comma_list(<expr()>) _ ")" {
                     ExpressionCall::new(f,p.into_boxed_slice()).into()
                 }
-                accessed:@ _ "[" _ index:expr() _ "]" {
-                    ExpressionMemberAccess { accessed: Box::new(accessed), index: Box::new(index) }.into()
+                accessed:@ sep() "[" _ index:expr() _ "]" {
+                    ExpressionMemberAccess { accessed: Box::new(accessed), index: Box::new(index), span: Span::NONE }.into()
                 }
-                accessed:@ _ "." _ index:(
-                    i:ident()      { Expression::Const(Value::String((&**i).into())) }
-                    / s: string()  { Expression::Const(s.into()) }
-                    / n: number()  { Expression::Const(n.into()) }
+                accessed:@ sep() "." _ index:(
+                    i:ident()      { Expression::Const(Value::String((&**i).into()), Span::NONE) }
+                    / s: string()  { Expression::Const(s.into(), Span::NONE) }
+                    / n: number()  { Expression::Const(n.into(), Span::NONE) }
                 ) {
-                    ExpressionMemberAccess { accessed: Box::new(accessed), index: Box::new(index) }.into()
+                    ExpressionMemberAccess { accessed: Box::new(accessed), index: Box::new(index), span: Span::NONE }.into()
                 }
                 --
-                v:null()      { Expression::Const(v.into()) }
-                v:boolean()   { Expression::Const(v.into()) }
-                v:number()    { Expression::Const(v.into()) }
-                v:string()    { Expression::Const(v.into()) }
+                v:null()      { Expression::Const(v.into(), Span::NONE) }
+                v:boolean()   { Expression::Const(v.into(), Span::NONE) }
+                v:number()    { Expression::Const(v.into(), Span::NONE) }
+                v:string()    { Expression::Const(v.into(), Span::NONE) }
 
-                name:ident()     { ExpressionRef { name:name.to_owned() }.into() }
+                name:ident()     { ExpressionRef::new(name.to_owned()).into() }
 
-                "[" _ l:(expr() ** (_ "," _)) _ "]" {
+                "[" _ body:expr() _ "for" !ident() _ var:ident() _ "in" !ident() _ list:expr()
+                    condition:(_ "if" !ident() _ c:expr() { c })? _ "]" {
+                    comprehension(body, var, list, condition)
+                }
+
+                "[" _ l:comma_list(<expr()>) _ "]" {
                     ExpressionList::from_iter(l).into()
                 }
 
                 "<|" _
-                    elems:(
-                        (
-                            k:ident_or_quoted_string() _ ":" _ v:expr() {
-                                (ValueString::from(k.into_owned().into_boxed_str()),v)
-                            }
-                        ) ** (_ "," _)
-                    )
+                    elems:comma_list(<
+                        k:ident_or_quoted_string() _ ":" _ v:expr() {
+                            (MapKey::Static(ValueString::from(k.into_owned().into_boxed_str())),v)
+                        }
+                        / "(" _ k:expr() _ ")" _ ":" _ v:expr() {
+                            (MapKey::Computed(k),v)
+                        }
+                    >)
                 _ "|>" {
                     ExpressionMap::from_iter(elems).into()
                 }
@@ -91,19 +140,41 @@ peg::parser! {
             }
             / expected!("expression")
 
+        /// An operator defined by the user, given by the longest run of operator characters
+        rule custom_operator<InjectedIntrisic: InjectedIntr>() -> Intrisic<InjectedIntrisic>
+            = s:$([c if CustomOperators::is_operator_char(c)]+) {?
+                ops.get(s).and_then(Intrisic::named).ok_or("operator")
+            }
+
+        // -- PATTERNS
+
+        /// A pattern, on the right of `is`, parsed by the matcher grammar
+        rule pattern<InjectedIntrisic>() -> Matcher<InjectedIntrisic>
+            = m:#{|input, pos| match parse_prefix(&input[pos..]) {
+                Ok((m, len)) => RuleResult::Matched(pos + len, Ok(m)),
+                // consume up to the error, so it is reported where the pattern is wrong
+                Err(err) => RuleResult::Matched(pos + err.location.offset, Err(())),
+            }} {? m.or(Err("pattern")) }
+
         // -- LHS
-        rule receiver<InjectedIntrisic>() -> Receiver<InjectedIntrisic>
+        rule receiver<InjectedIntrisic: InjectedIntr>() -> Receiver<InjectedIntrisic>
          = "_"               { Receiver::Ignore }
          / "let" _ i:ident() { Receiver::Let(i.to_owned()) }
+         / "let" _ "_"       { Receiver::Ignore }
          / i:ident() indices:(
-            _ "." _ e:(
+            _ "." _ start:position!() e:(
                 i:ident()    { Value::String((**i).into())}
                 / s:string() { Value::String(s) }
                 / n:number() { Value::Number(n) }
-            ) { Expression::Const(e) }
+            ) end:position!() { Expression::Const(e, locator.span(start..end)) }
             /  _ "[" _ e:expr() _ "]" { e }
          ) *        { Receiver::Set(MemberReceiver::new(i.to_owned(), indices)) }
 
+        /// Elements separated by commas, with an optional trailing comma
+        rule comma_list<T>(elem: rule<T>) -> Vec<T>
+            = elems:(elem() ++ (_ "," _)) (_ ",")? { elems }
+            / { Vec::new() }
+
         // --- SCALARS ---
 
         /// A null value
@@ -115,9 +186,16 @@ peg::parser! {
             = "true"  { ValueBool::TRUE  }
             / "false" { ValueBool::FALSE }
 
-        /// An unsigned number
+        /// An unsigned number, in decimal, hexadecimal, octal or binary base
+        ///
+        /// Digits can be separated by underscores (`1_000`)
         rule number() -> ValueNumber
-            = n:$(['0'..='9']+) {? n.parse().or(Err("number")) }
+            = "0x" n:$(['0'..='9'|'a'..='f'|'A'..='F'] ['0'..='9'|'a'..='f'|'A'..='F'|'_']*) {?
+                parse_number(n, 16).ok_or("hexadecimal number")
+            }
+            / "0o" n:$(['0'..='7'] ['0'..='7'|'_']*) {? parse_number(n, 8).ok_or("octal number") }
+            / "0b" n:$(['0'..='1'] ['0'..='1'|'_']*) {? parse_number(n, 2).ok_or("binary number") }
+            / n:$(['0'..='9'] ['0'..='9'|'_']*) {? parse_number(n, 10).ok_or("number") }
 
         /// A quoted string value
         rule string() -> ValueString
@@ -185,15 +263,36 @@ peg::parser! {
             = i: ident()         { Cow::Borrowed(&**i) }
             / s: quoted_string() { s }
 
+        /// A whole file, that can start with a shebang line like `#!/usr/bin/env dices`
+        pub rule file<InjectedIntrisic: InjectedIntr>() -> Box<NonEmpty<[Expression<InjectedIntrisic>]>>
+            = ("#!" [^'\n']*)? exprs: scope_inner() { exprs }
+
         // --- Inner of a scope `{}`. Also the content of a file
-        pub rule scope_inner<InjectedIntrisic>() -> Box<NonEmpty<[Expression<InjectedIntrisic>]>>
-            = _ exprs: ( e:expr() {e} / { Value::Null(ValueNull).into() } ) ** (_ ";" _) _ {
+        pub rule scope_inner<InjectedIntrisic: InjectedIntr>() -> Box<NonEmpty<[Expression<InjectedIntrisic>]>>
+            = _ exprs: ( e:statement() {e} / { Value::Null(ValueNull).into() } ) ** statement_sep() _ {
                 exprs.into_boxed_slice()
                     .try_into()
                     .unwrap_or_else(|_| nunny::vec![Value::Null(ValueNull).into()].into())
             }
 
 
+        /// Separator between statements: a `;`, or a newline followed by another statement
+        ///
+        /// Blank lines do not count as empty statements.
+        rule statement_sep()
+            = _ ";" _
+            / inline_() "\n" _ !(";" / "}" / ![_])
+
+        /// Parse whitespace and comments on a single line, discarding them
+        rule inline_() -> ()
+            = quiet!{
+                (
+                    [' ' | '\t' | '\r']                // Whitespace
+                    / "//" [^'\n']*                   // C-style line comment, without the newline
+                    / "/*" (!"*/" [_])* "*/"         // C-style block comment
+                )* {}
+            }
+
         /// Parse whitespace and comments, discarding them
         rule _ -> ()
             = quiet!{
@@ -207,10 +306,171 @@ peg::parser! {
 
 }
 
+/// Desugar a list comprehension into calls to the `map` and `filter` intrisics
+///
+/// `[body for var in list if condition]` becomes `map(filter(list, |var| condition), |var| body)`,
+/// and `[var for var in list]` becomes `to_list(list)`.
+/// The intrisics are inserted as constants, so they do not depend on the names in scope.
+fn comprehension<InjectedIntrisic>(
+    body: Expression<InjectedIntrisic>,
+    var: &IdentStr,
+    list: Expression<InjectedIntrisic>,
+    condition: Option<Expression<InjectedIntrisic>>,
+) -> Expression<InjectedIntrisic> {
+    let call = |intrisic, list, body| -> Expression<InjectedIntrisic> {
+        ExpressionCall::new(
+            Expression::Const(Value::Intrisic(ValueIntrisic(intrisic)), Span::NONE),
+            Box::new([
+                list,
+                ExpressionClosure::new(Box::new([var.to_owned()]), body).into(),
+            ]),
+        )
+        .into()
+    };
+    let filtered = condition.is_some();
+    let list = match condition {
+        Some(condition) => call(Intrisic::Filter, list, condition),
+        None => list,
+    };
+    match body {
+        // `[x for x in list if condition]` does not need to map anything
+        Expression::Ref(ExpressionRef { name, .. }) if *name == *var && filtered => list,
+        // `[x for x in list]` only needs to convert the list
+        Expression::Ref(ExpressionRef { name, .. }) if *name == *var => ExpressionCall::new(
+            Expression::Const(Value::Intrisic(ValueIntrisic(Intrisic::ToList)), Span::NONE),
+            Box::new([list]),
+        )
+        .into(),
+        body => call(Intrisic::Map, list, body),
+    }
+}
+
+/// Parse the digits of a number literal, skipping the separators
+fn parse_number(digits: &str, radix: u32) -> Option<ValueNumber> {
+    let digits: Vec<u8> = digits.bytes().filter(|&b| b != b'_').collect();
+    BigInt::parse_bytes(&digits, radix).map(ValueNumber::from)
+}
+
 pub type Error = ParseError<LineCol>;
 
-pub fn parse_file<InjectedIntrisic>(
+pub fn parse_file<InjectedIntrisic: InjectedIntr>(
     src: &str,
 ) -> Result<Box<NonEmpty<[Expression<InjectedIntrisic>]>>, Error> {
-    expression::scope_inner(src)
+    parse_file_with_operators(src, &CustomOperators::new())
 }
+
+/// Parse a file, recognizing the given operators in addition to the builtin ones
+pub fn parse_file_with_operators<InjectedIntrisic: InjectedIntr>(
+    src: &str,
+    ops: &CustomOperators,
+) -> Result<Box<NonEmpty<[Expression<InjectedIntrisic>]>>, Error> {
+    parse_file_with_operators_at(src, ops, Position::START)
+}
+
+/// Parse a piece of a longer source, that starts at `start`
+///
+/// The spans of the expressions are positions in the whole source.
+pub fn parse_file_with_operators_at<InjectedIntrisic: InjectedIntr>(
+    src: &str,
+    ops: &CustomOperators,
+    start: Position,
+) -> Result<Box<NonEmpty<[Expression<InjectedIntrisic>]>>, Error> {
+    expression::file(src, ops, &Locator::new(src, start))
+}
+
+/// Finds the positions of the offsets of a source, to build the spans
+pub struct Locator<'s> {
+    src: &'s str,
+    /// Position of the start of the source
+    start: Position,
+    /// Offsets where each line starts
+    line_starts: Vec<usize>,
+}
+
+impl<'s> Locator<'s> {
+    fn new(src: &'s str, start: Position) -> Self {
+        let line_starts = [0]
+            .into_iter()
+            .chain(src.match_indices('\n').map(|(n, _)| n + 1))
+            .collect();
+        Self {
+            src,
+            start,
+            line_starts,
+        }
+    }
+
+    fn position(&self, offset: usize) -> Position {
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let line_start = self.line_starts[line];
+        let mut column = self.src[line_start..offset].chars().count() + 1;
+        // the first line does not start at the beginning of the line of the whole source
+        if line == 0 {
+            column += self.start.column - 1
+        }
+        Position {
+            offset: self.start.offset + offset,
+            line: self.start.line + line,
+            column,
+        }
+    }
+
+    fn span(&self, range: Range<usize>) -> Span {
+        Span::new(self.position(range.start), self.position(range.end))
+    }
+}
+
+/// Binary operators defined by the user
+///
+/// Each operator is a symbol made of operator characters, and is parsed as a call to an intrisic with
+/// the two operands. They bind less tightly than `+` and `-`, and more tightly than the shifts.
+#[derive(Debug, Clone, Default)]
+pub struct CustomOperators {
+    /// The name of the intrisic called by each symbol
+    ops: BTreeMap<Box<str>, &'static str>,
+}
+
+impl CustomOperators {
+    /// The characters that can make up an operator
+    const OPERATOR_CHARS: &'static str = "!$%&*+-/:<=>?@^|~";
+    /// Symbols that already have a meaning
+    const RESERVED: &'static [&'static str] =
+        &["+", "-", "*", "/", "%", "^", "~", "&", "|", "<<", ">>", "="];
+
+    pub const fn new() -> Self {
+        Self {
+            ops: BTreeMap::new(),
+        }
+    }
+
+    fn is_operator_char(ch: char) -> bool {
+        Self::OPERATOR_CHARS.contains(ch)
+    }
+
+    /// Add an operator, that calls the intrisic with the given name
+    ///
+    /// The symbol must be made of the characters `!$%&*+-/:<=>?@^|~`, must not be one of the builtin
+    /// operators and must not contain the start of a comment.
+    pub fn insert(&mut self, symbol: &str, intrisic: &'static str) -> Result<(), InvalidOperator> {
+        if symbol.is_empty()
+            || !symbol.chars().all(Self::is_operator_char)
+            || Self::RESERVED.contains(&symbol)
+            || symbol.contains("//")
+            || symbol.contains("/*")
+        {
+            return Err(InvalidOperator(symbol.to_owned()));
+        }
+        self.ops.insert(symbol.into(), intrisic);
+        Ok(())
+    }
+
+    /// The name of the intrisic called by an operator
+    pub fn get(&self, symbol: &str) -> Option<&'static str> {
+        self.ops.get(symbol).copied()
+    }
+}
+
+/// Error when defining an operator with an invalid symbol
+#[derive(Debug, Clone, Display, Error)]
+#[display("`{_0}` cannot be used as an operator")]
+pub struct InvalidOperator(#[error(not(source))] pub String);
@@ -2,21 +2,23 @@
 
 use crate::ident::IdentStr;
 
+use super::Span;
+
 /// An expression referencing a variable
-#[derive(
-    Debug,
-    Clone,
-    PartialEq,
-    Eq,
-    PartialOrd,
-    Ord,
-    Hash,
-)]
-#[cfg_attr(
-    feature = "bincode",
-    derive(bincode::Decode, bincode::Encode,)
-)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "bincode", derive(bincode::Decode, bincode::Encode,))]
 pub struct ExpressionRef {
     /// The name of the variable
     pub name: Box<IdentStr>,
+    /// Where the reference is in the source
+    pub span: Span,
+}
+
+impl ExpressionRef {
+    pub fn new(name: Box<IdentStr>) -> Self {
+        Self {
+            name,
+            span: Span::NONE,
+        }
+    }
 }
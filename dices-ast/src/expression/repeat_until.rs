@@ -0,0 +1,19 @@
+//! Expression that repeat its body until a condition is met
+
+use super::{Expression, Span};
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "bincode",
+    derive(bincode::Decode, bincode::Encode,),
+    bincode(bounds = "InjectedIntrisic: crate::intrisics::InjectedIntr")
+)]
+/// Evaluate `body` repeatedly, until `condition` is truthy
+///
+/// The value of the expression is the last value of `body`
+pub struct ExpressionRepeatUntil<InjectedIntrisic> {
+    pub body: Box<Expression<InjectedIntrisic>>,
+    pub condition: Box<Expression<InjectedIntrisic>>,
+    /// Where the loop is in the source
+    pub span: Span,
+}
@@ -1,12 +1,15 @@
 use std::ops::{Deref, DerefMut};
 
-use derive_more::derive::{From, Into};
 use nunny::NonEmpty;
 
-use super::Expression;
+use super::{Expression, Span};
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, From, Into)]
-pub struct ExpressionScope<InjectedIntrisic>(Box<NonEmpty<[Expression<InjectedIntrisic>]>>);
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ExpressionScope<InjectedIntrisic>(
+    Box<NonEmpty<[Expression<InjectedIntrisic>]>>,
+    /// Where the scope is in the source
+    pub(crate) Span,
+);
 
 impl<InjectedIntrisic> Deref for ExpressionScope<InjectedIntrisic> {
     type Target = NonEmpty<[Expression<InjectedIntrisic>]>;
@@ -23,7 +26,15 @@ impl<InjectedIntrisic> DerefMut for ExpressionScope<InjectedIntrisic> {
 
 impl<InjectedIntrisic> ExpressionScope<InjectedIntrisic> {
     pub fn new(exprs: Box<NonEmpty<[Expression<InjectedIntrisic>]>>) -> Self {
-        Self(exprs)
+        Self(exprs, Span::NONE)
+    }
+}
+
+impl<InjectedIntrisic> From<Box<NonEmpty<[Expression<InjectedIntrisic>]>>>
+    for ExpressionScope<InjectedIntrisic>
+{
+    fn from(exprs: Box<NonEmpty<[Expression<InjectedIntrisic>]>>) -> Self {
+        Self::new(exprs)
     }
 }
 
@@ -49,7 +60,7 @@ where
         decoder: &mut D,
     ) -> Result<Self, bincode::error::DecodeError> {
         let inner: Box<[Expression<InjectedIntrisic>]> = bincode::Decode::decode(decoder)?;
-        Ok(Self(
+        Ok(Self::new(
             nunny::Vec::new(inner.into_vec())
                 .map_err(|_| bincode::error::DecodeError::Other("Invalid empty scope"))?
                 .into_boxed_slice(),
@@ -66,7 +77,7 @@ where
     ) -> Result<Self, bincode::error::DecodeError> {
         let inner: Box<[Expression<InjectedIntrisic>]> =
             bincode::BorrowDecode::borrow_decode(decoder)?;
-        Ok(Self(
+        Ok(Self::new(
             nunny::Vec::new(inner.into_vec())
                 .map_err(|_| bincode::error::DecodeError::Other("Invalid empty scope"))?
                 .into_boxed_slice(),
@@ -2,7 +2,7 @@
 
 use crate::ident::IdentStr;
 
-use super::Expression;
+use super::{Expression, Span};
 
 /// An `=` expression
 
@@ -17,6 +17,8 @@ pub struct ExpressionSet<InjectedIntrisic> {
     pub receiver: Receiver<InjectedIntrisic>,
     /// The value to set
     pub value: Box<Expression<InjectedIntrisic>>,
+    /// Where the expression is in the source
+    pub span: Span,
 }
 
 /// The lhs of a `=` expression
@@ -28,7 +30,7 @@ pub struct ExpressionSet<InjectedIntrisic> {
     bincode(bounds = "InjectedIntrisic: crate::intrisics::InjectedIntr")
 )]
 pub enum Receiver<InjectedIntrisic> {
-    /// `_` receiver: throw away its value
+    /// `_` or `let _` receiver: throw away its value
     Ignore,
     /// Set a variable
     Set(MemberReceiver<InjectedIntrisic>),
@@ -0,0 +1,111 @@
+//! Static estimate of the size of the values built by an expression
+//!
+//! The estimate does not evaluate anything: it only multiplies the sizes of the repeated
+//! expressions by the number of repeats, when those are written as literals. It is meant to catch
+//! things like `d6 ^ 1000000` before they are evaluated, not to be exact.
+
+use crate::value::{Value, ValueNumber};
+
+use super::{
+    bin_ops::BinOp, Expression, ExpressionBinOp, ExpressionCall, ExpressionIs,
+    ExpressionMemberAccess, ExpressionRepeatUntil, ExpressionSet, ExpressionUnOp, MapKey, Receiver,
+};
+
+impl<InjectedIntrisic> Expression<InjectedIntrisic> {
+    /// Roughly how many values evaluating the expression will build
+    ///
+    /// Repeats are multiplied only if their number is a literal, and loops or closures are counted
+    /// as executed once, so this is a lower bound for the expressions that matter.
+    pub fn estimated_size(&self) -> u64 {
+        match self {
+            Expression::Const(value, _) => value_size(value),
+            Expression::List(list) => sum(list.iter()),
+            Expression::Map(map) => map
+                .iter()
+                .map(|(key, value)| match key {
+                    MapKey::Static(_) => value.estimated_size(),
+                    MapKey::Computed(key) => {
+                        key.estimated_size().saturating_add(value.estimated_size())
+                    }
+                })
+                .fold(0, u64::saturating_add),
+            // the body is not evaluated when the closure is built
+            Expression::Closure(_) => 1,
+            Expression::UnOp(ExpressionUnOp { expression, .. }) => expression.estimated_size(),
+            Expression::BinOp(ExpressionBinOp {
+                op: BinOp::Repeat,
+                expressions: box [body, repeats],
+                ..
+            }) => match literal_count(repeats) {
+                Some(repeats) => body.estimated_size().saturating_mul(repeats),
+                None => body
+                    .estimated_size()
+                    .saturating_add(repeats.estimated_size()),
+            },
+            Expression::BinOp(ExpressionBinOp {
+                expressions: box [a, b],
+                ..
+            }) => a.estimated_size().saturating_add(b.estimated_size()),
+            Expression::Call(ExpressionCall { called, params, .. }) => {
+                called.estimated_size().saturating_add(sum(params.iter()))
+            }
+            Expression::MemberAccess(ExpressionMemberAccess {
+                accessed, index, ..
+            }) => accessed
+                .estimated_size()
+                .saturating_add(index.estimated_size()),
+            Expression::RepeatUntil(ExpressionRepeatUntil {
+                body, condition, ..
+            }) => body
+                .estimated_size()
+                .saturating_add(condition.estimated_size()),
+            Expression::Is(ExpressionIs { value, .. }) => value.estimated_size(),
+            Expression::Scope(scope) => sum(scope.iter()),
+            Expression::Set(ExpressionSet {
+                receiver, value, ..
+            }) => {
+                let indices = match receiver {
+                    Receiver::Set(member) => sum(member.indices.iter()),
+                    Receiver::Ignore | Receiver::Let(_) => 0,
+                };
+                indices.saturating_add(value.estimated_size())
+            }
+            Expression::Ref(_) => 1,
+        }
+    }
+}
+
+/// Sum the estimated sizes of some expressions
+fn sum<'e, InjectedIntrisic: 'e>(
+    exprs: impl IntoIterator<Item = &'e Expression<InjectedIntrisic>>,
+) -> u64 {
+    exprs
+        .into_iter()
+        .map(Expression::estimated_size)
+        .fold(0, u64::saturating_add)
+}
+
+/// The number of values in a constant
+fn value_size<InjectedIntrisic>(value: &Value<InjectedIntrisic>) -> u64 {
+    match value {
+        Value::List(list) => list.iter().map(value_size).fold(0, u64::saturating_add),
+        Value::Map(map) => map
+            .iter()
+            .map(|(_, v)| value_size(v))
+            .fold(0, u64::saturating_add),
+        _ => 1,
+    }
+}
+
+/// The number of repeats, if it is written as a literal
+fn literal_count<InjectedIntrisic>(expr: &Expression<InjectedIntrisic>) -> Option<u64> {
+    match expr {
+        Expression::Const(Value::Number(n), _) => Some(if *n < ValueNumber::ZERO {
+            // negative repeats are an error, and do not build anything
+            0
+        } else {
+            u64::try_from(n.clone()).unwrap_or(u64::MAX)
+        }),
+        _ => None,
+    }
+}
@@ -0,0 +1,116 @@
+//! Positions of the expressions in the source
+
+use std::{cmp::Ordering, hash::Hash, ops::Range};
+
+use derive_more::derive::Display;
+
+/// A position in the source
+///
+/// It is shown as `line:column`, like the positions of the parse errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display)]
+#[display("{line}:{column}")]
+pub struct Position {
+    /// The offset in bytes from the start of the source
+    pub offset: usize,
+    /// The line, starting from 1
+    pub line: usize,
+    /// The column, in characters, starting from 1
+    pub column: usize,
+}
+
+impl Position {
+    /// The start of a source
+    pub const START: Self = Self {
+        offset: 0,
+        line: 1,
+        column: 1,
+    };
+
+    /// Move the position after a piece of the source
+    pub fn advance(&mut self, piece: &str) {
+        self.offset += piece.len();
+        match piece.rsplit_once('\n') {
+            Some((before, after)) => {
+                self.line += before.matches('\n').count() + 1;
+                self.column = after.chars().count() + 1;
+            }
+            None => self.column += piece.chars().count(),
+        }
+    }
+}
+
+/// The region of the source an expression was parsed from
+///
+/// Expressions built without a source have no span. Spans do not take part in
+/// comparisons and hashing, so two expressions that differ only in where they
+/// were written are equal.
+#[derive(Debug, Clone, Default)]
+pub struct Span(Option<(Position, Position)>);
+
+impl Span {
+    /// The span of an expression without a source
+    pub const NONE: Self = Self(None);
+
+    /// The span between two positions of the source
+    pub fn new(start: Position, end: Position) -> Self {
+        Self(Some((start, end)))
+    }
+
+    /// The range of the source, in bytes, if any
+    pub fn range(&self) -> Option<Range<usize>> {
+        self.0.map(|(start, end)| start.offset..end.offset)
+    }
+
+    /// The position where the span starts, if any
+    pub fn start(&self) -> Option<Position> {
+        self.0.map(|(start, _)| start)
+    }
+}
+
+impl PartialEq for Span {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+impl Eq for Span {}
+impl PartialOrd for Span {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Span {
+    fn cmp(&self, _: &Self) -> Ordering {
+        Ordering::Equal
+    }
+}
+impl Hash for Span {
+    fn hash<H: std::hash::Hasher>(&self, _: &mut H) {}
+}
+
+// Spans refer to a source that is not serialized with the expression, so they are dropped
+
+#[cfg(feature = "bincode")]
+impl bincode::Encode for Span {
+    fn encode<E: bincode::enc::Encoder>(
+        &self,
+        _: &mut E,
+    ) -> Result<(), bincode::error::EncodeError> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "bincode")]
+impl bincode::Decode for Span {
+    fn decode<D: bincode::de::Decoder>(_: &mut D) -> Result<Self, bincode::error::DecodeError> {
+        Ok(Self::NONE)
+    }
+}
+
+#[cfg(feature = "bincode")]
+impl<'de> bincode::BorrowDecode<'de> for Span {
+    fn borrow_decode<D: bincode::de::BorrowDecoder<'de>>(
+        _: &mut D,
+    ) -> Result<Self, bincode::error::DecodeError> {
+        Ok(Self::NONE)
+    }
+}
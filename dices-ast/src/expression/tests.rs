@@ -0,0 +1,598 @@
+#[cfg(feature = "parse_expression")]
+mod parse {
+    use crate::{
+        expression::{parse_file, Expression},
+        intrisics::NoInjectedIntrisics,
+        value::{Value, ValueNumber},
+    };
+
+    /// Parse a source made of a single expression
+    fn parse(src: &str) -> Expression<NoInjectedIntrisics> {
+        let exprs = parse_file(src).expect("The source should be parseable");
+        assert_eq!(
+            exprs.len(),
+            1,
+            "The source should contain a single expression"
+        );
+        exprs.first().clone()
+    }
+
+    fn number(n: impl Into<ValueNumber>) -> Expression<NoInjectedIntrisics> {
+        Value::Number(n.into()).into()
+    }
+
+    mod numbers {
+        use super::*;
+
+        #[test]
+        fn decimal() {
+            assert_eq!(parse("42"), number(42))
+        }
+
+        #[test]
+        fn hexadecimal() {
+            assert_eq!(parse("0x1F"), number(31));
+            assert_eq!(parse("0xff"), number(255))
+        }
+
+        #[test]
+        fn octal() {
+            assert_eq!(parse("0o17"), number(15))
+        }
+
+        #[test]
+        fn binary() {
+            assert_eq!(parse("0b1010"), number(10))
+        }
+
+        #[test]
+        fn separators() {
+            assert_eq!(parse("1_000_000"), number(1_000_000));
+            assert_eq!(parse("0b1111_0000"), number(0b1111_0000))
+        }
+
+        #[test]
+        fn big() {
+            assert_eq!(
+                parse("0xffff_ffff_ffff_ffff_ffff_ffff_ffff_ffff"),
+                number(u128::MAX)
+            )
+        }
+
+        #[test]
+        fn invalid_digits() {
+            assert!(parse_file::<NoInjectedIntrisics>("0b102").is_err());
+            assert!(parse_file::<NoInjectedIntrisics>("0o8").is_err())
+        }
+    }
+
+    mod bitwise {
+        use super::*;
+        use crate::expression::{bin_ops::BinOp, ExpressionBinOp};
+
+        fn bin_op(
+            op: BinOp,
+            a: Expression<NoInjectedIntrisics>,
+            b: Expression<NoInjectedIntrisics>,
+        ) -> Expression<NoInjectedIntrisics> {
+            ExpressionBinOp::new(op, a, b).into()
+        }
+
+        #[test]
+        fn precedence() {
+            assert_eq!(
+                parse("1 | 2 xor 3 & 4 << 5"),
+                bin_op(
+                    BinOp::BitOr,
+                    number(1),
+                    bin_op(
+                        BinOp::BitXor,
+                        number(2),
+                        bin_op(
+                            BinOp::BitAnd,
+                            number(3),
+                            bin_op(BinOp::Shl, number(4), number(5))
+                        )
+                    )
+                )
+            )
+        }
+
+        #[test]
+        fn or_inside_map() {
+            assert!(parse_file::<NoInjectedIntrisics>("<|a: 1 | 2|>").is_ok());
+            assert!(parse_file::<NoInjectedIntrisics>("<|a: 1|>").is_ok())
+        }
+
+        #[test]
+        fn xor_is_a_keyword() {
+            assert!(parse_file::<NoInjectedIntrisics>("let xor = 1").is_err());
+            assert!(parse_file::<NoInjectedIntrisics>("let xored = 1").is_ok())
+        }
+    }
+
+    mod comprehensions {
+        use super::*;
+        use crate::{
+            expression::{ExpressionCall, ExpressionClosure, ExpressionRef},
+            ident::IdentStr,
+            intrisics::Intrisic,
+            value::ValueIntrisic,
+        };
+
+        fn var(name: &str) -> Expression<NoInjectedIntrisics> {
+            ExpressionRef::new(IdentStr::new(name).unwrap().to_owned()).into()
+        }
+
+        fn call(
+            intrisic: Intrisic<NoInjectedIntrisics>,
+            list: Expression<NoInjectedIntrisics>,
+            param: &str,
+            body: Expression<NoInjectedIntrisics>,
+        ) -> Expression<NoInjectedIntrisics> {
+            ExpressionCall::new(
+                Value::Intrisic(ValueIntrisic(intrisic)).into(),
+                Box::new([
+                    list,
+                    ExpressionClosure::new(
+                        Box::new([IdentStr::new(param).unwrap().to_owned()]),
+                        body,
+                    )
+                    .into(),
+                ]),
+            )
+            .into()
+        }
+
+        #[test]
+        fn map_form() {
+            assert_eq!(
+                parse("[x for x in l]"),
+                ExpressionCall::new(
+                    Value::Intrisic(ValueIntrisic(Intrisic::ToList)).into(),
+                    Box::new([var("l")])
+                )
+                .into()
+            );
+            assert_eq!(
+                parse("[y for x in l]"),
+                call(Intrisic::Map, var("l"), "x", var("y"))
+            )
+        }
+
+        #[test]
+        fn filter_form() {
+            assert_eq!(
+                parse("[x for x in l if c]"),
+                call(Intrisic::Filter, var("l"), "x", var("c"))
+            );
+            assert_eq!(
+                parse("[y for x in l if c]"),
+                call(
+                    Intrisic::Map,
+                    call(Intrisic::Filter, var("l"), "x", var("c")),
+                    "x",
+                    var("y")
+                )
+            )
+        }
+
+        #[test]
+        fn nested() {
+            assert_eq!(
+                parse("[[z for y in x] for x in l]"),
+                call(
+                    Intrisic::Map,
+                    var("l"),
+                    "x",
+                    call(Intrisic::Map, var("x"), "y", var("z"))
+                )
+            )
+        }
+    }
+
+    mod trailing_commas {
+        use super::*;
+
+        #[test]
+        fn lists() {
+            assert_eq!(parse("[1, 2,]"), parse("[1, 2]"));
+            assert_eq!(parse("[\n    1,\n    2,\n]"), parse("[1, 2]"))
+        }
+
+        #[test]
+        fn maps() {
+            assert_eq!(parse("<|a: 1,|>"), parse("<|a: 1|>"));
+            assert_eq!(parse("<|a: 1, \"b\": 2 ,|>"), parse("<|a: 1, \"b\": 2|>"))
+        }
+
+        #[test]
+        fn calls() {
+            assert_eq!(parse("f(1, 2,)"), parse("f(1, 2)"));
+            assert_eq!(parse("f(1,)"), parse("f(1)"))
+        }
+
+        #[test]
+        fn a_lone_comma_is_an_error() {
+            assert!(parse_file::<NoInjectedIntrisics>("[,]").is_err());
+            assert!(parse_file::<NoInjectedIntrisics>("f(,)").is_err());
+            assert!(parse_file::<NoInjectedIntrisics>("[1,,]").is_err())
+        }
+    }
+
+    mod statements {
+        use super::*;
+
+        /// Parse a source, checking it gives the same statements as another
+        fn same_statements(src: &str, expected: &str) {
+            assert_eq!(
+                parse_file::<NoInjectedIntrisics>(src).unwrap(),
+                parse_file::<NoInjectedIntrisics>(expected).unwrap(),
+                "`{src}` was not parsed as `{expected}`"
+            )
+        }
+
+        #[test]
+        fn let_underscore_throws_the_value_away() {
+            same_statements("let _ = 3", "_ = 3");
+            // `_x` is a variable name like any other
+            assert_ne!(
+                parse_file::<NoInjectedIntrisics>("let _x = 3").unwrap(),
+                parse_file::<NoInjectedIntrisics>("_ = 3").unwrap(),
+            )
+        }
+
+        #[test]
+        fn newlines_separate_statements() {
+            same_statements("let x = 1\nx + 2", "let x = 1; x + 2");
+            same_statements("1; 2\n3", "1; 2; 3");
+            same_statements("1;\n2", "1; 2");
+            same_statements("x\n-1", "x; -1");
+        }
+
+        #[test]
+        fn blank_lines_are_ignored() {
+            same_statements("\n1\n\n\n2\n", "1; 2");
+            same_statements("1 // comment\n\n// another\n2", "1; 2");
+        }
+
+        #[test]
+        fn newlines_in_scopes() {
+            same_statements("{\n    let a = 1\n    a\n}", "{let a = 1; a}")
+        }
+
+        #[test]
+        fn multiline_expressions() {
+            same_statements("[1,\n 2,\n 3]", "[1, 2, 3]");
+            same_statements("f(\n    1,\n    2\n)", "f(1, 2)");
+            same_statements("<|\n    a: 1 +\n        2\n|>", "<|a: 1 + 2|>");
+            same_statements("1 +\n2", "1 + 2");
+            same_statements("repeat x = x + 1\nuntil x", "repeat x = x + 1 until x");
+        }
+    }
+
+    mod shebang {
+        use super::*;
+
+        #[test]
+        fn is_ignored() {
+            assert_eq!(
+                parse_file::<NoInjectedIntrisics>("#!/usr/bin/env dices\nlet x = d20; x + 5"),
+                parse_file::<NoInjectedIntrisics>("let x = d20; x + 5")
+            )
+        }
+
+        #[test]
+        fn keeps_the_spans() {
+            let src = "#!/usr/bin/env dices\n3d6";
+            assert_eq!(
+                parse_file::<NoInjectedIntrisics>(src)
+                    .unwrap()
+                    .first()
+                    .span()
+                    .range(),
+                Some(21..24)
+            )
+        }
+
+        #[test]
+        fn only_on_the_first_line() {
+            assert!(parse_file::<NoInjectedIntrisics>("3d6\n#!/usr/bin/env dices").is_err());
+            assert!(parse_file::<NoInjectedIntrisics>(" #!/usr/bin/env dices\n3d6").is_err())
+        }
+    }
+
+    mod custom_operators {
+        use super::*;
+        use crate::{
+            expression::{parse_file_with_operators, CustomOperators, ExpressionCall},
+            intrisics::Intrisic,
+            value::ValueIntrisic,
+        };
+
+        fn operators() -> CustomOperators {
+            let mut ops = CustomOperators::new();
+            ops.insert("<>", "pow").unwrap();
+            ops
+        }
+
+        #[test]
+        fn parsed_as_intrisic_calls() {
+            let exprs =
+                parse_file_with_operators::<NoInjectedIntrisics>("2 <> 3", &operators()).unwrap();
+            assert_eq!(
+                *exprs.first(),
+                ExpressionCall::new(
+                    Value::Intrisic(ValueIntrisic(Intrisic::Pow)).into(),
+                    Box::new([number(2), number(3)])
+                )
+                .into()
+            )
+        }
+
+        #[test]
+        fn precedence() {
+            assert_eq!(
+                parse_file_with_operators::<NoInjectedIntrisics>("1 + 2 <> 3 << 4", &operators())
+                    .unwrap(),
+                parse_file_with_operators::<NoInjectedIntrisics>("(1 + 2) <> 3 << 4", &operators())
+                    .unwrap()
+            );
+            assert_eq!(
+                parse_file_with_operators::<NoInjectedIntrisics>("1 << 2 <> 3", &operators())
+                    .unwrap(),
+                parse_file_with_operators::<NoInjectedIntrisics>("1 << (2 <> 3)", &operators())
+                    .unwrap()
+            )
+        }
+
+        #[test]
+        fn unknown_operators_are_errors() {
+            assert!(parse_file::<NoInjectedIntrisics>("2 <> 3").is_err());
+            assert!(
+                parse_file_with_operators::<NoInjectedIntrisics>("2 <?> 3", &operators()).is_err()
+            )
+        }
+
+        #[test]
+        fn invalid_symbols() {
+            let mut ops = CustomOperators::new();
+            for symbol in ["", "+", "<<", "=", "a", "<a>", "//", "+/*", "<>;"] {
+                assert!(
+                    ops.insert(symbol, "pow").is_err(),
+                    "`{symbol}` should not be accepted"
+                )
+            }
+        }
+    }
+
+    mod patterns {
+        use super::*;
+        use crate::{
+            expression::{ExpressionIs, Span},
+            matcher::Matcher,
+        };
+
+        fn matches(
+            value: Expression<NoInjectedIntrisics>,
+            pattern: &str,
+        ) -> Expression<NoInjectedIntrisics> {
+            ExpressionIs {
+                value: Box::new(value),
+                pattern: pattern.parse::<Matcher<_>>().unwrap(),
+                span: Span::NONE,
+            }
+            .into()
+        }
+
+        #[test]
+        fn parsed_into_matchers() {
+            assert_eq!(parse("1 is [_, 1..6]"), matches(number(1), "[_, 1..6]"));
+            assert_eq!(
+                parse("1 is !(number || null) && _"),
+                matches(number(1), "!(number || null) && _")
+            )
+        }
+
+        #[test]
+        fn binds_looser_than_operators() {
+            assert_eq!(parse("1 + 2 is 3"), parse("(1 + 2) is 3"));
+            assert_eq!(parse("1 | 2 is 3"), parse("(1 | 2) is 3"))
+        }
+
+        #[test]
+        fn is_a_keyword() {
+            assert!(parse_file::<NoInjectedIntrisics>("1 island").is_err());
+            assert!(parse_file::<NoInjectedIntrisics>("let is = 1").is_err())
+        }
+
+        #[test]
+        fn unknown_types_are_rejected() {
+            assert!(parse_file::<NoInjectedIntrisics>("5 is nubmer").is_err());
+            assert!(parse_file::<NoInjectedIntrisics>("5 is [integer, _]").is_err())
+        }
+
+        #[test]
+        fn the_rest_of_the_expression_follows() {
+            assert_eq!(parse("[x is 1..=3, 2]"), parse("[(x is 1..=3), 2]"));
+            assert_eq!(parse("f(x is _)"), parse("f((x is _))"))
+        }
+    }
+
+    mod spans {
+        use super::*;
+        use crate::expression::{bin_ops::BinOp, ExpressionBinOp, ExpressionMemberAccess};
+
+        #[test]
+        fn parsed_expressions_have_spans() {
+            let expr = parse("1 + a.b");
+            assert_eq!(expr.span().range(), Some(0..7));
+            let Expression::BinOp(ExpressionBinOp {
+                expressions: box [one, access],
+                ..
+            }) = &expr
+            else {
+                panic!("The expression should be a sum")
+            };
+            assert_eq!(one.span().range(), Some(0..1));
+            assert_eq!(access.span().range(), Some(4..7));
+            let Expression::MemberAccess(ExpressionMemberAccess { accessed, .. }) = access else {
+                panic!("The second operand should be a member access")
+            };
+            assert_eq!(accessed.span().range(), Some(4..5));
+        }
+
+        #[test]
+        fn parentheses_are_not_in_the_span() {
+            let Expression::BinOp(ExpressionBinOp {
+                expressions: box [sum, _],
+                ..
+            }) = parse("(1 + 2) * 3")
+            else {
+                panic!("The expression should be a product")
+            };
+            assert_eq!(sum.span().range(), Some(1..6));
+        }
+
+        #[test]
+        fn spans_start_at_line_and_column() {
+            // columns count the characters, offsets the bytes
+            let exprs = parse_file::<NoInjectedIntrisics>("1;\n\"é\"; 22").unwrap();
+            let start = exprs[2].span().start().unwrap();
+            assert_eq!((start.line, start.column, start.offset), (2, 6, 9));
+            assert_eq!(start.to_string(), "2:6")
+        }
+
+        #[test]
+        fn constructed_expressions_have_no_span() {
+            let expr: Expression<NoInjectedIntrisics> =
+                ExpressionBinOp::new(BinOp::Add, number(1), number(2)).into();
+            assert_eq!(expr.span().range(), None);
+            assert_eq!(number(1).span().range(), None);
+        }
+    }
+
+    mod size {
+        use super::*;
+
+        #[test]
+        fn repeats_are_multiplied() {
+            assert_eq!(parse("d6 ^ 1000000").estimated_size(), 1_000_000);
+            assert_eq!(parse("1000000d6").estimated_size(), 1_000_000);
+            assert_eq!(parse("(3d6) ^ 1000").estimated_size(), 3_000);
+        }
+
+        #[test]
+        fn small_expressions() {
+            assert_eq!(parse("d20 + 5").estimated_size(), 2);
+            assert_eq!(parse("[1, 2, 3]").estimated_size(), 3);
+            assert_eq!(parse("d6 ^ 0").estimated_size(), 0);
+        }
+
+        #[test]
+        fn unknown_repeats_are_counted_once() {
+            assert_eq!(parse("d6 ^ x").estimated_size(), 2);
+        }
+    }
+
+    mod format {
+        use crate::{
+            expression::span::Span, ident::IdentStr, intrisics::Intrisic, value::ValueIntrisic,
+        };
+
+        use super::*;
+
+        fn format_source(src: &str) -> Result<String, crate::expression::ParseError> {
+            crate::expression::format_source(src, IdentStr::new("std").unwrap())
+        }
+
+        const SOURCES: &[&str] = &[
+            "1+2*3",
+            "(1 + 2) * 3",
+            "1 - (2 - 3)",
+            "- - 3",
+            "-(3 + 4)",
+            "3d6 kh 2",
+            "d20+5",
+            "(1+1)d(2+4)",
+            "x d y",
+            "d x",
+            "dx",
+            "d!6 + 3d!x",
+            "4d6 r [1, 2] kh 3",
+            "d20 r (|x| x is 1..=2)",
+            "(d6)d!(1 + 3)",
+            "d6 ^ 3 ^ 2",
+            "let x = y = 3",
+            "_ = 5",
+            "x.a[1 + 1].0 = 2",
+            "f(1, [2, 3], <|a: 1, \"b c\": 2|>)(4)",
+            "x.\"not an ident\"",
+            "|a, b| a * b",
+            "|x| (y = x)",
+            "(|x| x)(3)",
+            "{let x = 3; x + 1}",
+            "{}",
+            "repeat x = x + 1 until x / 5",
+            "1 | 2 xor 3 & 4 << 5",
+            "(1 | 2) & 3",
+            "~x ~ [1]",
+            "[x * 2 for x in l if x]",
+            "\"a\\\"b\" ~ null ~ true",
+            "1; 2;",
+            "[[y * x for y in x] for x in l]",
+            "[y for y in 3d6]",
+            "<|(k ~ \"x\"): 1, a: (k)|>",
+            "x is [_, 1..=6] || null",
+            "(x is _) | 1",
+            "x+1 is number && !0",
+            "f(x is <|a: _, \"b c\": [string]|>)",
+        ];
+
+        #[test]
+        fn canonical() {
+            assert_eq!(format_source("1+2*3").unwrap(), "1 + 2 * 3");
+            assert_eq!(format_source("(1+2)*3").unwrap(), "(1 + 2) * 3");
+            assert_eq!(format_source("d20+5").unwrap(), "d20 + 5");
+            assert_eq!(format_source("3 d 6").unwrap(), "3d6");
+            assert_eq!(format_source("d x").unwrap(), "d x");
+            assert_eq!(format_source("2 d! 6").unwrap(), "2d!6");
+            assert_eq!(format_source("let x=1;x").unwrap(), "let x = 1; x");
+        }
+
+        #[test]
+        fn intrisics_are_reached_from_the_std_name() {
+            let expr: Expression<NoInjectedIntrisics> =
+                Expression::Const(Value::Intrisic(ValueIntrisic(Intrisic::Sum)), Span::NONE);
+            assert_eq!(expr.to_string(), "std.intrisics.sum");
+            assert_eq!(
+                expr.source(IdentStr::new("lib").unwrap()).to_string(),
+                "lib.intrisics.sum"
+            );
+        }
+
+        #[test]
+        fn preserves_the_expression() {
+            for src in SOURCES {
+                let formatted = format_source(src).unwrap();
+                assert_eq!(
+                    parse_file::<NoInjectedIntrisics>(&formatted),
+                    parse_file::<NoInjectedIntrisics>(src),
+                    "Formatting `{src}` into `{formatted}` changed its meaning"
+                )
+            }
+        }
+
+        #[test]
+        fn idempotent() {
+            for src in SOURCES {
+                let formatted = format_source(src).unwrap();
+                assert_eq!(
+                    format_source(&formatted).unwrap(),
+                    formatted,
+                    "Formatting `{src}` is not idempotent"
+                )
+            }
+        }
+    }
+}
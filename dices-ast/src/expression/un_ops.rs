@@ -1,6 +1,6 @@
 //! unary operations
 
-use super::Expression;
+use super::{Expression, Span};
 
 /// An unary operator
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -12,6 +12,10 @@ pub enum UnOp {
     Neg,
     /// `d`: Throw a dice
     Dice,
+    /// `~`: Bitwise not of a number
+    BitNot,
+    /// `d!`: Throw an exploding dice, rolling again and adding while it gives the highest face
+    Explode,
 }
 
 /// An expression made with an unary operator
@@ -24,6 +28,8 @@ pub enum UnOp {
 pub struct ExpressionUnOp<InjectedIntrisic> {
     pub op: UnOp,
     pub expression: Box<Expression<InjectedIntrisic>>,
+    /// Where the operation is in the source
+    pub span: Span,
 }
 
 impl<InjectedIntrisic> ExpressionUnOp<InjectedIntrisic> {
@@ -31,6 +37,7 @@ impl<InjectedIntrisic> ExpressionUnOp<InjectedIntrisic> {
         Self {
             op,
             expression: Box::new(expression),
+            span: Span::NONE,
         }
     }
 }
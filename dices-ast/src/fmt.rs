@@ -50,6 +50,26 @@ pub fn quoted_if_not_ident(s: &str, f: &mut Formatter<'_>) -> std::fmt::Result {
     }
 }
 
+#[cfg(feature = "pretty")]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// Options changing how the values are prettified
+pub struct PrettyOptions {
+    /// Numbers with a magnitude above this are written in scientific notation, as `1.2e9`
+    pub scientific_above: Option<crate::value::ValueNumber>,
+    /// How many significant digits are kept in the scientific notation
+    pub significant_digits: usize,
+}
+
+#[cfg(feature = "pretty")]
+impl Default for PrettyOptions {
+    fn default() -> Self {
+        Self {
+            scientific_above: None,
+            significant_digits: 3,
+        }
+    }
+}
+
 #[cfg(feature = "pretty")]
 #[derive(Clone, Copy)]
 /// Structure that prettify in a comma followed by an optional line
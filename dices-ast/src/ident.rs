@@ -1,14 +1,50 @@
 //! Definitions about `dices` identifiers
 
 use derive_more::derive::{AsRef, Deref, Display};
-use lazy_regex::{regex, Lazy, Regex};
 use phf::phf_set;
 
-static IDENT_RE: &Lazy<Regex> = regex!(r"^(?:[a-zA-Z]|_+[a-zA-Z0-9])[_a-zA-Z0-9]*$");
-static KEYWORDS: phf::Set<&'static str> = phf_set!("d", "kh", "kl", "rh", "rl", "let");
+static KEYWORDS: phf::Set<&'static str> = phf_set!(
+    "d", "kh", "kl", "rh", "rl", "r", "xor", "is", "let", "repeat", "until", "for", "in", "if"
+);
 
 pub fn is_valid_ident(s: &str) -> bool {
-    IDENT_RE.is_match(s) && !KEYWORDS.contains(s)
+    matches_ident_pattern(s) && !is_keyword(s)
+}
+
+/// Check if a string matches `r"^(?:[a-zA-Z]|_+[a-zA-Z0-9])[_a-zA-Z0-9]*$"`
+///
+/// This is checked by hand to avoid depending on a regex engine
+fn matches_ident_pattern(s: &str) -> bool {
+    let unprefixed = s.trim_start_matches('_');
+    let mut chars = unprefixed.chars();
+    let Some(first) = chars.next() else {
+        return false;
+    };
+    let first_ok = if unprefixed.len() == s.len() {
+        first.is_ascii_alphabetic()
+    } else {
+        first.is_ascii_alphanumeric()
+    };
+    first_ok && chars.all(|ch| ch == '_' || ch.is_ascii_alphanumeric())
+}
+
+#[cfg(test)]
+#[test]
+fn ident_pattern() {
+    for valid in ["a", "abc", "a_1", "A2", "_a", "__1", "_a_", "x__"] {
+        assert!(matches_ident_pattern(valid), "`{valid}` should be valid")
+    }
+    for invalid in ["", "_", "___", "1a", "a-b", "a b", "è", "_è"] {
+        assert!(
+            !matches_ident_pattern(invalid),
+            "`{invalid}` should be invalid"
+        )
+    }
+}
+
+/// Check if a string is one of the reserved keywords
+pub fn is_keyword(s: &str) -> bool {
+    KEYWORDS.contains(s)
 }
 
 /// A string that is guarantee to be a valid identifier (`r"(?:[a-zA-Z]|_+[a-zA-Z0-9])[_a-zA-Z0-9]*"`)
@@ -26,7 +62,7 @@ impl IdentStr {
             return None;
         }
         Some(unsafe {
-            // SAFETY: we checked that IDENT_RE matches
+            // SAFETY: we checked that the string is a valid identifier
             Self::new_unchecked(s)
         })
     }
@@ -46,7 +82,7 @@ impl IdentStr {
             return Err(s);
         }
         Ok(unsafe {
-            // SAFETY: we checked that IDENT_RE matches
+            // SAFETY: we checked that the string is a valid identifier
             Self::new_boxed_unchecked(s)
         })
     }
@@ -40,10 +40,88 @@ pub enum Intrisic<Injected> {
     ToString,
     /// Parse a string into a value
     Parse,
+    /// Convert a value to a string of source that evaluates back to the same value
+    Repr,
+    /// Unwrap the only element of a list, leaving the other values untouched
+    Scalar,
+
+    /// Check if a value matches a pattern, given as a string
+    ///
+    /// `value is pattern` is a shorthand for `matches(value, "pattern")`
+    Matches,
 
     /// Call its first parameter with the arguments given by the second, converted to a list
     Call,
 
+    /// Get a value from a map, falling back to a default if the key is missing
+    Get,
+    /// Get a value nested in maps, following a path of dot separated keys
+    GetPath,
+    /// Build a copy of a map with a nested value replaced, following a path of dot separated keys
+    SetPath,
+    /// Freeze a map, so that it cannot be changed by `set` expressions
+    Freeze,
+
+    /// Call a function, checking if any of the dice it rolled landed on its highest or lowest face
+    CritCheck,
+    /// Call a function again, using one of the rerolls left
+    Reroll,
+    /// Net the opposing labels rolled with narrative dice
+    Resolve,
+    /// Lower the numbers above a maximum to it, distributing over lists and maps
+    Cap,
+    /// Raise the numbers below a minimum to it, distributing over lists and maps
+    FloorAt,
+    /// Roll two dice and keep the highest, reporting both
+    Advantage,
+    /// Roll two dice and keep the lowest, reporting both
+    Disadvantage,
+    /// Call a function many times, counting how many times each result came out
+    Dist,
+
+    /// Call a function on every element of a list, collecting the results
+    Map,
+    /// Keep only the elements of a list for which a function returns a truthy value
+    Filter,
+    /// Sort a list by the keys computed by a function
+    SortBy,
+    /// Sort a list, optionally by the keys computed by a function
+    Sort,
+    /// Reverse the order of a list
+    Reverse,
+    /// Group the elements of a list in a map, by the keys computed by a function
+    GroupBy,
+    /// Draw distinct elements from a list, without replacement
+    Sample,
+    /// Count how many times each value appears in a list
+    Tally,
+    /// Multiply the numbers in a list
+    Product,
+    /// Average of the numbers in a list, truncated toward zero
+    Mean,
+    /// Split a list in the elements for which a function returns a truthy value, and the others
+    Partition,
+    /// The first elements of a list, up to a given number
+    Take,
+    /// A list without its first elements, up to a given number
+    Drop,
+    /// The first element of a list, or `null` if it is empty
+    First,
+    /// The last element of a list, or `null` if it is empty
+    Last,
+
+    /// Remainder of the euclidean division, that is never negative
+    Mod,
+    /// Raise a number to a non negative power
+    Pow,
+    /// Greatest common divisor of two numbers
+    Gcd,
+    /// Least common multiple of two numbers
+    Lcm,
+
+    /// Print its params, one per line, on the output of the engine
+    Print,
+
     /// Convert its param to a json string
     ToJson,
     /// Convert its param from a json string
@@ -55,6 +133,10 @@ pub enum Intrisic<Injected> {
     SaveRNG,
     /// Restore the RNG state
     RestoreRNG,
+    /// Call a closure, giving a token with its source that can replay the call with the same rolls
+    ReplayToken,
+    /// Replay a call from its token
+    Replay,
 
     /// Injected intrisic
     ///
@@ -123,12 +205,144 @@ repetitive_impl! {
     ToList <=> "to_list",
     ToString <=> "to_string",
     Parse <=> "parse",
+    Repr <=> "repr",
+    Scalar <=> "scalar",
+    Matches <=> "matches",
     Call <=> "call",
+    Get <=> "get",
+    GetPath <=> "get_path",
+    SetPath <=> "set_path",
+    Map <=> "map",
+    Filter <=> "filter",
+    SortBy <=> "sort_by",
+    Sort <=> "sort",
+    Reverse <=> "reverse",
+    GroupBy <=> "group_by",
+    Sample <=> "sample",
+    Tally <=> "tally",
+    Freeze <=> "freeze",
+    CritCheck <=> "crit_check",
+    Reroll <=> "reroll",
+    Resolve <=> "resolve",
+    Cap <=> "cap",
+    FloorAt <=> "floor_at",
+    Advantage <=> "advantage",
+    Disadvantage <=> "disadvantage",
+    Dist <=> "dist",
+    Product <=> "product",
+    Mean <=> "mean",
+    Partition <=> "partition",
+    Take <=> "take",
+    Drop <=> "drop",
+    First <=> "first",
+    Last <=> "last",
+    Mod <=> "mod",
+    Pow <=> "pow",
+    Gcd <=> "gcd",
+    Lcm <=> "lcm",
+    Print <=> "print",
     ToJson <=> "to_json",
     FromJson <=> "from_json",
     SeedRNG <=> "seed_rng",
     SaveRNG <=> "save_rng",
-    RestoreRNG <=> "restore_rng"
+    RestoreRNG <=> "restore_rng",
+    ReplayToken <=> "replay_token",
+    Replay <=> "replay"
+}
+
+/// Give each intrisic its stable identifier
+///
+/// This table is kept apart from the names on purpose: the names can change, as long as the
+/// identifiers stay the same.
+macro_rules! stable_ids {
+    (
+        $(
+            $variant:ident => $id:literal
+        ),*
+    ) => {
+        impl<Injected> Intrisic<Injected>
+        where
+            Injected: InjectedIntr,
+        {
+            /// The stable identifier of this intrisic
+            ///
+            /// This is used in place of the variant when serializing, so it does not
+            /// depend on the order of the enum. It must never change between versions, even if the
+            /// intrisic is renamed.
+            pub fn id(&self) -> &'static str {
+                match self {
+                    $(
+                        Self::$variant => $id,
+                    )*
+                    Intrisic::Injected(injected) => injected.id(),
+                }
+            }
+
+            /// Get the intrisic from its stable identifier
+            pub fn from_id(id: &str) -> Option<Self> {
+                Some(match id {
+                    $(
+                        $id => Self::$variant,
+                    )*
+                    _ => return Injected::from_id(id).map(Intrisic::Injected),
+                })
+            }
+        }
+    };
+}
+
+stable_ids! {
+    Sum => "sum",
+    Join => "join",
+    Mult => "mult",
+    ToNumber => "to_number",
+    ToList => "to_list",
+    ToString => "to_string",
+    Parse => "parse",
+    Repr => "repr",
+    Scalar => "scalar",
+    Matches => "matches",
+    Call => "call",
+    Get => "get",
+    GetPath => "get_path",
+    SetPath => "set_path",
+    Map => "map",
+    Filter => "filter",
+    SortBy => "sort_by",
+    Sort => "sort",
+    Reverse => "reverse",
+    GroupBy => "group_by",
+    Sample => "sample",
+    Tally => "tally",
+    Freeze => "freeze",
+    CritCheck => "crit_check",
+    Reroll => "reroll",
+    Resolve => "resolve",
+    Cap => "cap",
+    FloorAt => "floor_at",
+    Advantage => "advantage",
+    Disadvantage => "disadvantage",
+    Dist => "dist",
+    Product => "product",
+    Mean => "mean",
+    Partition => "partition",
+    Take => "take",
+    Drop => "drop",
+    First => "first",
+    Last => "last",
+    Mod => "mod",
+    Pow => "pow",
+    Gcd => "gcd",
+    Lcm => "lcm",
+    // this also reads the `print` of the REPL, encoded before it was renamed `pretty_print`
+    Print => "print",
+    ToJson => "to_json",
+    FromJson => "from_json",
+    SeedRNG => "seed_rng",
+    SaveRNG => "save_rng",
+    RestoreRNG => "restore_rng",
+    ReplayToken => "replay_token",
+    Replay => "replay"
 }
 
 impl<Injected> Intrisic<Injected>
@@ -146,6 +360,33 @@ where
     }
 }
 
+#[cfg(test)]
+#[test]
+fn all_ids_are_unique() {
+    // the ids are a table of their own, so nothing but this test stops two of them from colliding
+    let ids: std::collections::BTreeSet<_> = Intrisic::<NoInjectedIntrisics>::iter()
+        .into_iter()
+        .map(|intrisic| intrisic.id())
+        .collect();
+    assert_eq!(
+        ids.len(),
+        Intrisic::<NoInjectedIntrisics>::iter().into_iter().count()
+    )
+}
+
+#[cfg(test)]
+#[test]
+fn all_ids_roundtrip() {
+    for intrisic in Intrisic::<NoInjectedIntrisics>::iter() {
+        let id = intrisic.id();
+        assert_eq!(
+            Intrisic::<NoInjectedIntrisics>::from_id(id),
+            Some(intrisic),
+            "Intrisic `{id}` did not roundtrip"
+        )
+    }
+}
+
 #[cfg(test)]
 #[test]
 fn all_names_roundtrip() {
@@ -158,7 +399,10 @@ fn all_names_roundtrip() {
     }
 }
 
-pub trait InjectedIntr: Sized + Clone + 'static + Hash {
+/// Intrisics injected by the host of the engine
+///
+/// They must be totally ordered, so that values containing them can be sorted.
+pub trait InjectedIntr: Sized + Clone + 'static + Hash + Ord {
     /// The data used by the injected intrisics
     type Data;
     /// The error type given by calling this intrisic
@@ -170,6 +414,17 @@ pub trait InjectedIntr: Sized + Clone + 'static + Hash {
     fn name(&self) -> &'static str;
     /// Get the intrisic from the name
     fn named(name: &str) -> Option<Self>;
+    /// Give a stable identifier for this intrisic, used when serializing
+    ///
+    /// It must never change between versions. By default it is the name, so an intrisic that is
+    /// renamed must implement this to keep its old identifier.
+    fn id(&self) -> &'static str {
+        self.name()
+    }
+    /// Get the intrisic from the stable identifier
+    fn from_id(id: &str) -> Option<Self> {
+        Self::named(id)
+    }
     /// Give all the paths in the std library this intrisic should be injected to
     fn std_paths(&self) -> &[&[&'static str]] {
         // default to not injecting anywhere
@@ -266,7 +521,7 @@ where
         &self,
         encoder: &mut E,
     ) -> Result<(), bincode::error::EncodeError> {
-        self.name().encode(encoder)
+        self.id().encode(encoder)
     }
 }
 
@@ -278,9 +533,9 @@ where
     fn decode<D: bincode::de::Decoder>(
         decoder: &mut D,
     ) -> Result<Self, bincode::error::DecodeError> {
-        let name: Cow<str> = bincode::Decode::decode(decoder)?;
-        Self::named(&*name).ok_or_else(|| {
-            bincode::error::DecodeError::OtherString(format!("Unknow intrisic {name}"))
+        let id: Cow<str> = bincode::Decode::decode(decoder)?;
+        Self::from_id(&id).ok_or_else(|| {
+            bincode::error::DecodeError::OtherString(format!("Unknow intrisic {id}"))
         })
     }
 }
@@ -293,9 +548,9 @@ where
     fn borrow_decode<D: bincode::de::BorrowDecoder<'de>>(
         decoder: &mut D,
     ) -> Result<Self, bincode::error::DecodeError> {
-        let name: Cow<str> = bincode::BorrowDecode::borrow_decode(decoder)?;
-        Self::named(&*name).ok_or_else(|| {
-            bincode::error::DecodeError::OtherString(format!("Unknow intrisic {name}"))
+        let id: Cow<str> = bincode::BorrowDecode::borrow_decode(decoder)?;
+        Self::from_id(&id).ok_or_else(|| {
+            bincode::error::DecodeError::OtherString(format!("Unknow intrisic {id}"))
         })
     }
 }
@@ -317,7 +572,7 @@ mod serde {
         where
             S: serde::Serializer,
         {
-            self.name().serialize(serializer)
+            self.id().serialize(serializer)
         }
     }
     impl<'de, II> Deserialize<'de> for Intrisic<II>
@@ -328,9 +583,9 @@ mod serde {
         where
             D: serde::Deserializer<'de>,
         {
-            let name = Cow::<str>::deserialize(deserializer)?;
-            Self::named(&name).ok_or_else(|| {
-                <D::Error as serde::de::Error>::custom(format!("Unknow intrisic {name}"))
+            let id = Cow::<str>::deserialize(deserializer)?;
+            Self::from_id(&id).ok_or_else(|| {
+                <D::Error as serde::de::Error>::custom(format!("Unknow intrisic {id}"))
             })
         }
     }
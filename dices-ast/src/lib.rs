@@ -7,6 +7,9 @@
 pub mod fmt;
 pub mod ident;
 pub mod intrisics;
+#[cfg(feature = "parse_expression")]
+pub mod symbols;
+pub mod tokens;
 
 pub mod value;
 pub use value::Value;
@@ -16,9 +19,7 @@ pub mod expression;
 pub use expression::parse_file;
 pub use expression::Expression;
 
-#[cfg(feature = "matcher")]
 pub mod matcher;
-#[cfg(feature = "matcher")]
 pub use matcher::Matcher;
 
 pub mod version {
@@ -30,12 +31,48 @@ pub mod version {
     /// Identifies the version of the AST used
     #[derive(Debug, Clone)]
     #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
-    #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+    #[cfg_attr(feature = "bincode", derive(bincode::Encode))]
     pub struct Version {
         pub major: u16,
         pub minor: u16,
         pub patch: u16,
+        /// The optional features the crate was built with
+        ///
+        /// Versions recorded before this field was added (before 0.4) have no features, both
+        /// with serde and with bincode.
+        #[cfg_attr(feature = "serde", serde(default))]
+        pub features: Features,
     }
+
+    /// The first version recording the features
+    #[cfg(feature = "bincode")]
+    const FEATURES_SINCE: (u16, u16) = (0, 4);
+
+    #[cfg(feature = "bincode")]
+    impl bincode::Decode for Version {
+        fn decode<D: bincode::de::Decoder>(
+            decoder: &mut D,
+        ) -> Result<Self, bincode::error::DecodeError> {
+            let major = u16::decode(decoder)?;
+            let minor = u16::decode(decoder)?;
+            let patch = u16::decode(decoder)?;
+            // older versions end here: they are read anyway, so that the incompatibility can be
+            // reported by `is_compatible_with` instead of failing to decode
+            let features = if (major, minor) >= FEATURES_SINCE {
+                Features::decode(decoder)?
+            } else {
+                Features::default()
+            };
+            Ok(Self {
+                major,
+                minor,
+                patch,
+                features,
+            })
+        }
+    }
+    #[cfg(feature = "bincode")]
+    bincode::impl_borrow_decode! {Version}
     impl Version {
         /// Check if this version is compatible with the remote one
         pub fn is_compatible_with(&self, remote: &Self) -> Result<(), IncompatibilityReason> {
@@ -52,21 +89,208 @@ pub mod version {
                 });
             }
             // patch cannot add incompatibilities, so no need to check
+
+            // what was built with a feature that affects the format could need it to be read back
+            if let Some(feature) = remote
+                .features
+                .affecting_format()
+                .find(|feature| !self.features.affecting_format().any(|f| f == *feature))
+            {
+                return Err(IncompatibilityReason::MissingLocalFeature { feature });
+            }
             Ok(())
         }
     }
 
+    /// The optional features of the AST crate
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    #[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+    // features added later are missing from the older versions.
+    // bincode cannot default them, so adding a feature needs a minor version bump
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub struct Features {
+        pub parse_value: bool,
+        pub parse_expression: bool,
+        pub matcher: bool,
+        pub parse_matcher: bool,
+        pub rand: bool,
+        pub serde: bool,
+        pub bincode: bool,
+        pub pretty: bool,
+        pub sync: bool,
+    }
+    impl Features {
+        /// The features this build of the crate has
+        pub const LOCAL: Self = Self {
+            parse_value: cfg!(feature = "parse_value"),
+            parse_expression: cfg!(feature = "parse_expression"),
+            // matchers are always available, the feature is kept only for compatibility
+            matcher: true,
+            parse_matcher: cfg!(feature = "parse_matcher"),
+            rand: cfg!(feature = "rand"),
+            serde: cfg!(feature = "serde"),
+            bincode: cfg!(feature = "bincode"),
+            pretty: cfg!(feature = "pretty"),
+            sync: cfg!(feature = "sync"),
+        };
+
+        /// The names of the enabled features
+        pub fn enabled(&self) -> impl Iterator<Item = &'static str> {
+            [
+                (self.parse_value, "parse_value"),
+                (self.parse_expression, "parse_expression"),
+                (self.matcher, "matcher"),
+                (self.parse_matcher, "parse_matcher"),
+                (self.rand, "rand"),
+                (self.serde, "serde"),
+                (self.bincode, "bincode"),
+                (self.pretty, "pretty"),
+                (self.sync, "sync"),
+            ]
+            .into_iter()
+            .filter_map(|(enabled, name)| enabled.then_some(name))
+        }
+
+        /// The names of the enabled features that change what data can be read back
+        ///
+        /// The others (e.g. `pretty` or `rand`) only add functionalities, and do not matter
+        /// when exchanging values and expressions. `matcher` is always enabled, so it cannot
+        /// tell two builds apart.
+        pub fn affecting_format(&self) -> impl Iterator<Item = &'static str> {
+            [(self.bincode, "bincode")]
+                .into_iter()
+                .filter_map(|(enabled, name)| enabled.then_some(name))
+        }
+    }
+
     #[derive(Debug, Clone, Display, Error)]
     pub enum IncompatibilityReason {
         #[display("The local major version ({local}) is diffent from the remote one ({remote})")]
         Major { local: u16, remote: u16 },
         #[display("The local minor version ({local}) is greather of the remote one ({remote})")]
         Minor { local: u16, remote: u16 },
+        #[display("The remote was built with the feature `{feature}`, that is missing locally")]
+        MissingLocalFeature {
+            #[error(not(source))]
+            feature: &'static str,
+        },
     }
 
     pub const VERSION: Version = Version {
         major: unwrap_ctx!(parse_u16(env!("CARGO_PKG_VERSION_MAJOR"))),
         minor: unwrap_ctx!(parse_u16(env!("CARGO_PKG_VERSION_MINOR"))),
         patch: unwrap_ctx!(parse_u16(env!("CARGO_PKG_VERSION_PATCH"))),
+        features: Features::LOCAL,
     };
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn same_build_is_compatible() {
+            assert!(VERSION.is_compatible_with(&VERSION).is_ok())
+        }
+
+        #[test]
+        fn missing_feature_is_incompatible() {
+            let local = Version {
+                features: Features {
+                    bincode: false,
+                    ..Features::LOCAL
+                },
+                ..VERSION
+            };
+            let remote = Version {
+                features: Features {
+                    bincode: true,
+                    ..Features::LOCAL
+                },
+                ..VERSION
+            };
+            assert!(matches!(
+                local.is_compatible_with(&remote),
+                Err(IncompatibilityReason::MissingLocalFeature { feature: "bincode" })
+            ));
+            // the remote can read what the local made
+            assert!(remote.is_compatible_with(&local).is_ok())
+        }
+
+        #[test]
+        fn features_not_affecting_format_are_ignored() {
+            let local = Version {
+                features: Features {
+                    pretty: false,
+                    serde: false,
+                    ..Features::LOCAL
+                },
+                ..VERSION
+            };
+            let remote = Version {
+                features: Features {
+                    pretty: true,
+                    serde: true,
+                    ..Features::LOCAL
+                },
+                ..VERSION
+            };
+            assert!(local.is_compatible_with(&remote).is_ok())
+        }
+
+        #[cfg(feature = "serde")]
+        #[test]
+        fn versions_without_features_are_read() {
+            use crate::value::serde::{deserialize_from_value, serialize_to_value};
+
+            #[derive(serde::Serialize)]
+            struct OldVersion {
+                major: u16,
+                minor: u16,
+                patch: u16,
+            }
+
+            let old = serialize_to_value::<_, crate::intrisics::NoInjectedIntrisics>(OldVersion {
+                major: 1,
+                minor: 2,
+                patch: 3,
+            })
+            .unwrap();
+            let version: Version = deserialize_from_value(old).unwrap();
+            assert_eq!((version.major, version.minor, version.patch), (1, 2, 3));
+            assert_eq!(version.features, Features::default())
+        }
+
+        #[cfg(feature = "bincode")]
+        #[test]
+        fn features_survive_encoding() {
+            let remote = Version {
+                features: Features {
+                    pretty: true,
+                    ..Features::LOCAL
+                },
+                ..VERSION
+            };
+            let encoded = bincode::encode_to_vec(&remote, bincode::config::standard()).unwrap();
+            let (decoded, _): (Version, _) =
+                bincode::decode_from_slice(&encoded, bincode::config::standard()).unwrap();
+            assert_eq!(decoded.features, remote.features)
+        }
+
+        #[cfg(feature = "bincode")]
+        #[test]
+        fn old_encoded_versions_are_read() {
+            // the header written by 0.3.1, before the features were recorded
+            let encoded =
+                bincode::encode_to_vec((0u16, 3u16, 1u16), bincode::config::standard()).unwrap();
+            let (old, _): (Version, _) =
+                bincode::decode_from_slice(&encoded, bincode::config::standard()).unwrap();
+            assert_eq!((old.major, old.minor, old.patch), (0, 3, 1));
+            assert_eq!(old.features, Features::default());
+            assert!(matches!(
+                VERSION.is_compatible_with(&old),
+                Err(IncompatibilityReason::Minor { .. })
+            ))
+        }
+    }
 }
@@ -1,7 +1,17 @@
-use crate::value::*;
-use std::collections::BTreeMap;
+use crate::{fmt::quoted_if_not_ident, intrisics::InjectedIntr, value::*};
+use std::{collections::BTreeMap, fmt::Display};
 
-#[derive(Debug, Clone, Hash)]
+use itertools::Itertools;
+
+/// A pattern checking if a value has a given shape
+///
+/// This is the right side of the `is` operator.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "bincode",
+    derive(bincode::Decode, bincode::Encode,),
+    bincode(bounds = "InjectedIntrisic: crate::intrisics::InjectedIntr")
+)]
 pub enum Matcher<InjectedIntrisic> {
     Exact(Value<InjectedIntrisic>),
     List(Box<[Matcher<InjectedIntrisic>]>),
@@ -14,6 +24,8 @@ pub enum Matcher<InjectedIntrisic> {
     And(Box<[Matcher<InjectedIntrisic>; 2]>),
     Or(Box<[Matcher<InjectedIntrisic>; 2]>),
     Not(Box<Matcher<InjectedIntrisic>>),
+    /// Match any value of the given type
+    Type(ValueType),
     Any,
     None,
 }
@@ -64,11 +76,63 @@ impl<InjectedIntrisic> Matcher<InjectedIntrisic> {
             Matcher::And(box [a, b]) => a.is_match(v) && b.is_match(v),
             Matcher::Or(box [a, b]) => a.is_match(v) || b.is_match(v),
             Matcher::Not(box a) => !a.is_match(v),
+            Matcher::Type(ty) => v.value_type() == *ty,
             Matcher::Any => true,
             Matcher::None => false,
         }
     }
 }
 
+impl<InjectedIntrisic: InjectedIntr> Display for Matcher<InjectedIntrisic> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        /// Display a matcher, putting it in parentheses if it's a binary one
+        struct Operand<'m, II>(&'m Matcher<II>);
+        impl<II: InjectedIntr> Display for Operand<'_, II> {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                match self.0 {
+                    m @ (Matcher::And(_) | Matcher::Or(_)) => write!(f, "({m})"),
+                    m => write!(f, "{m}"),
+                }
+            }
+        }
+        struct KeyMatcher<'m, II>((&'m str, &'m Matcher<II>));
+        impl<II: InjectedIntr> Display for KeyMatcher<'_, II> {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                let (key, matcher) = self.0;
+                quoted_if_not_ident(key, f)?;
+                write!(f, ": {matcher}")
+            }
+        }
+
+        match self {
+            Matcher::Exact(v) => write!(f, "{v}"),
+            Matcher::List(matchers) => write!(f, "[{}]", matchers.iter().format(", ")),
+            Matcher::Map(matchers) => {
+                write!(
+                    f,
+                    "<|{}|>",
+                    matchers
+                        .iter()
+                        .map(|(k, m)| KeyMatcher((k, m)))
+                        .format(", ")
+                )
+            }
+            Matcher::Range {
+                start,
+                end,
+                inclusive,
+            } => write!(f, "{start}{}{end}", if *inclusive { "..=" } else { ".." }),
+            Matcher::And(box [a, b]) => write!(f, "{} && {}", Operand(a), Operand(b)),
+            Matcher::Or(box [a, b]) => write!(f, "{a} || {b}"),
+            Matcher::Not(box a) => write!(f, "!{}", Operand(a)),
+            Matcher::Type(ty) => write!(f, "{ty}"),
+            Matcher::Any => write!(f, "_"),
+            Matcher::None => write!(f, "!_"),
+        }
+    }
+}
+
 #[cfg(feature = "parse_matcher")]
 mod parse;
+#[cfg(feature = "parse_expression")]
+pub(crate) use parse::parse_prefix;
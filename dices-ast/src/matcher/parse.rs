@@ -26,6 +26,7 @@ peg::parser! {
                     } else {
                         Matcher::Exact(v)
                     } }
+                    / t:ident() {? ValueType::from_name(t).map(Matcher::Type).ok_or("type name") }
                     / "_" { Matcher::Any }
                     / "(" _ m:matcher() _ ")" { m }
                 ) {a}
@@ -37,6 +38,10 @@ peg::parser! {
                 "!" _ a:@ { Matcher::Not(Box::new(a)) }
             }
 
+        /// A matcher at the start of the input, followed by anything else, with where it ends
+        pub rule prefix<InjectedIntrisic>() -> (Matcher<InjectedIntrisic>, usize)
+            = m:matcher() end:position!() [_]* { (m, end) }
+
         /// A `dices` serialized value
         rule value<InjectedIntrisic>() -> Value<InjectedIntrisic>
             = v: null()    { v.into() }
@@ -170,6 +175,14 @@ peg::parser! {
     }
 }
 
+/// Parse the matcher at the start of `src`, giving it and the length of its source
+#[cfg(feature = "parse_expression")]
+pub(crate) fn parse_prefix<InjectedIntrisic>(
+    src: &str,
+) -> Result<(Matcher<InjectedIntrisic>, usize), ParseError<LineCol>> {
+    matcher::prefix(src)
+}
+
 impl<InjectedIntrisic> FromStr for Matcher<InjectedIntrisic> {
     type Err = ParseError<LineCol>;
 
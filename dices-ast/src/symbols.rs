@@ -0,0 +1,427 @@
+//! Extraction of the variables defined and used in a source
+//!
+//! This is meant for editor integrations (go-to-definition, rename, ...). The
+//! source is parsed, and the variables are resolved on the expressions in the
+//! order the engine evaluates them, following the scoping of the language:
+//! - `let` definitions are visible after their value is evaluated, up to the end of
+//!   the enclosing `{}` or closure
+//! - closure parameters are visible in the body of the closure
+//! - the variable of a comprehension is visible in its body and condition
+//!
+//! The expressions do not keep the spans of the names they define, so those are
+//! found by [tokenizing](crate::tokens) the source of the expression.
+
+use std::ops::Range;
+
+use itertools::Itertools;
+
+use crate::{
+    expression::{
+        bin_ops::EvalOrder, comprehension, list_conversion, parse_file, set::MemberReceiver,
+        Expression, ExpressionBinOp, ExpressionCall, MapKey, ParseError, Receiver, Span,
+    },
+    ident::IdentStr,
+    intrisics::NoInjectedIntrisics,
+    tokens::{tokenize, Token, TokenKind},
+};
+
+/// A variable in the source
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Symbol {
+    /// The name of the variable
+    pub name: Box<IdentStr>,
+    /// The span of the definition, if it is defined in the source
+    ///
+    /// Variables coming from the environment (like the ones in the prelude) have no definition
+    pub definition: Option<Range<usize>>,
+    /// The spans where the variable is referenced
+    pub references: Vec<Range<usize>>,
+}
+
+/// Find all the variables defined and used in a source
+///
+/// Each definition gives a different symbol, even if they share the same name.
+/// References to variables not defined in the source are grouped by name in
+/// symbols with no definition. The symbols are given in the order they first
+/// appear in the source.
+pub fn symbols(src: &str) -> Result<Vec<Symbol>, ParseError> {
+    let exprs = parse_file::<NoInjectedIntrisics>(src)?;
+
+    let mut resolver = Resolver {
+        src,
+        symbols: vec![],
+        visible: vec![],
+    };
+    for expr in exprs.iter() {
+        resolver.expression(expr)
+    }
+
+    let mut symbols = resolver.symbols;
+    for symbol in &mut symbols {
+        symbol.references.sort_by_key(|span| span.start)
+    }
+    symbols.sort_by_key(|symbol| {
+        symbol
+            .definition
+            .iter()
+            .chain(symbol.references.first())
+            .map(|span| span.start)
+            .min()
+    });
+    Ok(symbols)
+}
+
+struct Resolver<'s> {
+    src: &'s str,
+    symbols: Vec<Symbol>,
+    /// The symbols that can be referenced, innermost last
+    visible: Vec<usize>,
+}
+impl<'s> Resolver<'s> {
+    /// Resolve the variables of an expression, in the order they are evaluated
+    fn expression(&mut self, expr: &Expression<NoInjectedIntrisics>) {
+        match expr {
+            Expression::Const(..) => (),
+            Expression::List(list) => list.iter().for_each(|e| self.expression(e)),
+            Expression::Map(map) => {
+                for (key, value) in map.iter() {
+                    // computed keys are evaluated before their value
+                    if let MapKey::Computed(key) = key {
+                        self.expression(key)
+                    }
+                    self.expression(value)
+                }
+            }
+            Expression::Closure(closure) => {
+                let params = self
+                    .idents(&closure.span)
+                    .take(closure.params.len())
+                    .collect_vec();
+                self.scoped(|this| {
+                    for param in params {
+                        this.define(param);
+                    }
+                    this.expression(&closure.body)
+                })
+            }
+            Expression::UnOp(un_op) => self.expression(&un_op.expression),
+            Expression::BinOp(ExpressionBinOp {
+                op,
+                expressions: box [a, b],
+                ..
+            }) => {
+                let [first, second] = match op.eval_order() {
+                    Some(EvalOrder::AB) => [a, b],
+                    // the operators without an order evaluate the right operand first, and then
+                    // the left one as many times as needed
+                    Some(EvalOrder::BA) | None => [b, a],
+                };
+                self.expression(first);
+                self.expression(second)
+            }
+            Expression::Call(call) => self.call(call),
+            Expression::MemberAccess(access) => {
+                self.expression(&access.accessed);
+                self.expression(&access.index)
+            }
+            Expression::Is(is) => self.expression(&is.value),
+            Expression::RepeatUntil(repeat) => {
+                self.expression(&repeat.body);
+                self.expression(&repeat.condition)
+            }
+            Expression::Scope(scope) => {
+                self.scoped(|this| scope.iter().for_each(|e| this.expression(e)))
+            }
+            Expression::Set(set) => {
+                self.expression(&set.value);
+                match &set.receiver {
+                    Receiver::Ignore => (),
+                    Receiver::Let(_) => {
+                        if let Some(name) = self.idents(&set.span).next() {
+                            self.define(name);
+                        }
+                    }
+                    Receiver::Set(MemberReceiver { indices, .. }) => {
+                        indices.iter().for_each(|e| self.expression(e));
+                        if let Some(root) = self.idents(&set.span).next() {
+                            self.reference(root)
+                        }
+                    }
+                }
+            }
+            Expression::Ref(r) => {
+                if let Some(span) = r.span.range() {
+                    self.reference(span)
+                }
+            }
+        }
+    }
+
+    /// Resolve the variables of a call, recognizing the ones generated by the comprehensions
+    fn call(&mut self, call: &ExpressionCall<NoInjectedIntrisics>) {
+        if let Some(list) = list_conversion(call) {
+            self.expression(list);
+            return self.comprehension(&call.span, None, None);
+        }
+        if let Some((body, _, list, condition)) = comprehension(call) {
+            self.expression(list);
+            return self.comprehension(&call.span, condition, body);
+        }
+        self.expression(&call.called);
+        call.params.iter().for_each(|e| self.expression(e))
+    }
+
+    /// Resolve the variables of the condition and the body of a comprehension
+    ///
+    /// If the body is the variable itself, it is not in the expression, and it is found
+    /// in the source.
+    fn comprehension(
+        &mut self,
+        span: &Span,
+        condition: Option<&Expression<NoInjectedIntrisics>>,
+        body: Option<&Expression<NoInjectedIntrisics>>,
+    ) {
+        let tokens = self.tokens(span).collect_vec();
+        // the variable follows the `for` that is not nested in the body
+        let mut depth = 0usize;
+        let var = tokens.iter().tuple_windows().find_map(|(token, next)| {
+            match &self.src[token.span.clone()] {
+                "(" | "[" | "{" | "<|" => depth += 1,
+                ")" | "]" | "}" | "|>" => depth = depth.saturating_sub(1),
+                "for" if depth == 1 && next.kind == TokenKind::Ident => {
+                    return Some(next.span.clone())
+                }
+                _ => (),
+            }
+            None
+        });
+        self.scoped(|this| {
+            if let Some(var) = var {
+                this.define(var);
+            }
+            if let Some(condition) = condition {
+                this.expression(condition)
+            }
+            match body {
+                Some(body) => this.expression(body),
+                None => {
+                    let body = tokens.iter().find(|t| t.kind == TokenKind::Ident);
+                    if let Some(body) = body {
+                        this.reference(body.span.clone())
+                    }
+                }
+            }
+        })
+    }
+
+    /// Resolve the variables in a nested scope
+    fn scoped(&mut self, f: impl FnOnce(&mut Self)) {
+        let outer = self.visible.len();
+        f(self);
+        self.visible.truncate(outer)
+    }
+
+    /// The tokens of the source of an expression, apart from whitespace and comments
+    fn tokens(&self, span: &Span) -> impl Iterator<Item = Token> + 's {
+        let range = span.range().unwrap_or_default();
+        tokenize(&self.src[range.clone()])
+            .filter(|t| !matches!(t.kind, TokenKind::Whitespace | TokenKind::Comment))
+            .map(move |Token { kind, span }| Token {
+                kind,
+                span: span.start + range.start..span.end + range.start,
+            })
+    }
+
+    /// The spans of the identifiers in the source of an expression
+    fn idents(&self, span: &Span) -> impl Iterator<Item = Range<usize>> + 's {
+        self.tokens(span)
+            .filter(|t| t.kind == TokenKind::Ident)
+            .map(|t| t.span)
+    }
+
+    fn name(&self, span: &Range<usize>) -> &IdentStr {
+        IdentStr::new(&self.src[span.clone()]).expect("The tokenizer should give valid identifiers")
+    }
+
+    /// Add a new symbol, defined at the given span, and make it visible
+    fn define(&mut self, span: Range<usize>) {
+        self.symbols.push(Symbol {
+            name: self.name(&span).to_owned(),
+            definition: Some(span),
+            references: vec![],
+        });
+        self.visible.push(self.symbols.len() - 1)
+    }
+
+    /// Resolve a reference to the innermost visible symbol with that name
+    ///
+    /// If there is none, the variable comes from the environment
+    fn reference(&mut self, span: Range<usize>) {
+        let name = self.name(&span);
+        let found = self
+            .visible
+            .iter()
+            .rev()
+            .copied()
+            .find(|&sym| &*self.symbols[sym].name == name)
+            .or_else(|| {
+                self.symbols
+                    .iter()
+                    .position(|s| s.definition.is_none() && &*s.name == name)
+            });
+        let sym = match found {
+            Some(sym) => sym,
+            None => {
+                self.symbols.push(Symbol {
+                    name: name.to_owned(),
+                    definition: None,
+                    references: vec![],
+                });
+                self.symbols.len() - 1
+            }
+        };
+        self.symbols[sym].references.push(span)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Find the symbols defined in the source
+    fn defined(src: &str) -> Vec<Symbol> {
+        symbols(src)
+            .unwrap()
+            .into_iter()
+            .filter(|s| s.definition.is_some())
+            .collect()
+    }
+
+    /// Build a symbol, with spans given as `(start, end)`
+    fn sym(name: &str, def: (usize, usize), refs: &[(usize, usize)]) -> Symbol {
+        Symbol {
+            name: IdentStr::new(name).unwrap().to_owned(),
+            definition: Some(def.0..def.1),
+            references: refs.iter().map(|&(start, end)| start..end).collect(),
+        }
+    }
+
+    #[test]
+    fn let_and_use() {
+        assert_eq!(defined("let x = 1; x + 1"), [sym("x", (4, 5), &[(11, 12)])])
+    }
+
+    #[test]
+    fn let_value_sees_the_old_variable() {
+        assert_eq!(
+            defined("let x = 1; let x = x + 1; x"),
+            [
+                sym("x", (4, 5), &[(19, 20)]),
+                sym("x", (15, 16), &[(26, 27)])
+            ]
+        )
+    }
+
+    #[test]
+    fn scoped() {
+        assert_eq!(
+            defined("let x = 1; { let x = 2; x }; x"),
+            [
+                sym("x", (4, 5), &[(29, 30)]),
+                sym("x", (17, 18), &[(24, 25)])
+            ]
+        )
+    }
+
+    #[test]
+    fn closures_params() {
+        assert_eq!(
+            defined("let f = |a, b| a * b; a"),
+            [
+                sym("f", (4, 5), &[]),
+                sym("a", (9, 10), &[(15, 16)]),
+                sym("b", (12, 13), &[(19, 20)])
+            ]
+        )
+    }
+
+    #[test]
+    fn comprehensions() {
+        assert_eq!(
+            defined("[x * 2 for x in [1, 2]]"),
+            [sym("x", (11, 12), &[(1, 2)])]
+        )
+    }
+
+    #[test]
+    fn newline_separated() {
+        assert_eq!(
+            defined("let x = 1\nlet y = x\n{\n  x + y\n}"),
+            [
+                sym("x", (4, 5), &[(18, 19), (24, 25)]),
+                sym("y", (14, 15), &[(28, 29)])
+            ]
+        )
+    }
+
+    #[test]
+    fn multiline_expressions() {
+        assert_eq!(
+            defined("let x = [\n  1,\n  2\n]\nlet y = x +\n  x\ny"),
+            [
+                sym("x", (4, 5), &[(29, 30), (35, 36)]),
+                sym("y", (25, 26), &[(37, 38)])
+            ]
+        )
+    }
+
+    #[test]
+    fn assignments() {
+        assert_eq!(
+            defined("let x = <|a: 1|>; x.a = x"),
+            [sym("x", (4, 5), &[(18, 19), (24, 25)])]
+        )
+    }
+
+    #[test]
+    fn comprehensions_without_body() {
+        assert_eq!(
+            defined("[x for x in l if x]"),
+            [sym("x", (7, 8), &[(1, 2), (17, 18)])]
+        );
+        assert_eq!(defined("[x for x in l]"), [sym("x", (7, 8), &[(1, 2)])])
+    }
+
+    #[test]
+    fn nested_comprehensions() {
+        assert_eq!(
+            defined("[[x for y in x] for x in l]"),
+            [
+                sym("x", (20, 21), &[(2, 3), (13, 14)]),
+                sym("y", (8, 9), &[])
+            ]
+        )
+    }
+
+    #[test]
+    fn rerolls() {
+        assert_eq!(
+            defined("let rr = 1; 4d6 r rr"),
+            [sym("rr", (4, 6), &[(18, 20)])]
+        )
+    }
+
+    #[test]
+    fn environment_variables() {
+        let symbols = symbols("sum(y, [y]).y").unwrap();
+        assert_eq!(symbols.len(), 2);
+        assert_eq!(&*symbols[0].name, IdentStr::new("sum").unwrap());
+        assert_eq!(symbols[0].definition, None);
+        assert_eq!(symbols[1].references, [4..5, 8..9]);
+    }
+
+    #[test]
+    fn map_keys_are_not_references() {
+        assert_eq!(symbols("<|a: 1|>").unwrap(), [])
+    }
+}
@@ -0,0 +1,348 @@
+//! A lightweight lexer for `dices` source
+//!
+//! This is not used by the parser, and it never fails: it is meant for tools
+//! (highlighters, editors) that need to classify the source even when it is
+//! incomplete or invalid.
+
+use std::ops::Range;
+
+use crate::ident::is_keyword;
+
+/// The kind of a token
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TokenKind {
+    /// Spaces, tabs and newlines
+    Whitespace,
+    /// Line (`// ...`) or block (`/* ... */`) comment
+    Comment,
+    /// A number literal
+    Number,
+    /// A string literal, quotes included
+    String,
+    /// `true` or `false`
+    Bool,
+    /// `null`
+    Null,
+    /// A reserved keyword (`let`, `d`, `kh`, ...)
+    Keyword,
+    /// An identifier
+    Ident,
+    /// An operator (`+`, `<<`, `=`, ...)
+    Operator,
+    /// A delimiter or separator (`(`, `<|`, `,`, `;`, ...)
+    Punctuation,
+    /// A char that cannot start any token
+    Unknown,
+}
+
+/// A token, with its kind and its span in bytes
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Range<usize>,
+}
+
+/// Split the source into tokens
+///
+/// The tokens cover the whole source, whitespace and comments included.
+pub fn tokenize(src: &str) -> Tokens<'_> {
+    Tokens { src, pos: 0 }
+}
+
+/// Iterator over the tokens of a source. Created by [`tokenize`]
+#[derive(Debug, Clone)]
+pub struct Tokens<'s> {
+    src: &'s str,
+    pos: usize,
+}
+
+impl Iterator for Tokens<'_> {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = &self.src[self.pos..];
+        let first = rest.chars().next()?;
+
+        let (kind, len) = match first {
+            ' ' | '\t' | '\r' | '\n' => (
+                TokenKind::Whitespace,
+                prefix_len(rest, |ch| matches!(ch, ' ' | '\t' | '\r' | '\n')),
+            ),
+            '/' if rest.starts_with("//") => {
+                (TokenKind::Comment, rest.find('\n').unwrap_or(rest.len()))
+            }
+            '/' if rest.starts_with("/*") => (
+                TokenKind::Comment,
+                rest[2..].find("*/").map_or(rest.len(), |end| end + 4),
+            ),
+            '0'..='9' => (TokenKind::Number, number_len(rest)),
+            '"' => (TokenKind::String, string_len(rest)),
+            'a'..='z' | 'A'..='Z' | '_' => {
+                let len = prefix_len(rest, |ch| ch.is_ascii_alphanumeric() || ch == '_');
+                let word = &rest[..len];
+                match word {
+                    "true" | "false" => (TokenKind::Bool, len),
+                    "null" => (TokenKind::Null, len),
+                    _ if is_keyword(word) => (TokenKind::Keyword, len),
+                    _ => match keyword_before_number(word) {
+                        // `d6`, `kh3` and the like are a keyword followed by a number
+                        Some(kw_len) => (TokenKind::Keyword, kw_len),
+                        None => (TokenKind::Ident, len),
+                    },
+                }
+            }
+            _ => {
+                if let Some(op) = ["<<", ">>", "<|", "|>"]
+                    .into_iter()
+                    .find(|op| rest.starts_with(op))
+                {
+                    let kind = if matches!(op, "<|" | "|>") {
+                        TokenKind::Punctuation
+                    } else {
+                        TokenKind::Operator
+                    };
+                    (kind, op.len())
+                } else {
+                    let kind = match first {
+                        '+' | '-' | '*' | '/' | '%' | '^' | '~' | '&' | '|' | '=' | '.' | '!' => {
+                            TokenKind::Operator
+                        }
+                        '(' | ')' | '[' | ']' | '{' | '}' | ',' | ';' | ':' => {
+                            TokenKind::Punctuation
+                        }
+                        _ => TokenKind::Unknown,
+                    };
+                    (kind, first.len_utf8())
+                }
+            }
+        };
+
+        let span = self.pos..self.pos + len;
+        self.pos = span.end;
+        Some(Token { kind, span })
+    }
+}
+
+/// Find the end of the first complete statement in the source
+///
+/// Return the position of the first separator that is not nested inside brackets, strings or
+/// comments, or `None` if the source does not contain a complete statement yet.
+/// A separator is either a `;`, or a newline that ends a line finishing with a value and is
+/// followed by another statement, the same rule used by the parser.
+pub fn statement_end(src: &str) -> Option<usize> {
+    let mut depth: usize = 0;
+    // if the last token could end an expression
+    let mut after_value = false;
+    // a newline after a value, that will be a separator if another statement follows
+    let mut newline = None;
+    for Token { kind, span } in tokenize(src) {
+        let text = &src[span.clone()];
+        match kind {
+            TokenKind::Whitespace => {
+                if depth == 0 && after_value && newline.is_none() {
+                    newline = text.find('\n').map(|pos| span.start + pos)
+                }
+                continue;
+            }
+            // a line comment does not include the newline that ends it
+            TokenKind::Comment => continue,
+            _ => (),
+        }
+        if let Some(newline) = newline.take() {
+            if !matches!(text, ";" | "}") {
+                return Some(newline);
+            }
+        }
+        match text {
+            "(" | "[" | "{" | "<|" => depth += 1,
+            ")" | "]" | "}" | "|>" => depth = depth.saturating_sub(1),
+            ";" if depth == 0 => return Some(span.start),
+            _ => (),
+        }
+        after_value = match kind {
+            TokenKind::Number
+            | TokenKind::String
+            | TokenKind::Bool
+            | TokenKind::Null
+            | TokenKind::Ident => true,
+            TokenKind::Punctuation => matches!(text, ")" | "]" | "}" | "|>"),
+            _ => false,
+        };
+    }
+    None
+}
+
+/// Length of the longest prefix made of chars satisfying `f`
+fn prefix_len(s: &str, f: impl Fn(char) -> bool) -> usize {
+    s.find(|ch| !f(ch)).unwrap_or(s.len())
+}
+
+/// Length of a number literal at the start of `s`
+fn number_len(s: &str) -> usize {
+    let digits_len = |digits: &str, is_digit: fn(&char) -> bool| {
+        prefix_len(digits, |ch| is_digit(&ch) || ch == '_')
+    };
+    match s.get(..2) {
+        Some("0x") if s[2..].starts_with(|ch: char| ch.is_ascii_hexdigit()) => {
+            2 + digits_len(&s[2..], char::is_ascii_hexdigit)
+        }
+        Some("0o") if s[2..].starts_with(|ch| matches!(ch, '0'..='7')) => {
+            2 + digits_len(&s[2..], |ch| matches!(ch, '0'..='7'))
+        }
+        Some("0b") if s[2..].starts_with(['0', '1']) => {
+            2 + digits_len(&s[2..], |ch| matches!(ch, '0' | '1'))
+        }
+        _ => digits_len(s, char::is_ascii_digit),
+    }
+}
+
+/// Length of a string literal at the start of `s`, including the quotes
+///
+/// Unterminated strings extend to the end of the source
+fn string_len(s: &str) -> usize {
+    let mut escaped = false;
+    for (pos, ch) in s.char_indices().skip(1) {
+        match ch {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => return pos + 1,
+            _ => (),
+        }
+    }
+    s.len()
+}
+
+/// If `word` is a keyword immediately followed by digits, return the length of the keyword
+fn keyword_before_number(word: &str) -> Option<usize> {
+    let kw_len = prefix_len(word, |ch| ch.is_ascii_alphabetic());
+    (kw_len < word.len()
+        && is_keyword(&word[..kw_len])
+        && word[kw_len..].starts_with(|ch: char| ch.is_ascii_digit())
+        && word[kw_len..]
+            .chars()
+            .all(|ch| ch.is_ascii_digit() || ch == '_'))
+    .then_some(kw_len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{tokenize, TokenKind::*};
+
+    fn kinds(src: &str) -> Vec<(super::TokenKind, &str)> {
+        tokenize(src).map(|t| (t.kind, &src[t.span])).collect()
+    }
+
+    #[test]
+    fn representative_line() {
+        assert_eq!(
+            kinds(r#"let x = 3d6 kh2 + "a\"b"; // roll"#),
+            [
+                (Keyword, "let"),
+                (Whitespace, " "),
+                (Ident, "x"),
+                (Whitespace, " "),
+                (Operator, "="),
+                (Whitespace, " "),
+                (Number, "3"),
+                (Keyword, "d"),
+                (Number, "6"),
+                (Whitespace, " "),
+                (Keyword, "kh"),
+                (Number, "2"),
+                (Whitespace, " "),
+                (Operator, "+"),
+                (Whitespace, " "),
+                (String, r#""a\"b""#),
+                (Punctuation, ";"),
+                (Whitespace, " "),
+                (Comment, "// roll"),
+            ]
+        )
+    }
+
+    #[test]
+    fn spans_cover_the_source() {
+        let src = "<|a: 0x1F, b: [true, null]|> << 2 /* c */ ?";
+        let mut end = 0;
+        for token in tokenize(src) {
+            assert_eq!(token.span.start, end);
+            end = token.span.end;
+        }
+        assert_eq!(end, src.len());
+        assert_eq!(
+            kinds(src)
+                .into_iter()
+                .filter(|(k, _)| *k != Whitespace)
+                .collect::<Vec<_>>(),
+            [
+                (Punctuation, "<|"),
+                (Ident, "a"),
+                (Punctuation, ":"),
+                (Number, "0x1F"),
+                (Punctuation, ","),
+                (Ident, "b"),
+                (Punctuation, ":"),
+                (Punctuation, "["),
+                (Bool, "true"),
+                (Punctuation, ","),
+                (Null, "null"),
+                (Punctuation, "]"),
+                (Punctuation, "|>"),
+                (Operator, "<<"),
+                (Number, "2"),
+                (Comment, "/* c */"),
+                (Unknown, "?"),
+            ]
+        )
+    }
+
+    #[test]
+    fn unterminated() {
+        assert_eq!(kinds(r#""abc"#), [(String, r#""abc"#)]);
+        assert_eq!(kinds("/* abc"), [(Comment, "/* abc")]);
+    }
+
+    #[test]
+    fn statement_ends() {
+        use super::statement_end;
+
+        assert_eq!(statement_end("1 + 2; 3"), Some(5));
+        assert_eq!(statement_end("{1; 2}; 3"), Some(6));
+        assert_eq!(statement_end(r#""a;b"; 3"#), Some(5));
+        // the `;` in the comment is ignored, and the newline separates the statements
+        assert_eq!(statement_end("1 // ;\n + 2"), Some(6));
+        assert_eq!(statement_end("f(1;"), None);
+        assert_eq!(statement_end("1 + 2\n3"), Some(5));
+        assert_eq!(statement_end("1 // c\n\n3"), Some(6));
+        assert_eq!(statement_end("1 +\n2\n"), None);
+        assert_eq!(statement_end("[1,\n2]\n3"), Some(6));
+        assert_eq!(statement_end("1\n;2"), Some(2));
+    }
+
+    #[test]
+    fn identifiers_with_keyword_prefix() {
+        assert_eq!(kinds("dice"), [(Ident, "dice")]);
+        assert_eq!(kinds("d20x"), [(Ident, "d20x")]);
+        assert_eq!(kinds("d20"), [(Keyword, "d"), (Number, "20")]);
+    }
+
+    #[test]
+    fn rerolls_and_explosions() {
+        assert_eq!(
+            kinds("4d6 r1 d!6"),
+            [
+                (Number, "4"),
+                (Keyword, "d"),
+                (Number, "6"),
+                (Whitespace, " "),
+                (Keyword, "r"),
+                (Number, "1"),
+                (Whitespace, " "),
+                (Keyword, "d"),
+                (Operator, "!"),
+                (Number, "6"),
+            ]
+        );
+        assert_eq!(kinds("rest"), [(Ident, "rest")]);
+    }
+}
@@ -45,6 +45,12 @@ impl ValueBool {
     }
 }
 
+impl PartialEq<bool> for ValueBool {
+    fn eq(&self, other: &bool) -> bool {
+        self.0 == *other
+    }
+}
+
 #[cfg(feature = "pretty")]
 impl<'a, D, A> pretty::Pretty<'a, D, A> for &'a ValueBool
 where
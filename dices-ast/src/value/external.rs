@@ -0,0 +1,216 @@
+//! Values defined by the host
+//!
+//! A host can thread its own types (a card, a token) through the scripts by implementing
+//! [`ExternalValue`]. The engine treats them as opaque: they can be stored in variables and
+//! collections, compared, printed and passed to the intrisics, but have no operations of their own.
+
+use std::{
+    any::{Any, TypeId},
+    cmp::Ordering,
+    fmt::{Debug, Display},
+    hash::{Hash, Hasher},
+};
+
+use super::{ToNumberError, ValueList, ValueNumber};
+
+/// A type defined by the host, that can be stored in a [`Value`](super::Value)
+///
+/// With the feature `sync` the values must be sendable between threads, so that an engine can
+/// evaluate in parallel.
+pub trait ExternalValue: Debug + Display + Clone + Ord + Hash + MaybeSendSync + 'static {
+    /// The name of the type, used in errors
+    ///
+    /// External values of different types are ordered by this name.
+    const TYPE_NAME: &'static str;
+
+    /// Convert the value to a number
+    ///
+    /// By default external values cannot be converted.
+    fn to_number(&self) -> Option<ValueNumber> {
+        None
+    }
+}
+
+/// `Send + Sync` if the feature `sync` is enabled, implemented by every type otherwise
+#[cfg(feature = "sync")]
+pub trait MaybeSendSync: Send + Sync {}
+#[cfg(feature = "sync")]
+impl<T: Send + Sync> MaybeSendSync for T {}
+
+/// `Send + Sync` if the feature `sync` is enabled, implemented by every type otherwise
+#[cfg(not(feature = "sync"))]
+pub trait MaybeSendSync {}
+#[cfg(not(feature = "sync"))]
+impl<T> MaybeSendSync for T {}
+
+/// The object safe part of [`ExternalValue`]
+trait DynExternalValue: Debug + Display + MaybeSendSync {
+    fn as_any(&self) -> &dyn Any;
+    fn type_name(&self) -> &'static str;
+    fn clone_box(&self) -> Box<dyn DynExternalValue>;
+    fn eq_dyn(&self, other: &dyn DynExternalValue) -> bool;
+    fn cmp_dyn(&self, other: &dyn DynExternalValue) -> Ordering;
+    fn hash_dyn(&self, state: &mut dyn Hasher);
+    fn to_number(&self) -> Option<ValueNumber>;
+}
+
+impl<T: ExternalValue> DynExternalValue for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn type_name(&self) -> &'static str {
+        T::TYPE_NAME
+    }
+
+    fn clone_box(&self) -> Box<dyn DynExternalValue> {
+        Box::new(self.clone())
+    }
+
+    fn eq_dyn(&self, other: &dyn DynExternalValue) -> bool {
+        other.as_any().downcast_ref::<T>() == Some(self)
+    }
+
+    fn cmp_dyn(&self, other: &dyn DynExternalValue) -> Ordering {
+        match other.as_any().downcast_ref::<T>() {
+            Some(other) => self.cmp(other),
+            // different types with the same name must still be different
+            None => T::TYPE_NAME
+                .cmp(other.type_name())
+                .then_with(|| TypeId::of::<T>().cmp(&other.as_any().type_id())),
+        }
+    }
+
+    fn hash_dyn(&self, state: &mut dyn Hasher) {
+        T::TYPE_NAME.hash(&mut HasherRef(state));
+        self.hash(&mut HasherRef(state))
+    }
+
+    fn to_number(&self) -> Option<ValueNumber> {
+        ExternalValue::to_number(self)
+    }
+}
+
+/// Adapter to use a `dyn Hasher` where a sized one is needed
+struct HasherRef<'h>(&'h mut dyn Hasher);
+impl Hasher for HasherRef<'_> {
+    fn finish(&self) -> u64 {
+        self.0.finish()
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.write(bytes)
+    }
+}
+
+/// A value defined by the host
+pub struct ValueExternal(Box<dyn DynExternalValue>);
+
+impl ValueExternal {
+    pub fn new(value: impl ExternalValue) -> Self {
+        Self(Box::new(value))
+    }
+
+    /// The name of the type of the external value
+    pub fn type_name(&self) -> &'static str {
+        self.0.type_name()
+    }
+
+    /// Get the external value, if it is of type `T`
+    pub fn downcast_ref<T: ExternalValue>(&self) -> Option<&T> {
+        self.0.as_any().downcast_ref()
+    }
+
+    pub fn to_number(self) -> Result<ValueNumber, ToNumberError> {
+        self.0
+            .to_number()
+            .ok_or(ToNumberError::External(self.type_name()))
+    }
+
+    pub fn to_list<InjectedIntrisic>(
+        self,
+    ) -> Result<ValueList<InjectedIntrisic>, super::ToListError> {
+        Ok(ValueList::from_iter([self.into()]))
+    }
+}
+
+impl Clone for ValueExternal {
+    fn clone(&self) -> Self {
+        Self(self.0.clone_box())
+    }
+}
+impl Debug for ValueExternal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&self.0, f)
+    }
+}
+impl Display for ValueExternal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&self.0, f)
+    }
+}
+impl PartialEq for ValueExternal {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.eq_dyn(&*other.0)
+    }
+}
+impl Eq for ValueExternal {}
+impl PartialOrd for ValueExternal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for ValueExternal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.cmp_dyn(&*other.0)
+    }
+}
+impl Hash for ValueExternal {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash_dyn(state)
+    }
+}
+
+#[cfg(feature = "pretty")]
+impl<'a, D, A> pretty::Pretty<'a, D, A> for &'a ValueExternal
+where
+    A: 'a,
+    D: ?Sized + pretty::DocAllocator<'a, A>,
+{
+    fn pretty(self, allocator: &'a D) -> pretty::DocBuilder<'a, D, A> {
+        allocator.text(self.to_string())
+    }
+}
+
+// External values are known only to the host that created them, so they cannot be serialized
+
+#[cfg(feature = "bincode")]
+impl bincode::Encode for ValueExternal {
+    fn encode<E: bincode::enc::Encoder>(
+        &self,
+        _: &mut E,
+    ) -> Result<(), bincode::error::EncodeError> {
+        Err(bincode::error::EncodeError::OtherString(format!(
+            "External values cannot be encoded (found a {})",
+            self.type_name()
+        )))
+    }
+}
+#[cfg(feature = "bincode")]
+impl bincode::Decode for ValueExternal {
+    fn decode<D: bincode::de::Decoder>(_: &mut D) -> Result<Self, bincode::error::DecodeError> {
+        Err(bincode::error::DecodeError::Other(
+            "External values cannot be decoded",
+        ))
+    }
+}
+#[cfg(feature = "bincode")]
+impl<'de> bincode::BorrowDecode<'de> for ValueExternal {
+    fn borrow_decode<D: bincode::de::BorrowDecoder<'de>>(
+        _: &mut D,
+    ) -> Result<Self, bincode::error::DecodeError> {
+        Err(bincode::error::DecodeError::Other(
+            "External values cannot be decoded",
+        ))
+    }
+}
@@ -31,7 +31,7 @@ pub struct ValueIntrisic<Injected>(pub Intrisic<Injected>);
 
 impl<Injected: InjectedIntr> Display for ValueIntrisic<Injected> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "<intrisic `{}`>", self.0.name())
+        write!(f, "<intrisic `{}`>", self.0.id())
     }
 }
 
@@ -59,7 +59,7 @@ where
     fn pretty(self, allocator: &'a D) -> pretty::DocBuilder<'a, D, A> {
         allocator
             .text("<intrisic `")
-            .append(self.0.name())
+            .append(self.0.id())
             .append("`>")
     }
 }
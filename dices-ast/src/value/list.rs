@@ -117,8 +117,28 @@ where
     II: InjectedIntr,
 {
     fn pretty(self, allocator: &'a D) -> pretty::DocBuilder<'a, D, A> {
+        self.pretty_with(allocator, &Default::default())
+    }
+}
+
+#[cfg(feature = "pretty")]
+impl<II: InjectedIntr> ValueList<II> {
+    /// Prettify the list, following the options
+    pub fn pretty_with<'a, D, A>(
+        &'a self,
+        allocator: &'a D,
+        options: &crate::fmt::PrettyOptions,
+    ) -> pretty::DocBuilder<'a, D, A>
+    where
+        A: 'a,
+        D: ?Sized + pretty::DocAllocator<'a, A>,
+    {
         allocator
-            .intersperse(self.iter(), crate::fmt::CommaLine)
+            .intersperse(
+                self.iter()
+                    .map(|value| value.pretty_with(allocator, options)),
+                crate::fmt::CommaLine,
+            )
             .enclose(allocator.line_(), allocator.line_())
             .group()
             .nest(4)
@@ -1,4 +1,4 @@
-use std::{collections::BTreeMap, fmt::Display};
+use std::{collections::BTreeMap, fmt::Display, hash::Hash};
 
 use itertools::Itertools;
 
@@ -14,61 +14,97 @@ use super::{list::ValueList, string::ValueString, Value};
     Debug,
     // cloning
     Clone,
-    // comparisons
-    PartialEq,
-    Eq,
-    PartialOrd,
-    Ord,
-    Hash,
 )]
 #[cfg_attr(
     feature = "bincode",
     derive(bincode::Decode, bincode::Encode,),
     bincode(bounds = "InjectedIntrisic: InjectedIntr")
 )]
-pub struct ValueMap<InjectedIntrisic>(pub(super) BTreeMap<ValueString, Value<InjectedIntrisic>>);
+pub struct ValueMap<InjectedIntrisic> {
+    pub(super) content: BTreeMap<ValueString, Value<InjectedIntrisic>>,
+    /// If the map refuses to be changed by the `set` expressions
+    ///
+    /// This takes no part in the comparisons: a frozen map is equal to the same map not frozen.
+    /// It is kept by the serialization, both with bincode and with serde (as a `$frozen` tag).
+    pub(super) frozen: bool,
+}
 type Entry<'m, InjectedIntrisic> =
     std::collections::btree_map::Entry<'m, ValueString, Value<InjectedIntrisic>>;
 
+impl<InjectedIntrisic: PartialEq> PartialEq for ValueMap<InjectedIntrisic> {
+    fn eq(&self, other: &Self) -> bool {
+        self.content == other.content
+    }
+}
+impl<InjectedIntrisic: Eq> Eq for ValueMap<InjectedIntrisic> {}
+impl<InjectedIntrisic: PartialOrd> PartialOrd for ValueMap<InjectedIntrisic> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        self.content.partial_cmp(&other.content)
+    }
+}
+impl<InjectedIntrisic: Ord> Ord for ValueMap<InjectedIntrisic> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.content.cmp(&other.content)
+    }
+}
+impl<InjectedIntrisic: Hash> Hash for ValueMap<InjectedIntrisic> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.content.hash(state)
+    }
+}
+
 impl<InjectedIntrisic> ValueMap<InjectedIntrisic> {
     pub fn new() -> Self {
-        Self(BTreeMap::new())
+        Self {
+            content: BTreeMap::new(),
+            frozen: false,
+        }
+    }
+
+    /// Freeze the map, so that the `set` expressions cannot change it
+    pub fn freeze(&mut self) {
+        self.frozen = true
+    }
+
+    /// If the map was frozen
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
     }
 
     #[cfg(feature = "parse_value")]
     pub fn to_number(self) -> Result<super::number::ValueNumber, super::ToNumberError> {
-        match self.0.into_iter().exactly_one() {
+        match self.content.into_iter().exactly_one() {
             Ok((_, value)) => value.to_number(),
             Err(vals) => Err(super::ToNumberError::WrongListLength(vals.len())),
         }
     }
 
     pub fn to_list(self) -> Result<ValueList<InjectedIntrisic>, super::ToListError> {
-        Ok(self.0.into_values().collect())
+        Ok(self.content.into_values().collect())
     }
 
     pub fn len(&self) -> usize {
-        self.0.len()
+        self.content.len()
     }
 
     pub fn iter(&self) -> impl Iterator<Item = (&ValueString, &Value<InjectedIntrisic>)> {
-        self.0.iter()
+        self.content.iter()
     }
     pub fn iter_mut(
         &mut self,
     ) -> impl Iterator<Item = (&ValueString, &mut Value<InjectedIntrisic>)> {
-        self.0.iter_mut()
+        self.content.iter_mut()
     }
 
     pub fn get(&self, key: &str) -> Option<&Value<InjectedIntrisic>> {
-        self.0.get(key)
+        self.content.get(key)
     }
     pub fn get_mut(&mut self, key: &str) -> Option<&mut Value<InjectedIntrisic>> {
-        self.0.get_mut(key)
+        self.content.get_mut(key)
     }
 
     pub fn remove(&mut self, key: &str) -> Option<Value<InjectedIntrisic>> {
-        self.0.remove(key)
+        self.content.remove(key)
     }
 
     pub fn insert(
@@ -76,25 +112,27 @@ impl<InjectedIntrisic> ValueMap<InjectedIntrisic> {
         key: ValueString,
         value: Value<InjectedIntrisic>,
     ) -> Option<Value<InjectedIntrisic>> {
-        self.0.insert(key, value)
+        self.content.insert(key, value)
     }
 
     pub fn contains(&self, key: &str) -> bool {
-        self.0.contains_key(key)
+        self.content.contains_key(key)
     }
 
     pub fn entry(&mut self, s: ValueString) -> Entry<InjectedIntrisic> {
-        self.0.entry(s)
+        self.content.entry(s)
     }
 }
 impl ValueMap<NoInjectedIntrisics> {
     pub fn with_arbitrary_injected_intrisics<II>(self) -> ValueMap<II> {
-        ValueMap(
-            self.0
+        ValueMap {
+            content: self
+                .content
                 .into_iter()
                 .map(|(k, v)| (k, v.with_arbitrary_injected_intrisics()))
                 .collect(),
-        )
+            frozen: self.frozen,
+        }
     }
 }
 
@@ -109,7 +147,7 @@ impl<II: InjectedIntr> Display for ValueMap<II> {
             }
         }
 
-        write!(f, "<|{}|>", self.0.iter().map(KeyValue).format(", "))
+        write!(f, "<|{}|>", self.content.iter().map(KeyValue).format(", "))
     }
 }
 
@@ -121,6 +159,22 @@ where
     II: InjectedIntr,
 {
     fn pretty(self, allocator: &'a D) -> pretty::DocBuilder<'a, D, A> {
+        self.pretty_with(allocator, &Default::default())
+    }
+}
+
+#[cfg(feature = "pretty")]
+impl<II: InjectedIntr> ValueMap<II> {
+    /// Prettify the map, following the options
+    pub fn pretty_with<'a, D, A>(
+        &'a self,
+        allocator: &'a D,
+        options: &crate::fmt::PrettyOptions,
+    ) -> pretty::DocBuilder<'a, D, A>
+    where
+        A: 'a,
+        D: ?Sized + pretty::DocAllocator<'a, A>,
+    {
         allocator
             .intersperse(
                 self.iter().map(|(key, value)| {
@@ -134,7 +188,7 @@ where
                         .text(QuoteIfNotIdent(&key).to_string())
                         .append(":")
                         .append(allocator.space())
-                        .append(value)
+                        .append(value.pretty_with(allocator, options))
                 }),
                 crate::fmt::CommaLine,
             )
@@ -149,7 +203,10 @@ impl<InjectedIntrisic> FromIterator<(ValueString, Value<InjectedIntrisic>)>
     for ValueMap<InjectedIntrisic>
 {
     fn from_iter<T: IntoIterator<Item = (ValueString, Value<InjectedIntrisic>)>>(iter: T) -> Self {
-        Self(FromIterator::from_iter(iter))
+        Self {
+            content: FromIterator::from_iter(iter),
+            frozen: false,
+        }
     }
 }
 impl<InjectedIntrisic> IntoIterator for ValueMap<InjectedIntrisic> {
@@ -158,7 +215,7 @@ impl<InjectedIntrisic> IntoIterator for ValueMap<InjectedIntrisic> {
     type IntoIter = <BTreeMap<ValueString, Value<InjectedIntrisic>> as IntoIterator>::IntoIter;
 
     fn into_iter(self) -> Self::IntoIter {
-        self.0.into_iter()
+        self.content.into_iter()
     }
 }
 
@@ -178,6 +235,9 @@ mod serde {
         Nested {
             #[serde(rename = "$content")]
             content: BTreeMap<ValueString, Value<InjectedIntrisic>>,
+            // maps serialized before freezing was added are not frozen
+            #[serde(rename = "$frozen", default)]
+            frozen: bool,
         },
         #[serde(untagged)]
         Flattened(BTreeMap<ValueString, Value<InjectedIntrisic>>),
@@ -190,11 +250,17 @@ mod serde {
         Nested {
             #[serde(rename = "$content")]
             content: &'m BTreeMap<ValueString, Value<InjectedIntrisic>>,
+            #[serde(rename = "$frozen", skip_serializing_if = "is_false")]
+            frozen: bool,
         },
         #[serde(untagged)]
         Flattened(&'m BTreeMap<ValueString, Value<InjectedIntrisic>>),
     }
 
+    fn is_false(b: &bool) -> bool {
+        !*b
+    }
+
     impl<II> Serialize for ValueMap<II>
     where
         II: InjectedIntr,
@@ -203,10 +269,13 @@ mod serde {
         where
             S: serde::Serializer,
         {
-            if self.contains("$type") {
-                BorrowedSerialized::Nested { content: &self.0 }
+            if self.frozen || self.contains("$type") {
+                BorrowedSerialized::Nested {
+                    content: &self.content,
+                    frozen: self.frozen,
+                }
             } else {
-                BorrowedSerialized::Flattened(&self.0)
+                BorrowedSerialized::Flattened(&self.content)
             }
             .serialize(serializer)
         }
@@ -219,9 +288,13 @@ mod serde {
         where
             D: serde::Deserializer<'de>,
         {
-            let (Serialized::Nested { content } | Serialized::Flattened(content)) =
-                Serialized::deserialize(deserializer)?;
-            Ok(Self(content))
+            Ok(match Serialized::deserialize(deserializer)? {
+                Serialized::Nested { content, frozen } => Self { content, frozen },
+                Serialized::Flattened(content) => Self {
+                    content,
+                    frozen: false,
+                },
+            })
         }
     }
 }
@@ -4,6 +4,7 @@ use derive_more::derive::{Display, Error, From, TryUnwrap, Unwrap};
 
 pub use boolean::ValueBool;
 pub use closure::ValueClosure;
+pub use external::{ExternalValue, ValueExternal};
 pub use intrisics::ValueIntrisic;
 pub use list::ValueList;
 pub use map::ValueMap;
@@ -15,6 +16,7 @@ use crate::intrisics::{Intrisic, NoInjectedIntrisics};
 
 pub mod boolean;
 pub mod closure;
+pub mod external;
 pub mod intrisics;
 pub mod list;
 pub mod map;
@@ -28,13 +30,23 @@ mod tests;
 #[cfg(feature = "parse_value")]
 mod parse;
 
+/// A `dices` value
+///
+/// # Ordering
+/// Values of the same type are compared by their content. Values of different types are ordered
+/// by type, in the order the variants are declared:
+///
+/// `null` < booleans < numbers < strings < lists < maps < intrisics < closures < external values
+///
+/// This order is part of the language, as it decides how lists of mixed values are sorted, so the
+/// variants must not be reordered.
 #[derive(
     // display helper
     Debug,
     Display,
     // cloning
     Clone,
-    // comparisons
+    // comparisons, that depend on the order of the variants
     PartialEq,
     Eq,
     PartialOrd,
@@ -64,6 +76,54 @@ pub enum Value<InjectedIntrisic = NoInjectedIntrisics> {
 
     Intrisic(ValueIntrisic<InjectedIntrisic>),
     Closure(Box<ValueClosure<InjectedIntrisic>>),
+
+    External(ValueExternal),
+}
+
+/// The type of a `dices` value
+///
+/// All the external values share the same type, whatever their host type is.
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "bincode", derive(bincode::Decode, bincode::Encode,))]
+pub enum ValueType {
+    #[display("null")]
+    Null,
+    #[display("bool")]
+    Bool,
+    #[display("number")]
+    Number,
+    #[display("string")]
+    String,
+    #[display("list")]
+    List,
+    #[display("map")]
+    Map,
+    #[display("intrisic")]
+    Intrisic,
+    #[display("closure")]
+    Closure,
+    #[display("external")]
+    External,
+}
+
+impl ValueType {
+    /// All the types, in the order values are sorted by
+    pub const ALL: [ValueType; 9] = [
+        ValueType::Null,
+        ValueType::Bool,
+        ValueType::Number,
+        ValueType::String,
+        ValueType::List,
+        ValueType::Map,
+        ValueType::Intrisic,
+        ValueType::Closure,
+        ValueType::External,
+    ];
+
+    /// Find the type with the given name
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|ty| ty.to_string() == name)
+    }
 }
 
 impl Value<NoInjectedIntrisics> {
@@ -82,6 +142,7 @@ impl Value<NoInjectedIntrisics> {
             Value::Closure(value_closure) => {
                 Value::Closure(Box::new(value_closure.with_arbitrary_injected_intrisics()))
             }
+            Value::External(value_external) => Value::External(value_external),
         }
     }
 }
@@ -98,6 +159,53 @@ impl<InjectedIntrisic> Value<InjectedIntrisic> {
             Value::Intrisic(v) => v.to_number(),
             Value::Closure(v) => v.to_number(),
             Value::Null(v) => v.to_number(),
+            Value::External(v) => v.to_number(),
+        }
+    }
+
+    /// The type of this value
+    pub fn value_type(&self) -> ValueType {
+        match self {
+            Value::Null(_) => ValueType::Null,
+            Value::Bool(_) => ValueType::Bool,
+            Value::Number(_) => ValueType::Number,
+            Value::String(_) => ValueType::String,
+            Value::List(_) => ValueType::List,
+            Value::Map(_) => ValueType::Map,
+            Value::Intrisic(_) => ValueType::Intrisic,
+            Value::Closure(_) => ValueType::Closure,
+            Value::External(_) => ValueType::External,
+        }
+    }
+
+    /// The name of the type of this value
+    ///
+    /// This is the name of the [`ValueType`], apart from external values that give the name of
+    /// their host type.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Null(_) => "null",
+            Value::Bool(_) => "bool",
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::List(_) => "list",
+            Value::Map(_) => "map",
+            Value::Intrisic(_) => "intrisic",
+            Value::Closure(_) => "closure",
+            Value::External(v) => v.type_name(),
+        }
+    }
+
+    /// Iterate over the content of a list or a map
+    ///
+    /// The elements of a list are given without a key, the entries of a map with their key, in
+    /// order. Any other value has no content, and gives `None`. To get the list or the map itself,
+    /// use [`Value::as_list`] and [`Value::as_map`].
+    pub fn iter(&self) -> Option<Members<'_, InjectedIntrisic>> {
+        match self {
+            Value::List(list) => Some(Members::List(list[..].iter())),
+            Value::Map(map) => Some(Members::Map(map.content.iter())),
+            _ => None,
         }
     }
 
@@ -111,10 +219,37 @@ impl<InjectedIntrisic> Value<InjectedIntrisic> {
             Value::Intrisic(v) => v.to_list(),
             Value::Closure(v) => v.to_list(),
             Value::Null(v) => v.to_list(),
+            Value::External(v) => v.to_list(),
         }
     }
 }
 
+/// The content of a list or a map, given by [`Value::iter`]
+#[derive(Debug, Clone)]
+pub enum Members<'v, InjectedIntrisic> {
+    List(std::slice::Iter<'v, Value<InjectedIntrisic>>),
+    Map(std::collections::btree_map::Iter<'v, ValueString, Value<InjectedIntrisic>>),
+}
+impl<'v, InjectedIntrisic> Iterator for Members<'v, InjectedIntrisic> {
+    /// The key, if in a map, and the value
+    type Item = (Option<&'v ValueString>, &'v Value<InjectedIntrisic>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Members::List(list) => list.next().map(|value| (None, value)),
+            Members::Map(map) => map.next().map(|(key, value)| (Some(key), value)),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self {
+            Members::List(list) => list.size_hint(),
+            Members::Map(map) => map.size_hint(),
+        }
+    }
+}
+impl<InjectedIntrisic> ExactSizeIterator for Members<'_, InjectedIntrisic> {}
+
 #[cfg(feature = "pretty")]
 impl<'a, D, A, II> pretty::Pretty<'a, D, A> for &'a Value<II>
 where
@@ -132,6 +267,100 @@ where
             Value::Map(value) => value.pretty(allocator),
             Value::Intrisic(value) => value.pretty(allocator),
             Value::Closure(value) => value.pretty(allocator),
+            Value::External(value) => value.pretty(allocator),
+        }
+    }
+}
+
+#[cfg(feature = "pretty")]
+impl<II: crate::intrisics::InjectedIntr> Value<II> {
+    /// Prettify the value, following the options
+    ///
+    /// The options only change how the value is shown: numbers in scientific notation cannot be
+    /// parsed back.
+    pub fn pretty_with<'a, D, A>(
+        &'a self,
+        allocator: &'a D,
+        options: &crate::fmt::PrettyOptions,
+    ) -> pretty::DocBuilder<'a, D, A>
+    where
+        A: 'a,
+        D: ?Sized + pretty::DocAllocator<'a, A>,
+    {
+        match self {
+            Value::Number(value) => value.pretty_with(allocator, options),
+            Value::List(value) => value.pretty_with(allocator, options),
+            Value::Map(value) => value.pretty_with(allocator, options),
+            _ => pretty::Pretty::pretty(self, allocator),
+        }
+    }
+}
+
+// Conversions to native values, that succeed only from the value of the same type
+
+#[derive(Debug, Display, Error, Clone)]
+pub enum FromValueError {
+    #[display("Expected a {expected}, found a {found}")]
+    WrongType {
+        expected: &'static str,
+        found: &'static str,
+    },
+    #[display("The number {_0} is too big to be converted")]
+    NumberTooBig(#[error(not(source))] ValueNumber),
+}
+
+impl<InjectedIntrisic> TryFrom<Value<InjectedIntrisic>> for i64 {
+    type Error = FromValueError;
+
+    fn try_from(value: Value<InjectedIntrisic>) -> Result<Self, Self::Error> {
+        match value {
+            Value::Number(n) => n
+                .clone()
+                .try_into()
+                .map_err(|_| FromValueError::NumberTooBig(n)),
+            value => Err(FromValueError::WrongType {
+                expected: "number",
+                found: value.type_name(),
+            }),
+        }
+    }
+}
+impl<InjectedIntrisic> TryFrom<Value<InjectedIntrisic>> for String {
+    type Error = FromValueError;
+
+    fn try_from(value: Value<InjectedIntrisic>) -> Result<Self, Self::Error> {
+        match value {
+            Value::String(s) => Ok(Box::<str>::from(s).into_string()),
+            value => Err(FromValueError::WrongType {
+                expected: "string",
+                found: value.type_name(),
+            }),
+        }
+    }
+}
+impl<InjectedIntrisic> TryFrom<Value<InjectedIntrisic>> for bool {
+    type Error = FromValueError;
+
+    fn try_from(value: Value<InjectedIntrisic>) -> Result<Self, Self::Error> {
+        match value {
+            Value::Bool(b) => Ok(*b),
+            value => Err(FromValueError::WrongType {
+                expected: "bool",
+                found: value.type_name(),
+            }),
+        }
+    }
+}
+impl<InjectedIntrisic> TryFrom<Value<InjectedIntrisic>> for Vec<Value<InjectedIntrisic>> {
+    type Error = FromValueError;
+
+    fn try_from(value: Value<InjectedIntrisic>) -> Result<Self, Self::Error> {
+        match value {
+            Value::List(l) => Ok(Box::<[_]>::from(l).into_vec()),
+            value => Err(FromValueError::WrongType {
+                expected: "list",
+                found: value.type_name(),
+            }),
         }
     }
 }
@@ -151,6 +380,8 @@ pub enum ToNumberError {
     Closure,
     #[display("`null` cannot be interpreted as a number")]
     InvalidNull,
+    #[display("A {_0} cannot be interpreted as a number")]
+    External(#[error(not(source))] &'static str),
 }
 
 #[derive(Debug, Display, Error, Clone)]
@@ -1,10 +1,11 @@
 use std::iter::Step;
 
 use derive_more::derive::{
-    Add, AddAssign, Display, Div, DivAssign, Error, From, Into, Mul, MulAssign, Neg, Rem,
-    RemAssign, Sub, SubAssign,
+    Add, AddAssign, BitAnd, BitOr, BitXor, Display, Div, DivAssign, Error, From, Into, Mul,
+    MulAssign, Neg, Not, Rem, RemAssign, Shl, Shr, Sub, SubAssign,
 };
 use num_bigint::{BigInt, ToBigInt};
+use num_integer::Integer;
 
 use super::list::ValueList;
 
@@ -33,6 +34,13 @@ use super::list::ValueList;
     Neg,
     Rem,
     RemAssign,
+    // bitwise operations
+    BitAnd,
+    BitOr,
+    BitXor,
+    Not,
+    Shl,
+    Shr,
     From,
     Into,
 )]
@@ -41,6 +49,12 @@ use super::list::ValueList;
 #[div(forward)]
 pub struct ValueNumber(pub(super) BigInt);
 
+impl PartialEq<i64> for ValueNumber {
+    fn eq(&self, other: &i64) -> bool {
+        self.0 == BigInt::from(*other)
+    }
+}
+
 impl ValueNumber {
     pub const ZERO: Self = ValueNumber(BigInt::ZERO);
 
@@ -60,6 +74,76 @@ impl ValueNumber {
             self.0.into_parts().1,
         ))
     }
+
+    /// The number of bits needed to represent the magnitude of the number
+    pub fn bits(&self) -> u64 {
+        self.0.bits()
+    }
+
+    /// Raise the number to the power `exp`
+    pub fn pow(&self, exp: u32) -> Self {
+        Self(self.0.pow(exp))
+    }
+
+    /// The greatest common divisor, that is never negative
+    pub fn gcd(&self, other: &Self) -> Self {
+        Self(self.0.gcd(&other.0))
+    }
+
+    /// The least common multiple, that is never negative
+    pub fn lcm(&self, other: &Self) -> Self {
+        Self(self.0.lcm(&other.0))
+    }
+
+    /// The euclidean remainder of the division by `rhs`, that is never negative
+    ///
+    /// Returns `None` if `rhs` is zero
+    pub fn checked_rem_euclid(&self, rhs: &Self) -> Option<Self> {
+        if rhs.0 == BigInt::ZERO {
+            return None;
+        }
+        let rem = &self.0 % &rhs.0;
+        Some(if rem < BigInt::ZERO {
+            Self(rem) + rhs.clone().abs()
+        } else {
+            Self(rem)
+        })
+    }
+
+    /// Write the number in scientific notation, as `1.2e9`
+    ///
+    /// The mantissa is rounded to `significant_digits` digits (at least one), and its trailing
+    /// zeros are dropped.
+    pub fn to_scientific(&self, significant_digits: usize) -> String {
+        let significant_digits = significant_digits.max(1);
+        let digits = self.0.magnitude().to_string();
+        let mut exponent = digits.len() - 1;
+        let mut mantissa = if digits.len() > significant_digits {
+            let mut kept: BigInt = digits[..significant_digits].parse().unwrap();
+            if digits.as_bytes()[significant_digits] >= b'5' {
+                kept += 1;
+            }
+            let kept = kept.to_string();
+            if kept.len() > significant_digits {
+                // the rounding carried over, as in 9.99 -> 10.0
+                exponent += 1;
+                kept[..significant_digits].to_owned()
+            } else {
+                kept
+            }
+        } else {
+            digits
+        };
+        let decimals = mantissa.split_off(1);
+        let decimals = decimals.trim_end_matches('0');
+
+        let sign = if self.0 < BigInt::ZERO { "-" } else { "" };
+        if decimals.is_empty() {
+            format!("{sign}{mantissa}e{exponent}")
+        } else {
+            format!("{sign}{mantissa}.{decimals}e{exponent}")
+        }
+    }
 }
 macro_rules! impl_lesser_nums {
     ( $( $n:ty ) *) => {
@@ -124,7 +208,28 @@ where
     D: ?Sized + pretty::DocAllocator<'a, A>,
 {
     fn pretty(self, allocator: &'a D) -> pretty::DocBuilder<'a, D, A> {
-        allocator.text(self.to_string())
+        self.pretty_with(allocator, &Default::default())
+    }
+}
+
+#[cfg(feature = "pretty")]
+impl ValueNumber {
+    /// Prettify the number, following the options
+    pub fn pretty_with<'a, D, A>(
+        &self,
+        allocator: &'a D,
+        options: &crate::fmt::PrettyOptions,
+    ) -> pretty::DocBuilder<'a, D, A>
+    where
+        A: 'a,
+        D: ?Sized + pretty::DocAllocator<'a, A>,
+    {
+        match &options.scientific_above {
+            Some(threshold) if self.0.magnitude() > threshold.0.magnitude() => {
+                allocator.text(self.to_scientific(options.significant_digits))
+            }
+            _ => allocator.text(self.to_string()),
+        }
     }
 }
 
@@ -112,6 +112,7 @@ impl<'de, II: InjectedIntr> Deserializer<'de> for Value<II> {
                     .expect("Values should always be serializable to plain values");
                 plain.deserialize_any(visitor)
             }
+            Value::External(_) => Err(self.invalid_type(&visitor)),
         }
     }
 
@@ -538,6 +539,7 @@ impl<II> Value<II> {
             Value::List(_) => Unexpected::Seq,
             Value::Map(_) => Unexpected::Map,
             Value::Intrisic(_) => Unexpected::Other("intrisic"),
+            Value::External(_) => Unexpected::Other("external value"),
             Value::Closure(_) => Unexpected::Other("closure"),
         }
     }
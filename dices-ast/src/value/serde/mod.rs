@@ -21,6 +21,9 @@ enum Serialized<InjectedIntrisic> {
     NestedMap {
         #[serde(rename = "$content")]
         content: BTreeMap<ValueString, Value<InjectedIntrisic>>,
+        // maps serialized before freezing was added are not frozen
+        #[serde(rename = "$frozen", default)]
+        frozen: bool,
     },
     #[serde(rename = "intrisic")]
     NestedIntrisic {
@@ -65,6 +68,8 @@ enum BorrowedSerialized<'m, InjectedIntrisic> {
     NestedMap {
         #[serde(rename = "$content")]
         content: &'m BTreeMap<ValueString, Value<InjectedIntrisic>>,
+        #[serde(rename = "$frozen", skip_serializing_if = "is_false")]
+        frozen: bool,
     },
     #[serde(rename = "intrisic")]
     NestedIntrisic {
@@ -102,6 +107,10 @@ enum BorrowedSerialized<'m, InjectedIntrisic> {
     Map(&'m BTreeMap<ValueString, Value<InjectedIntrisic>>),
 }
 
+fn is_false(b: &bool) -> bool {
+    !*b
+}
+
 impl<II> Serialize for Value<II>
 where
     II: InjectedIntr,
@@ -126,17 +135,24 @@ where
             Value::String(value_string) => BorrowedSerialized::String(value_string),
             Value::List(value_list) => BorrowedSerialized::List(value_list),
             Value::Map(value_map) => {
-                if value_map.contains("$type") {
+                if value_map.frozen || value_map.contains("$type") {
                     BorrowedSerialized::NestedMap {
-                        content: &value_map.0,
+                        content: &value_map.content,
+                        frozen: value_map.frozen,
                     }
                 } else {
-                    BorrowedSerialized::Map(&value_map.0)
+                    BorrowedSerialized::Map(&value_map.content)
                 }
             }
             Value::Intrisic(ValueIntrisic(intrinsic)) => {
                 BorrowedSerialized::NestedIntrisic { intrinsic }
             }
+            Value::External(value_external) => {
+                return Err(<S::Error as serde::ser::Error>::custom(format!(
+                    "External values cannot be serialized (found a {})",
+                    value_external.type_name()
+                )))
+            }
             Value::Closure(box ValueClosure {
                 params,
                 captures,
@@ -162,9 +178,8 @@ where
         D: serde::Deserializer<'de>,
     {
         Ok(match Serialized::deserialize(deserializer)? {
-            Serialized::NestedMap { content } | Serialized::Map(content) => {
-                Value::Map(ValueMap(content))
-            }
+            Serialized::NestedMap { content, frozen } => Value::Map(ValueMap { content, frozen }),
+            Serialized::Map(content) => Value::Map(content.into_iter().collect()),
             Serialized::NestedIntrisic { intrisic } => Value::Intrisic(ValueIntrisic(intrisic)),
             Serialized::NestedClosure {
                 params,
@@ -45,6 +45,22 @@ impl ValueString {
     ) -> Result<ValueList<InjectedIntrisic>, super::ToListError> {
         Ok(ValueList::from_iter([self.into()]))
     }
+
+    /// The string repeated `times` times
+    pub fn repeat(&self, times: usize) -> Self {
+        Self(self.0.repeat(times).into_boxed_str())
+    }
+}
+
+impl PartialEq<str> for ValueString {
+    fn eq(&self, other: &str) -> bool {
+        *self.0 == *other
+    }
+}
+impl PartialEq<&str> for ValueString {
+    fn eq(&self, other: &&str) -> bool {
+        *self.0 == **other
+    }
 }
 
 impl From<&str> for ValueString {
@@ -147,3 +147,369 @@ mod roundtrips {
         ]);
     }
 }
+
+#[cfg(feature = "bincode")]
+mod stable_ids {
+    use super::super::*;
+    use crate::intrisics::Intrisic;
+
+    /// `[<intrisic `filter`>, <intrisic `sum`>]`, encoded with an older order of the intrisics
+    const ENCODED: &[u8] = &[
+        4, 2, 6, 6, 102, 105, 108, 116, 101, 114, 6, 3, 115, 117, 109,
+    ];
+
+    #[test]
+    fn decode_does_not_depend_on_enum_order() {
+        let (decoded, _): (Value, _) =
+            bincode::decode_from_slice(ENCODED, bincode::config::standard())
+                .expect("Previously encoded values should decode");
+        assert_eq!(
+            decoded,
+            ValueList::from_iter([
+                Value::Intrisic(ValueIntrisic(Intrisic::Filter)),
+                Value::Intrisic(ValueIntrisic(Intrisic::Sum)),
+            ])
+            .into()
+        )
+    }
+
+    #[test]
+    fn encode_roundtrip() {
+        for intrisic in Intrisic::iter() {
+            let value: Value = ValueIntrisic(intrisic).into();
+            let encoded = bincode::encode_to_vec(&value, bincode::config::standard()).unwrap();
+            let (decoded, _): (Value, _) =
+                bincode::decode_from_slice(&encoded, bincode::config::standard()).unwrap();
+            assert_eq!(decoded, value)
+        }
+    }
+}
+
+mod frozen_maps {
+    use super::super::*;
+
+    fn frozen() -> Value {
+        let mut map = ValueMap::from_iter([
+            (ValueString::from("a"), Value::Number(1.into())),
+            (ValueString::from("$type"), Value::Null(ValueNull)),
+        ]);
+        map.freeze();
+        map.into()
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_roundtrip() {
+        use crate::value::serde::{deserialize_from_value, serialize_to_value};
+
+        let serialized: Value = serialize_to_value(frozen()).unwrap();
+        let Value::Map(decoded) = deserialize_from_value::<Value, _>(serialized).unwrap() else {
+            panic!("A map should deserialize to a map")
+        };
+        assert!(decoded.is_frozen())
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_maps_without_frozen_are_not_frozen() {
+        use crate::value::serde::deserialize_from_value;
+
+        let serialized: Value = ValueMap::from_iter([
+            (ValueString::from("$type"), Value::String("map".into())),
+            (
+                ValueString::from("$content"),
+                ValueMap::from_iter([(ValueString::from("a"), Value::Number(1.into()))]).into(),
+            ),
+        ])
+        .into();
+        let Value::Map(decoded) = deserialize_from_value::<Value, _>(serialized).unwrap() else {
+            panic!("A map should deserialize to a map")
+        };
+        assert!(!decoded.is_frozen())
+    }
+
+    #[cfg(feature = "bincode")]
+    #[test]
+    fn bincode_roundtrip() {
+        let encoded = bincode::encode_to_vec(frozen(), bincode::config::standard()).unwrap();
+        let (decoded, _): (Value, _) =
+            bincode::decode_from_slice(&encoded, bincode::config::standard()).unwrap();
+        let Value::Map(decoded) = decoded else {
+            panic!("A map should decode to a map")
+        };
+        assert!(decoded.is_frozen())
+    }
+}
+
+mod native_comparisons {
+    use super::super::*;
+
+    #[test]
+    fn numbers() {
+        assert!(ValueNumber::from(7) == 7);
+        assert!(ValueNumber::from(-3) == -3);
+        assert!(ValueNumber::from(7) != 8)
+    }
+
+    #[test]
+    fn strings() {
+        let value = ValueString::from("hello");
+        assert!(value == "hello");
+        assert!(value != "world")
+    }
+
+    #[test]
+    fn bools() {
+        assert!(ValueBool::TRUE == true);
+        assert!(ValueBool::FALSE != true)
+    }
+}
+
+mod native_conversions {
+    use super::super::*;
+
+    #[test]
+    fn numbers() {
+        assert_eq!(
+            i64::try_from(Value::<NoInjectedIntrisics>::Number(7.into())).unwrap(),
+            7
+        )
+    }
+
+    #[test]
+    fn numbers_too_big() {
+        assert!(matches!(
+            i64::try_from(Value::<NoInjectedIntrisics>::Number(u64::MAX.into())),
+            Err(FromValueError::NumberTooBig(_))
+        ))
+    }
+
+    #[test]
+    fn strings() {
+        let value: Value = ValueString::from("hello".to_owned().into_boxed_str()).into();
+        assert_eq!(String::try_from(value).unwrap(), "hello")
+    }
+
+    #[test]
+    fn bools() {
+        assert!(bool::try_from(Value::<NoInjectedIntrisics>::Bool(ValueBool::TRUE)).unwrap())
+    }
+
+    #[test]
+    fn lists() {
+        let value: Value =
+            ValueList::from_iter([Value::Number(1.into()), Value::Null(ValueNull)]).into();
+        assert_eq!(
+            Vec::try_from(value).unwrap(),
+            vec![Value::Number(1.into()), Value::Null(ValueNull)]
+        )
+    }
+
+    #[test]
+    fn wrong_type() {
+        let err = i64::try_from(Value::<NoInjectedIntrisics>::String(
+            "7".to_owned().into_boxed_str().into(),
+        ))
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            FromValueError::WrongType {
+                expected: "number",
+                found: "string"
+            }
+        ));
+        assert_eq!(err.to_string(), "Expected a number, found a string")
+    }
+}
+
+mod iteration {
+    use super::super::*;
+
+    #[test]
+    fn lists_give_their_elements() {
+        let list: Value =
+            ValueList::from_iter([Value::Number(1.into()), Value::Null(ValueNull)]).into();
+        assert_eq!(list.as_list().map(|l| l.len()), Some(2));
+        assert_eq!(
+            list.iter().unwrap().collect::<Vec<_>>(),
+            [
+                (None, &Value::Number(1.into())),
+                (None, &Value::Null(ValueNull))
+            ]
+        )
+    }
+
+    #[test]
+    fn maps_give_their_entries() {
+        let map: Value = ValueMap::from_iter([
+            ("b".into(), Value::Number(2.into())),
+            ("a".into(), Value::Number(1.into())),
+        ])
+        .into();
+        assert!(map.as_map().is_some());
+        let entries: Vec<_> = map
+            .iter()
+            .unwrap()
+            .map(|(k, v)| (k.map(|k| &***k), v))
+            .collect();
+        assert_eq!(
+            entries,
+            [
+                (Some("a"), &Value::Number(1.into())),
+                (Some("b"), &Value::Number(2.into()))
+            ]
+        )
+    }
+
+    #[test]
+    fn scalars_have_no_members() {
+        let number: Value = Value::Number(3.into());
+        assert!(number.iter().is_none());
+        assert!(number.as_list().is_none());
+        assert!(number.as_map().is_none())
+    }
+}
+
+mod ordering {
+    use std::fmt::Display;
+
+    use super::super::{external::ExternalValue, *};
+    use crate::intrisics::Intrisic;
+
+    #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    struct Token;
+    impl Display for Token {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "token")
+        }
+    }
+    impl ExternalValue for Token {
+        const TYPE_NAME: &'static str = "token";
+    }
+
+    /// One value of each type, in the documented order
+    fn one_of_each() -> Vec<Value> {
+        vec![
+            Value::Null(ValueNull),
+            Value::Bool(ValueBool::TRUE),
+            Value::Number((-100).into()),
+            Value::String("".to_owned().into_boxed_str().into()),
+            Value::List(ValueList::from_iter([])),
+            Value::Map(ValueMap::new()),
+            Value::Intrisic(Intrisic::Sum.into()),
+            Value::Closure(Box::new(ValueClosure {
+                params: Box::new([]),
+                captures: Default::default(),
+                body: Value::Null(ValueNull).into(),
+            })),
+            Value::External(ValueExternal::new(Token)),
+        ]
+    }
+
+    #[test]
+    fn types_are_ordered_as_documented() {
+        let values = one_of_each();
+        for (i, a) in values.iter().enumerate() {
+            for (j, b) in values.iter().enumerate() {
+                assert_eq!(
+                    a.cmp(b),
+                    i.cmp(&j),
+                    "{} and {} are in the wrong order",
+                    a.type_name(),
+                    b.type_name()
+                )
+            }
+        }
+    }
+
+    #[test]
+    fn type_comes_before_content() {
+        // the largest boolean is still less than the smallest number
+        assert!(
+            Value::<NoInjectedIntrisics>::Bool(ValueBool::TRUE) < Value::Number((-1000).into())
+        );
+        // a number is less than any string, even the empty one
+        assert!(
+            Value::<NoInjectedIntrisics>::Number(1000.into())
+                < Value::String("".to_owned().into_boxed_str().into())
+        );
+        let mut mixed = one_of_each();
+        mixed.reverse();
+        mixed.sort();
+        assert_eq!(mixed, one_of_each())
+    }
+
+    #[test]
+    fn externals_with_the_same_name_are_different() {
+        #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        struct OtherToken;
+        impl Display for OtherToken {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, "token")
+            }
+        }
+        impl ExternalValue for OtherToken {
+            const TYPE_NAME: &'static str = "token";
+        }
+
+        let token = ValueExternal::new(Token);
+        let other = ValueExternal::new(OtherToken);
+        assert_ne!(token, other);
+        assert_ne!(token.cmp(&other), std::cmp::Ordering::Equal);
+        assert_eq!(token.cmp(&other), other.cmp(&token).reverse())
+    }
+}
+
+#[cfg(feature = "pretty")]
+mod scientific {
+    use pretty::{Arena, Pretty};
+
+    use super::super::*;
+    use crate::fmt::PrettyOptions;
+
+    fn render(value: &Value, options: Option<&PrettyOptions>) -> String {
+        let arena = Arena::<()>::new();
+        let doc = match options {
+            Some(options) => value.pretty_with(&arena, options),
+            None => value.pretty(&arena),
+        };
+        let mut buffer = String::new();
+        doc.render_fmt(80, &mut buffer)
+            .expect("Pretty printing should be infallible");
+        buffer
+    }
+
+    #[test]
+    fn large_numbers_are_written_in_full_by_default() {
+        let value = Value::from(ValueNumber::from(1_234_567_890));
+        assert_eq!(render(&value, None), "1234567890");
+        assert_eq!(
+            render(&value, Some(&PrettyOptions::default())),
+            "1234567890"
+        );
+    }
+
+    #[test]
+    fn large_numbers_can_be_written_in_scientific_notation() {
+        let options = PrettyOptions {
+            scientific_above: Some(ValueNumber::from(1_000_000)),
+            significant_digits: 2,
+        };
+        let value: Value = ValueList::from_iter([
+            Value::from(ValueNumber::from(1_234_567_890)),
+            Value::from(ValueNumber::from(-1_250_000_000)),
+            Value::from(ValueNumber::from(999_999_999)),
+            Value::from(ValueNumber::from(1_000)),
+        ])
+        .into();
+        assert_eq!(render(&value, Some(&options)), "[1.2e9, -1.3e9, 1e9, 1000]");
+    }
+
+    #[test]
+    fn scientific_notation_rounds_the_mantissa() {
+        assert_eq!(ValueNumber::from(123_456).to_scientific(3), "1.23e5");
+        assert_eq!(ValueNumber::from(99_960).to_scientific(3), "1e5");
+        assert_eq!(ValueNumber::from(7).to_scientific(3), "7e0");
+        assert_eq!(ValueNumber::from(-120).to_scientific(5), "-1.2e2");
+    }
+}
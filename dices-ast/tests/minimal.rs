@@ -0,0 +1,32 @@
+//! Check that the core types are usable without any feature
+//!
+//! This must keep compiling with `cargo test -p dices-ast --no-default-features`,
+//! so it can only use the items that are not gated behind a feature.
+
+use dices_ast::{
+    expression::{bin_ops::BinOp, ExpressionBinOp, ExpressionRef},
+    ident::IdentStr,
+    intrisics::NoInjectedIntrisics,
+    value::{ValueList, ValueMap, ValueNull, ValueNumber},
+    Expression, Value,
+};
+
+#[test]
+fn build_and_display_values() {
+    let list: Value =
+        ValueList::from_iter([Value::Number(1.into()), Value::Null(ValueNull)]).into();
+    let map: Value = ValueMap::from_iter([("a".into(), list)]).into();
+    assert_eq!(map.to_string(), "<|a: [1, null]|>")
+}
+
+#[test]
+fn build_and_display_expressions() {
+    let x = IdentStr::new("x").unwrap().to_owned();
+    let expr: Expression<NoInjectedIntrisics> = ExpressionBinOp::new(
+        BinOp::Add,
+        ExpressionRef::new(x).into(),
+        Value::Number(ValueNumber::from(2)).into(),
+    )
+    .into();
+    assert_eq!(expr.to_string(), "x + 2")
+}
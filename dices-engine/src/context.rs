@@ -1,12 +1,32 @@
 //! Context essential to evaluate a `dices` expression
 
-use std::{collections::BTreeMap, mem};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+    io::Write,
+    mem,
+    time::{Duration, Instant},
+};
 
-use dices_ast::{ident::IdentStr, intrisics::InjectedIntr, value::Value};
+use dices_ast::{
+    expression::Expression,
+    ident::IdentStr,
+    intrisics::InjectedIntr,
+    value::{Value, ValueNumber},
+};
 use nunny::NonEmpty;
 
 type Scope<InjectedIntrisic> = BTreeMap<Box<IdentStr>, Value<InjectedIntrisic>>;
 
+/// Default maximum number of iterations of a single loop
+pub const DEFAULT_MAX_ITERATIONS: usize = 10_000;
+
+/// Maximum number of bits of the numbers built by raising to a power or shifting left
+pub const MAX_NUMBER_BITS: u64 = 1 << 16;
+
+/// Maximum length of the strings (in bytes) and lists built by repeating them with `*`
+pub const MAX_REPEATED_LEN: usize = 1 << 20;
+
 pub struct Context<RNG, InjectedIntrisic: InjectedIntr> {
     /// the stack of variables
     scopes: NonEmpty<Vec<Scope<InjectedIntrisic>>>,
@@ -14,17 +34,71 @@ pub struct Context<RNG, InjectedIntrisic: InjectedIntr> {
     rng: RNG,
     /// The data for the injected intrisics
     injected_intrisics_data: <InjectedIntrisic as InjectedIntr>::Data,
+    /// The maximum number of iterations a single loop can do
+    max_iterations: usize,
+    /// How many times each intrisic was called, by id, if counting is enabled
+    intrisic_stats: Option<BTreeMap<&'static str, usize>>,
+    /// Where `print` writes to
+    output: Box<dyn Write>,
+    /// The dice rolled, if they are being recorded
+    rolls: Option<Vec<Roll>>,
+    /// How many rerolls are left, if limited
+    reroll_budget: Option<usize>,
+    /// How long a single evaluation can take, if limited
+    timeout: Option<Duration>,
+    /// When the running evaluation must stop, if limited
+    deadline: Option<Instant>,
+    /// If the lists collect the errors of their elements, instead of failing
+    coalesce_errors: bool,
+    /// The steps of the expression being evaluated, if they are being traced
+    trace: Option<Vec<Trace<InjectedIntrisic>>>,
+    /// Names of the intrisics that cannot be called
+    denied: BTreeSet<&'static str>,
+    /// The name of the std library, used to write the intrisics as source
+    std_name: Cow<'static, IdentStr>,
+}
+
+/// A step of an evaluation, recorded by [`Engine::explain`](crate::Engine::explain)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trace<InjectedIntrisic> {
+    /// The expression evaluated
+    pub expression: Expression<InjectedIntrisic>,
+    /// The value it evaluated to
+    pub value: Value<InjectedIntrisic>,
+    /// The steps evaluating its sub-expressions, in the order they were evaluated
+    pub steps: Vec<Trace<InjectedIntrisic>>,
+}
+
+/// A single die rolled
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Roll {
+    /// The number of faces of the die
+    pub faces: ValueNumber,
+    /// The face it landed on, before any modifier
+    pub result: ValueNumber,
 }
 
 impl<RNG, InjectedIntrisic: InjectedIntr> Context<RNG, InjectedIntrisic> {
     pub fn new(
         rng: RNG,
         injected_intrisics_data: <InjectedIntrisic as InjectedIntr>::Data,
+        max_iterations: usize,
     ) -> Self {
         Self {
             scopes: nunny::vec![Scope::new()],
             rng,
             injected_intrisics_data,
+            max_iterations,
+            intrisic_stats: None,
+            output: Box::new(std::io::stdout()),
+            rolls: None,
+            reroll_budget: None,
+            timeout: None,
+            deadline: None,
+            coalesce_errors: false,
+            trace: None,
+            denied: BTreeSet::new(),
+            std_name: Cow::Borrowed(IdentStr::new("std").unwrap()),
         }
     }
 
@@ -49,6 +123,11 @@ impl<RNG, InjectedIntrisic: InjectedIntr> Context<RNG, InjectedIntrisic> {
         res
     }
 
+    /// Remove all the variables
+    pub fn clear_vars(&mut self) {
+        self.scopes = nunny::vec![Scope::new()]
+    }
+
     /// Obtain a readonly handle to the variables
     pub fn vars(&self) -> Vars<InjectedIntrisic> {
         Vars(&self.scopes)
@@ -63,6 +142,208 @@ impl<RNG, InjectedIntrisic: InjectedIntr> Context<RNG, InjectedIntrisic> {
         &mut self.rng
     }
 
+    /// The maximum number of iterations a single loop can do
+    pub fn max_iterations(&self) -> usize {
+        self.max_iterations
+    }
+
+    /// Obtain an handle to the output `print` writes to
+    pub fn output(&mut self) -> &mut dyn Write {
+        &mut *self.output
+    }
+
+    /// Change the output `print` writes to
+    pub fn set_output(&mut self, output: Box<dyn Write>) {
+        self.output = output
+    }
+
+    /// Start counting the calls to each intrisic
+    pub fn enable_intrisic_stats(&mut self) {
+        self.intrisic_stats.get_or_insert_default();
+    }
+
+    /// How many times each intrisic was called, by id, if counting is enabled
+    pub fn intrisic_stats(&self) -> Option<&BTreeMap<&'static str, usize>> {
+        self.intrisic_stats.as_ref()
+    }
+
+    /// Register a call to an intrisic
+    pub(crate) fn count_intrisic_call(&mut self, id: &'static str) {
+        if let Some(stats) = &mut self.intrisic_stats {
+            *stats.entry(id).or_default() += 1
+        }
+    }
+
+    /// How many rerolls are left, if limited
+    pub fn reroll_budget(&self) -> Option<usize> {
+        self.reroll_budget
+    }
+
+    /// Change how many rerolls are left, or remove the limit with `None`
+    pub fn set_reroll_budget(&mut self, reroll_budget: Option<usize>) {
+        self.reroll_budget = reroll_budget
+    }
+
+    /// Use one of the rerolls
+    ///
+    /// Return `false` if none is left.
+    pub(crate) fn spend_reroll(&mut self) -> bool {
+        match &mut self.reroll_budget {
+            None => true,
+            Some(0) => false,
+            Some(left) => {
+                *left -= 1;
+                true
+            }
+        }
+    }
+
+    /// How long a single evaluation can take, if limited
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Change how long a single evaluation can take, or remove the limit with `None`
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout
+    }
+
+    /// Run an evaluation, stopping it if it takes longer than the timeout
+    ///
+    /// Nested evaluations share the deadline of the outer one.
+    pub(crate) fn timed<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        if self.deadline.is_some() {
+            return f(self);
+        }
+        self.deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        let res = f(self);
+        self.deadline = None;
+        res
+    }
+
+    /// Check if the running evaluation took longer than the timeout
+    ///
+    /// Return the timeout if so.
+    pub(crate) fn timed_out(&self) -> Option<Duration> {
+        self.deadline
+            .filter(|deadline| Instant::now() >= *deadline)
+            .and(self.timeout)
+    }
+
+    /// If the lists collect the errors of their elements, instead of failing
+    pub fn coalesce_errors(&self) -> bool {
+        self.coalesce_errors
+    }
+
+    /// Change if the lists collect the errors of their elements
+    pub fn set_coalesce_errors(&mut self, coalesce_errors: bool) {
+        self.coalesce_errors = coalesce_errors
+    }
+
+    /// If the intrisic with the given name cannot be called
+    pub fn is_denied(&self, name: &str) -> bool {
+        self.denied.contains(name)
+    }
+
+    /// Change the intrisics that cannot be called
+    pub fn set_denied(&mut self, denied: BTreeSet<&'static str>) {
+        self.denied = denied
+    }
+
+    /// The name of the std library, used to write the intrisics as source
+    pub fn std_name(&self) -> &IdentStr {
+        &self.std_name
+    }
+
+    /// Change the name of the std library
+    pub fn set_std_name(&mut self, std_name: Cow<'static, IdentStr>) {
+        self.std_name = std_name
+    }
+
+    /// Snapshot the context, to build copies of it with other RNGs on other threads
+    ///
+    /// The variables, the denied intrisics and the rerolls left are copied, while the output is discarded and the calls
+    /// to the intrisics are not counted.
+    #[cfg(feature = "rayon")]
+    pub(crate) fn fork<NewRNG>(&self) -> impl Fn(NewRNG) -> Context<NewRNG, InjectedIntrisic> + Sync
+    where
+        InjectedIntrisic: Clone + Sync,
+        InjectedIntrisic::Data: Clone + Sync,
+    {
+        let scopes = self.scopes.clone();
+        let injected_intrisics_data = self.injected_intrisics_data.clone();
+        let max_iterations = self.max_iterations;
+        let reroll_budget = self.reroll_budget;
+        let timeout = self.timeout;
+        let coalesce_errors = self.coalesce_errors;
+        let denied = self.denied.clone();
+        let std_name = self.std_name.clone();
+        move |rng| Context {
+            scopes: scopes.clone(),
+            rng,
+            injected_intrisics_data: injected_intrisics_data.clone(),
+            max_iterations,
+            intrisic_stats: None,
+            output: Box::new(std::io::sink()),
+            rolls: None,
+            reroll_budget,
+            timeout,
+            deadline: None,
+            coalesce_errors,
+            trace: None,
+            denied: denied.clone(),
+            std_name: std_name.clone(),
+        }
+    }
+
+    /// Run code, recording the dice it rolls
+    ///
+    /// Recordings can be nested: the outer ones see the dice rolled in the inner ones too.
+    pub(crate) fn recording_rolls<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> (R, Vec<Roll>) {
+        let outer = self.rolls.replace(vec![]);
+        let res = f(self);
+        let rolls = mem::replace(&mut self.rolls, outer).expect("The recording was started");
+        if let Some(outer) = &mut self.rolls {
+            outer.extend(rolls.iter().cloned())
+        }
+        (res, rolls)
+    }
+
+    /// If the steps of the evaluation are being traced
+    pub(crate) fn is_tracing(&self) -> bool {
+        self.trace.is_some()
+    }
+
+    /// Run code, tracing the steps it evaluates
+    ///
+    /// The steps recorded by `f` are returned, and not added to any outer trace.
+    pub(crate) fn tracing<R>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> R,
+    ) -> (R, Vec<Trace<InjectedIntrisic>>) {
+        let outer = self.trace.replace(vec![]);
+        let res = f(self);
+        let steps = mem::replace(&mut self.trace, outer).expect("The tracing was started");
+        (res, steps)
+    }
+
+    /// Register a step of the evaluation, if they are being traced
+    pub(crate) fn record_step(&mut self, step: Trace<InjectedIntrisic>) {
+        if let Some(trace) = &mut self.trace {
+            trace.push(step)
+        }
+    }
+
+    /// Register a die roll, if they are being recorded
+    pub(crate) fn record_roll(&mut self, faces: &ValueNumber, result: &ValueNumber) {
+        if let Some(rolls) = &mut self.rolls {
+            rolls.push(Roll {
+                faces: faces.clone(),
+                result: result.clone(),
+            })
+        }
+    }
+
     pub fn injected_intrisics_data(&self) -> &<InjectedIntrisic as InjectedIntr>::Data {
         &self.injected_intrisics_data
     }
@@ -75,12 +356,17 @@ impl<RNG, InjectedIntrisic: InjectedIntr> Context<RNG, InjectedIntrisic> {
 #[derive(Debug, Clone, Copy)]
 pub struct Vars<'c, InjectedIntrisic>(&'c NonEmpty<[Scope<InjectedIntrisic>]>);
 
-impl<InjectedIntrisic> Vars<'_, InjectedIntrisic> {
+impl<'c, InjectedIntrisic> Vars<'c, InjectedIntrisic> {
     /// Find the value of a variable
-    pub fn get(&self, name: &IdentStr) -> Option<&Value<InjectedIntrisic>> {
+    pub fn get(&self, name: &IdentStr) -> Option<&'c Value<InjectedIntrisic>> {
         // find the last scope that contains that variable
         self.0.iter().rev().find_map(|s| s.get(name))
     }
+
+    /// The variables of the outermost scope, in name order
+    pub fn top_level(&self) -> impl Iterator<Item = (&'c IdentStr, &'c Value<InjectedIntrisic>)> {
+        self.0.first().iter().map(|(name, value)| (&**name, value))
+    }
 }
 impl<'c, InjectedIntrisic> From<VarsMut<'c, InjectedIntrisic>> for Vars<'c, InjectedIntrisic> {
     fn from(value: VarsMut<'c, InjectedIntrisic>) -> Self {
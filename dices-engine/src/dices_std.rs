@@ -47,6 +47,8 @@ where
                 seed: Intrisic::SeedRNG,
                 save: Intrisic::SaveRNG,
                 restore: Intrisic::RestoreRNG,
+                replay_token: Intrisic::ReplayToken,
+                replay: Intrisic::Replay,
             },
             variadics: mod {
                 call: Intrisic::Call,
@@ -54,14 +56,61 @@ where
                 join: Intrisic::Join,
                 mult: Intrisic::Mult,
             },
+            lists: mod {
+                map: Intrisic::Map,
+                filter: Intrisic::Filter,
+                partition: Intrisic::Partition,
+                sort_by: Intrisic::SortBy,
+                sort: Intrisic::Sort,
+                reverse: Intrisic::Reverse,
+                group_by: Intrisic::GroupBy,
+                sample: Intrisic::Sample,
+                tally: Intrisic::Tally,
+                product: Intrisic::Product,
+                mean: Intrisic::Mean,
+                take: Intrisic::Take,
+                drop: Intrisic::Drop,
+                first: Intrisic::First,
+                last: Intrisic::Last,
+            },
+            dice: mod {
+                crit_check: Intrisic::CritCheck,
+                reroll: Intrisic::Reroll,
+                resolve: Intrisic::Resolve,
+                cap: Intrisic::Cap,
+                floor_at: Intrisic::FloorAt,
+                advantage: Intrisic::Advantage,
+                disadvantage: Intrisic::Disadvantage,
+                dist: Intrisic::Dist,
+            },
+            math: mod {
+                mod: Intrisic::Mod,
+                pow: Intrisic::Pow,
+                gcd: Intrisic::Gcd,
+                lcm: Intrisic::Lcm,
+            },
+            io: mod {
+                print: Intrisic::Print,
+            },
+            maps: mod {
+                get: Intrisic::Get,
+                get_path: Intrisic::GetPath,
+                set_path: Intrisic::SetPath,
+                freeze: Intrisic::Freeze,
+            },
             conversions: mod {
                 to_number: Intrisic::ToNumber,
                 to_list: Intrisic::ToList,
                 to_string: Intrisic::ToString,
                 parse: Intrisic::Parse,
+                repr: Intrisic::Repr,
+                scalar: Intrisic::Scalar,
                 to_json: Intrisic::ToJson,
                 from_json: Intrisic::FromJson,
             },
+            patterns: mod {
+                matches: Intrisic::Matches,
+            },
             prelude: mod {
                 sum: Intrisic::Sum,
                 join: Intrisic::Join,
@@ -71,8 +120,47 @@ where
                 to_list: Intrisic::ToList,
                 to_string: Intrisic::ToString,
                 parse: Intrisic::Parse,
+                repr: Intrisic::Repr,
+                scalar: Intrisic::Scalar,
+                matches: Intrisic::Matches,
+
+                map: Intrisic::Map,
+                filter: Intrisic::Filter,
+                partition: Intrisic::Partition,
+                sort_by: Intrisic::SortBy,
+                sort: Intrisic::Sort,
+                reverse: Intrisic::Reverse,
+                group_by: Intrisic::GroupBy,
+                sample: Intrisic::Sample,
+                tally: Intrisic::Tally,
+                product: Intrisic::Product,
+                mean: Intrisic::Mean,
+                take: Intrisic::Take,
+                drop: Intrisic::Drop,
+                first: Intrisic::First,
+                last: Intrisic::Last,
+                get: Intrisic::Get,
+                get_path: Intrisic::GetPath,
+                set_path: Intrisic::SetPath,
+                freeze: Intrisic::Freeze,
 
                 seed: Intrisic::SeedRNG,
+
+                crit_check: Intrisic::CritCheck,
+                reroll: Intrisic::Reroll,
+                resolve: Intrisic::Resolve,
+                cap: Intrisic::Cap,
+                floor_at: Intrisic::FloorAt,
+                advantage: Intrisic::Advantage,
+                disadvantage: Intrisic::Disadvantage,
+                dist: Intrisic::Dist,
+
+                mod: Intrisic::Mod,
+                pow: Intrisic::Pow,
+                gcd: Intrisic::Gcd,
+                lcm: Intrisic::Lcm,
+
+                print: Intrisic::Print,
             },
             versions: mod {
                 ast: version_value()
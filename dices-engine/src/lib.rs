@@ -5,33 +5,80 @@
 #![feature(box_patterns)]
 #![feature(type_changing_struct_update)]
 
-use std::borrow::Cow;
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+};
 
 use nunny::NonEmpty;
 use rand::{Rng, SeedableRng};
 
 use dices_ast::{
+    expression::ExpressionCall,
     ident::IdentStr,
-    intrisics::{InjectedIntr, NoInjectedIntrisics},
+    intrisics::{InjectedIntr, Intrisic, NoInjectedIntrisics},
+    value::ValueMap,
     Expression, Value,
 };
 
 use serde::{de::DeserializeOwned, Serialize};
-use solve::{solve_multiple, Solvable};
+use solve::{free_vars, solve_multiple, Solvable};
 
-pub use context::Context;
+pub use context::{Context, Trace, DEFAULT_MAX_ITERATIONS, MAX_NUMBER_BITS, MAX_REPEATED_LEN};
 pub use dices_std::std as dices_std;
-pub use solve::{IntrisicError, SolveError};
+pub use solve::{arity, is_effectful, IntrisicError, SolveError, VarUseCalcError};
 
 mod context;
 mod dices_std;
+pub mod rngs;
 mod solve;
 
+#[cfg(test)]
+mod tests;
+
+/// A writer whose content can be read after giving it to the engine, to test what is printed
+#[cfg(any(feature = "test_output", test))]
+#[derive(Debug, Clone, Default)]
+pub struct SharedOutput(std::rc::Rc<std::cell::RefCell<Vec<u8>>>);
+#[cfg(any(feature = "test_output", test))]
+impl SharedOutput {
+    /// Take what was written until now
+    pub fn take(&self) -> String {
+        String::from_utf8(self.0.take()).expect("The output should be valid UTF-8")
+    }
+}
+#[cfg(any(feature = "test_output", test))]
+impl std::io::Write for SharedOutput {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
 pub struct EngineBuilder<RNG = (), InjectedIntrisic: InjectedIntr = NoInjectedIntrisics> {
     rng: RNG,
     std: Option<Cow<'static, IdentStr>>,
+    /// The std library to use instead of the default one
+    std_map: Option<ValueMap<InjectedIntrisic>>,
     prelude: bool,
     injected_intrisics_data: <InjectedIntrisic as InjectedIntr>::Data,
+    max_iterations: usize,
+    /// Names of the intrisics removed from the std library
+    denied: BTreeSet<&'static str>,
+    intrisic_stats: bool,
+    /// Where `print` writes to, if not the standard output
+    output: Option<Box<dyn std::io::Write>>,
+    /// How many times `reroll` can be called, if limited
+    reroll_budget: Option<usize>,
+    /// How long a single evaluation can take, if limited
+    timeout: Option<std::time::Duration>,
+    /// If the lists collect the errors of their elements
+    coalesce_errors: bool,
+    /// The operators defined by the user
+    #[cfg(feature = "eval_str")]
+    operators: dices_ast::expression::CustomOperators,
 }
 impl EngineBuilder<(), NoInjectedIntrisics> {
     /// Start building a new engine
@@ -39,8 +86,18 @@ impl EngineBuilder<(), NoInjectedIntrisics> {
         Self {
             rng: (),
             std: Some(Cow::Borrowed(IdentStr::new("std").unwrap())),
+            std_map: None,
             prelude: true,
             injected_intrisics_data: (),
+            max_iterations: DEFAULT_MAX_ITERATIONS,
+            denied: BTreeSet::new(),
+            intrisic_stats: false,
+            output: None,
+            reroll_budget: None,
+            timeout: None,
+            coalesce_errors: false,
+            #[cfg(feature = "eval_str")]
+            operators: dices_ast::expression::CustomOperators::new(),
         }
     }
 }
@@ -68,6 +125,8 @@ impl<RNG, InjectedIntrisic: InjectedIntr> EngineBuilder<RNG, InjectedIntrisic> {
     {
         EngineBuilder {
             injected_intrisics_data: Default::default(),
+            // a custom std library holds the old intrisics
+            std_map: None,
             ..self
         }
     }
@@ -79,6 +138,8 @@ impl<RNG, InjectedIntrisic: InjectedIntr> EngineBuilder<RNG, InjectedIntrisic> {
     ) -> EngineBuilder<RNG, NewInjected> {
         EngineBuilder {
             injected_intrisics_data: data,
+            // a custom std library holds the old intrisics
+            std_map: None,
             ..self
         }
     }
@@ -101,6 +162,17 @@ impl<RNG, InjectedIntrisic: InjectedIntr> EngineBuilder<RNG, InjectedIntrisic> {
         }
     }
 
+    /// Use a custom std library, instead of the default one
+    ///
+    /// The entries of its `prelude` map, if any, are imported as the ones of the default library.
+    /// As the map contains the injected intrisics, this must be called after injecting them.
+    pub fn with_std_map(self, std: ValueMap<InjectedIntrisic>) -> Self {
+        Self {
+            std_map: Some(std),
+            ..self
+        }
+    }
+
     /// Do not put the std library in the engine
     ///
     /// This will make most of the intrisics unreachable
@@ -126,6 +198,115 @@ impl<RNG, InjectedIntrisic: InjectedIntr> EngineBuilder<RNG, InjectedIntrisic> {
         }
     }
 
+    /// Set the maximum number of iterations a single loop can do
+    pub fn with_max_iterations(self, max_iterations: usize) -> Self {
+        Self {
+            max_iterations,
+            ..self
+        }
+    }
+
+    /// Send the output of `print` to a writer, instead of the standard output
+    pub fn with_output_writer(self, output: impl std::io::Write + 'static) -> Self {
+        Self {
+            output: Some(Box::new(output)),
+            ..self
+        }
+    }
+
+    /// Limit how many times `reroll` can be called
+    ///
+    /// The budget is shared by all the commands evaluated by the engine. Without a budget, the
+    /// rerolls are unlimited.
+    pub fn with_reroll_budget(self, reroll_budget: usize) -> Self {
+        Self {
+            reroll_budget: Some(reroll_budget),
+            ..self
+        }
+    }
+
+    /// Stop the evaluations that take longer than `timeout`
+    ///
+    /// The evaluations that run out of time fail with [`SolveError::TimedOut`].
+    pub fn with_timeout(self, timeout: std::time::Duration) -> Self {
+        Self {
+            timeout: Some(timeout),
+            ..self
+        }
+    }
+
+    /// Let the lists collect the errors of their elements, instead of failing
+    ///
+    /// Each element of a list literal becomes a map: `<|ok: value|>` if it was evaluated, or
+    /// `<|err: message|>` if it failed. Without this, the first error fails the whole list.
+    pub fn with_coalesce_errors(self) -> Self {
+        Self {
+            coalesce_errors: true,
+            ..self
+        }
+    }
+
+    /// Count how many times each intrisic is called
+    ///
+    /// The counts are available from [`Engine::intrisic_stats`].
+    pub fn with_intrisic_stats(self) -> Self {
+        Self {
+            intrisic_stats: true,
+            ..self
+        }
+    }
+
+    #[cfg(feature = "eval_str")]
+    /// Define a binary operator, that calls an intrisic with its two operands
+    ///
+    /// The operator is recognized only when evaluating strings with this engine.
+    pub fn with_operator(
+        mut self,
+        symbol: &str,
+        intrisic: Intrisic<InjectedIntrisic>,
+    ) -> Result<Self, dices_ast::expression::InvalidOperator> {
+        self.operators.insert(symbol, intrisic.name())?;
+        Ok(self)
+    }
+
+    /// Remove an intrisic from the std library and the prelude, and forbid calling it
+    ///
+    /// The intrisic cannot be called even if obtained in other ways, like deserializing it or through
+    /// the syntax that desugars to it. The intrisic is recognized by its name, so this must be called after injecting the intrisics
+    /// if an injected one has to be removed.
+    pub fn deny_intrisic(mut self, name: &str) -> Result<Self, UnknownIntrisic> {
+        let intrisic = Intrisic::<InjectedIntrisic>::named(name)
+            .ok_or_else(|| UnknownIntrisic(name.to_owned()))?;
+        self.denied.insert(intrisic.name());
+        Ok(self)
+    }
+
+    /// Remove all the intrisics from the std library and the prelude, apart from the given ones, and forbid
+    /// calling them
+    ///
+    /// The intrisics are recognized by their names, so this must be called after injecting the intrisics
+    /// if an injected one has to be kept.
+    pub fn allow_only<'n>(
+        mut self,
+        names: impl IntoIterator<Item = &'n str>,
+    ) -> Result<Self, UnknownIntrisic> {
+        let allowed: BTreeSet<_> = names
+            .into_iter()
+            .map(|name| {
+                Intrisic::<InjectedIntrisic>::named(name)
+                    .map(|intrisic| intrisic.name())
+                    .ok_or_else(|| UnknownIntrisic(name.to_owned()))
+            })
+            .try_collect()?;
+        self.denied.extend(
+            Intrisic::<InjectedIntrisic>::iter()
+                .into_iter()
+                .map(|intrisic| intrisic.name())
+                .filter(|name| !allowed.contains(name)),
+        );
+        Ok(self)
+    }
+
     /// Build the engine
     pub fn build(self) -> Engine<RNG, InjectedIntrisic>
     where
@@ -134,37 +315,196 @@ impl<RNG, InjectedIntrisic: InjectedIntr> EngineBuilder<RNG, InjectedIntrisic> {
         let Self {
             rng,
             std,
+            std_map,
             prelude,
             injected_intrisics_data,
+            max_iterations,
+            denied,
+            intrisic_stats,
+            output,
+            reroll_budget,
+            timeout,
+            coalesce_errors,
+            #[cfg(feature = "eval_str")]
+            operators,
         } = self;
         // build context
-        let mut context = Context::new(rng, injected_intrisics_data);
+        let mut context = Context::new(rng, injected_intrisics_data, max_iterations);
+        if intrisic_stats {
+            context.enable_intrisic_stats()
+        }
+        if let Some(output) = output {
+            context.set_output(output)
+        }
+        context.set_reroll_budget(reroll_budget);
+        context.set_timeout(timeout);
+        context.set_coalesce_errors(coalesce_errors);
         // adding std and prelude
-        if let Some(std_name) = std {
-            // generating the std library
-            let std = dices_std::std();
-            // adding the prelude
-            if prelude {
-                let Some(Value::Map(prelude)) = std.get("prelude") else {
-                    panic!("`std` should always contains a map called `prelude`")
-                };
-                for (name, value) in prelude.iter() {
-                    let name = IdentStr::new_boxed(name.clone().into()).expect(
-                        "The values in `prelude` should all be named with valid identifiers",
-                    );
-                    context.vars_mut().let_(name, value.clone())
+        let std = StdSetup {
+            name: std,
+            map: std_map,
+            prelude,
+            denied,
+        };
+        std.install(&mut context);
+
+        Engine {
+            context,
+            std,
+            #[cfg(feature = "eval_str")]
+            operators,
+        }
+    }
+}
+
+/// How the std library is installed in an engine
+struct StdSetup<InjectedIntrisic> {
+    /// The name of the std library, if installed
+    name: Option<Cow<'static, IdentStr>>,
+    /// The std library, if not the default one
+    map: Option<ValueMap<InjectedIntrisic>>,
+    /// If the prelude is imported
+    prelude: bool,
+    /// Names of the intrisics removed from the std library
+    denied: BTreeSet<&'static str>,
+}
+impl<InjectedIntrisic> StdSetup<InjectedIntrisic>
+where
+    InjectedIntrisic: InjectedIntr + Clone,
+{
+    /// Generate the std library, without the denied intrisics
+    fn library(&self) -> ValueMap<InjectedIntrisic> {
+        let mut std = match &self.map {
+            Some(map) => map.clone(),
+            None => dices_std::std(),
+        };
+        if !self.denied.is_empty() {
+            remove_intrisics(&mut std, &self.denied)
+        }
+        std
+    }
+
+    /// Add the std library and the prelude to the variables of a context
+    ///
+    /// A custom library can miss the prelude, or have entries in it that are not valid names: those
+    /// are not imported. The denied intrisics are forbidden in the context even if no library is installed.
+    fn install<RNG>(&self, context: &mut Context<RNG, InjectedIntrisic>) {
+        context.set_denied(self.denied.clone());
+        let Some(std_name) = &self.name else {
+            return;
+        };
+        context.set_std_name(std_name.clone());
+        let std = self.library();
+        // adding the prelude
+        if self.prelude {
+            let prelude = match std.get("prelude") {
+                Some(Value::Map(prelude)) => Some(prelude),
+                _ if self.map.is_some() => None,
+                _ => panic!("`std` should always contains a map called `prelude`"),
+            };
+            for (name, value) in prelude.into_iter().flat_map(ValueMap::iter) {
+                match IdentStr::new_boxed(name.clone().into()) {
+                    Ok(name) => context.vars_mut().let_(name, value.clone()),
+                    Err(_) if self.map.is_some() => (),
+                    Err(_) => {
+                        panic!("The values in `prelude` should all be named with valid identifiers")
+                    }
                 }
             }
-            // adding the std library
-            context.vars_mut().let_(std_name.into_owned(), std.into());
         }
+        // adding the std library
+        context
+            .vars_mut()
+            .let_(std_name.clone().into_owned(), std.into());
+    }
+}
+
+/// Remove the intrisics with the given names from a module of the std library and its submodules
+fn remove_intrisics<InjectedIntrisic: InjectedIntr>(
+    module: &mut ValueMap<InjectedIntrisic>,
+    names: &BTreeSet<&'static str>,
+) {
+    let removed: Vec<_> = module
+        .iter()
+        .filter(|(_, value)| {
+            matches!(value, Value::Intrisic(intrisic) if names.contains(intrisic.0.name()))
+        })
+        .map(|(key, _)| key.clone())
+        .collect();
+    for key in removed {
+        module.remove(&key);
+    }
+    for (_, value) in module.iter_mut() {
+        if let Value::Map(submodule) = value {
+            remove_intrisics(submodule, names)
+        }
+    }
+}
+
+/// Collect the intrisics in a module of the std library and its submodules
+fn collect_intrisics<InjectedIntrisic: InjectedIntr>(
+    module: &ValueMap<InjectedIntrisic>,
+    found: &mut BTreeMap<&'static str, Intrisic<InjectedIntrisic>>,
+) {
+    for (_, value) in module.iter() {
+        match value {
+            Value::Intrisic(intrisic) => {
+                found.insert(intrisic.0.name(), intrisic.0.clone());
+            }
+            Value::Map(submodule) => collect_intrisics(submodule, found),
+            _ => (),
+        }
+    }
+}
+
+/// The signature of an intrisic
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntrisicSignature {
+    /// The name of the intrisic
+    pub name: &'static str,
+    /// The minimum number of params
+    pub min_params: usize,
+    /// The maximum number of params, `None` if it accepts any number
+    pub max_params: Option<usize>,
+    /// If calling it has effects on the engine, see [`is_effectful`]
+    pub effectful: bool,
+}
+impl IntrisicSignature {
+    /// The signature of an intrisic
+    pub fn of<InjectedIntrisic>(intrisic: &Intrisic<InjectedIntrisic>) -> Self
+    where
+        InjectedIntrisic: InjectedIntr,
+    {
+        let (min_params, max_params) = match arity(intrisic) {
+            Some(arity) => (*arity.start(), Some(*arity.end())),
+            None => (0, None),
+        };
+        Self {
+            name: intrisic.name(),
+            min_params,
+            max_params,
+            effectful: is_effectful(intrisic),
+        }
+    }
 
-        Engine { context }
+    /// If the intrisic accepts any number of params
+    pub fn is_variadic(&self) -> bool {
+        self.max_params.is_none()
     }
 }
 
+/// Error when referring to an intrisic that does not exist
+#[derive(Debug, Clone, derive_more::Display, derive_more::Error)]
+#[display("No intrisic is named `{_0}`")]
+pub struct UnknownIntrisic(#[error(not(source))] pub String);
+
 pub struct Engine<RNG, InjectedIntrisic: InjectedIntr> {
     context: Context<RNG, InjectedIntrisic>,
+    /// How the std library was installed, to reinstall it on reset
+    std: StdSetup<InjectedIntrisic>,
+    /// The operators defined by the user
+    #[cfg(feature = "eval_str")]
+    operators: dices_ast::expression::CustomOperators,
 }
 
 #[cfg(feature = "eval_str")]
@@ -172,6 +512,26 @@ pub struct Engine<RNG, InjectedIntrisic: InjectedIntr> {
 pub type EvalStrError<InjectedIntrisic> =
     either::Either<dices_ast::expression::ParseError, SolveError<InjectedIntrisic>>;
 
+#[cfg(feature = "eval_str")]
+/// Error during evaluation of a reader
+#[derive(Debug, derive_more::Display, derive_more::Error)]
+pub enum EvalReaderError<InjectedIntrisic: InjectedIntr> {
+    #[display("Error while reading the source")]
+    Io(#[error(source)] std::io::Error),
+    #[display("Syntax error in the statement starting at line {line}")]
+    Parse {
+        line: usize,
+        #[error(source)]
+        source: dices_ast::expression::ParseError,
+    },
+    #[display("Error while evaluating the statement starting at line {line}")]
+    Solve {
+        line: usize,
+        #[error(source)]
+        source: SolveError<InjectedIntrisic>,
+    },
+}
+
 impl<RNG, InjectedIntrisic: InjectedIntr> Engine<RNG, InjectedIntrisic> {
     /// Initialize a new engine
     ///
@@ -209,7 +569,7 @@ impl<RNG, InjectedIntrisic: InjectedIntr> Engine<RNG, InjectedIntrisic> {
         RNG: DicesRng,
         InjectedIntrisic: Clone,
     {
-        expr.solve(&mut self.context)
+        self.context.timed(|context| expr.solve(context))
     }
 
     /// Evaluate the result of multiple expressions, returning the last one
@@ -221,7 +581,123 @@ impl<RNG, InjectedIntrisic: InjectedIntr> Engine<RNG, InjectedIntrisic> {
         RNG: DicesRng,
         InjectedIntrisic: Clone,
     {
-        solve_multiple(exprs, &mut self.context)
+        self.context.timed(|context| solve_multiple(exprs, context))
+    }
+
+    /// Evaluate the result of multiple expressions, returning the last one
+    ///
+    /// Unlike [`Engine::eval_multiple`], this accepts any slice, and fails if it is empty.
+    pub fn eval_slice(
+        &mut self,
+        exprs: &[Expression<InjectedIntrisic>],
+    ) -> Result<Value<InjectedIntrisic>, SolveError<InjectedIntrisic>>
+    where
+        RNG: DicesRng,
+        InjectedIntrisic: Clone,
+    {
+        let exprs = NonEmpty::<[_]>::new(exprs).ok_or(SolveError::NothingToEvaluate)?;
+        self.eval_multiple(exprs)
+    }
+
+    /// Evaluate an expression, recording each step of the evaluation
+    ///
+    /// The trace has a step for each sub-expression evaluated, with the value it evaluated to.
+    /// Closures and loops add a step each time their body is evaluated.
+    pub fn explain(
+        &mut self,
+        expr: &Expression<InjectedIntrisic>,
+    ) -> Result<Trace<InjectedIntrisic>, SolveError<InjectedIntrisic>>
+    where
+        RNG: DicesRng,
+        InjectedIntrisic: Clone,
+    {
+        let (value, mut steps) = self
+            .context
+            .timed(|context| context.tracing(|context| expr.solve(context)));
+        value?;
+        Ok(steps
+            .pop()
+            .expect("The expression evaluated should be traced"))
+    }
+
+    /// Call the function or intrisic bound to a variable, as `name(args...)` would
+    ///
+    /// Fails with [`SolveError::InvalidReference`] if the variable is not defined, and with
+    /// [`SolveError::NotCallable`] if its value cannot be called.
+    pub fn call(
+        &mut self,
+        name: &str,
+        args: Vec<Value<InjectedIntrisic>>,
+    ) -> Result<Value<InjectedIntrisic>, SolveError<InjectedIntrisic>>
+    where
+        RNG: DicesRng,
+        InjectedIntrisic: Clone,
+    {
+        let name = IdentStr::new(name).ok_or_else(|| SolveError::InvalidName(name.to_owned()))?;
+        let called = self
+            .context
+            .vars()
+            .get(name)
+            .cloned()
+            .ok_or_else(|| SolveError::InvalidReference(name.to_owned()))?;
+        let call = ExpressionCall::new(called.into(), args.into_iter().map(Into::into).collect());
+        self.context.timed(|context| call.solve(context))
+    }
+
+    /// Evaluate an expression once for each seed, each time with a new RNG seeded with it
+    ///
+    /// The RNG and the rerolls left of the engine are left untouched, and the variables defined by
+    /// the expression are dropped after each evaluation.
+    pub fn eval_batch(
+        &mut self,
+        expr: &Expression<InjectedIntrisic>,
+        seeds: &[u64],
+    ) -> Result<Vec<Value<InjectedIntrisic>>, SolveError<InjectedIntrisic>>
+    where
+        RNG: DicesRng,
+        InjectedIntrisic: Clone,
+    {
+        seeds
+            .iter()
+            .map(|&seed| {
+                let rng = std::mem::replace(self.context.rng(), RNG::seed_from_u64(seed));
+                let reroll_budget = self.context.reroll_budget();
+                let res = self
+                    .context
+                    .timed(|context| context.scoped(|context| expr.solve(context)));
+                *self.context.rng() = rng;
+                self.context.set_reroll_budget(reroll_budget);
+                res
+            })
+            .collect()
+    }
+
+    #[cfg(feature = "rayon")]
+    /// Like [`Engine::eval_batch`], but evaluating the seeds in parallel
+    ///
+    /// Each seed is evaluated in a copy of the engine, so the results are the same of the
+    /// sequential version, in the same order. The output of `print` is discarded.
+    pub fn eval_batch_par(
+        &self,
+        expr: &Expression<InjectedIntrisic>,
+        seeds: &[u64],
+    ) -> Result<Vec<Value<InjectedIntrisic>>, SolveError<InjectedIntrisic>>
+    where
+        RNG: DicesRng,
+        InjectedIntrisic: Clone + Send + Sync,
+        InjectedIntrisic::Data: Clone + Send + Sync,
+        InjectedIntrisic::Error: Send,
+    {
+        use rayon::prelude::*;
+
+        let fork = self.context.fork();
+        seeds
+            .par_iter()
+            .map(|&seed| {
+                let mut context = fork(RNG::seed_from_u64(seed));
+                context.timed(|context| context.scoped(|context| expr.solve(context)))
+            })
+            .collect()
     }
 
     #[cfg(feature = "eval_str")]
@@ -234,10 +710,191 @@ impl<RNG, InjectedIntrisic: InjectedIntr> Engine<RNG, InjectedIntrisic> {
         RNG: DicesRng,
         InjectedIntrisic: Clone,
     {
-        let exprs = dices_ast::parse_file(cmd).map_err(either::Either::Left)?;
+        let exprs = self.parse_str(cmd).map_err(either::Either::Left)?;
         self.eval_multiple(&exprs).map_err(either::Either::Right)
     }
 
+    #[cfg(feature = "eval_str")]
+    /// Parse a command string, recognizing the operators defined in this engine
+    pub fn parse_str(
+        &self,
+        cmd: &str,
+    ) -> Result<Box<NonEmpty<[Expression<InjectedIntrisic>]>>, dices_ast::expression::ParseError>
+    {
+        dices_ast::expression::parse_file_with_operators(cmd, &self.operators)
+    }
+
+    #[cfg(feature = "eval_str")]
+    /// Evaluate a source from a reader, returning the value of the last statement
+    ///
+    /// The source is read and evaluated one statement at a time, so it is never
+    /// loaded fully in memory.
+    pub fn eval_reader(
+        &mut self,
+        mut reader: impl std::io::BufRead,
+    ) -> Result<Value<InjectedIntrisic>, EvalReaderError<InjectedIntrisic>>
+    where
+        RNG: DicesRng,
+        InjectedIntrisic: Clone,
+    {
+        let mut buf = String::new();
+        // position where the buffer starts
+        let mut start = dices_ast::expression::Position::START;
+        loop {
+            let read = reader.read_line(&mut buf).map_err(EvalReaderError::Io)?;
+            while let Some(end) = dices_ast::tokens::statement_end(&buf) {
+                self.eval_statement(&buf[..end], &start)?;
+                start.advance(&buf[..=end]);
+                buf.drain(..=end);
+            }
+            if read == 0 {
+                break;
+            }
+        }
+        // the last statement gives the value of the whole source
+        self.eval_statement(&buf, &start)
+    }
+
+    #[cfg(feature = "eval_str")]
+    /// Evaluate a single statement of a longer source
+    fn eval_statement(
+        &mut self,
+        statement: &str,
+        start: &dices_ast::expression::Position,
+    ) -> Result<Value<InjectedIntrisic>, EvalReaderError<InjectedIntrisic>>
+    where
+        RNG: DicesRng,
+        InjectedIntrisic: Clone,
+    {
+        // the statement starts at its first token, after the whitespace and comments
+        let line = start.line
+            + dices_ast::tokens::tokenize(statement)
+                .take_while(|t| {
+                    matches!(
+                        t.kind,
+                        dices_ast::tokens::TokenKind::Whitespace
+                            | dices_ast::tokens::TokenKind::Comment
+                    )
+                })
+                .map(|t| statement[t.span].matches('\n').count())
+                .sum::<usize>();
+        let exprs =
+            dices_ast::expression::parse_file_with_operators_at(statement, &self.operators, *start)
+                .map_err(|mut source| {
+                    // make the location relative to the whole source
+                    if source.location.line == 1 {
+                        source.location.column += start.column - 1;
+                    }
+                    source.location.line += start.line - 1;
+                    source.location.offset += start.offset;
+                    EvalReaderError::Parse { line, source }
+                })?;
+        self.eval_multiple(&exprs)
+            .map_err(|source| EvalReaderError::Solve { line, source })
+    }
+
+    /// Find the variables an expression uses without defining them, and that are not in the context
+    ///
+    /// Nothing is evaluated. The names defined by the expression before using them, the ones
+    /// captured by its closures and the ones already in the context (like the std library and the
+    /// prelude) are accepted. The undefined names are returned sorted.
+    pub fn check_refs(
+        &self,
+        expr: &Expression<InjectedIntrisic>,
+    ) -> Result<Vec<Box<IdentStr>>, VarUseCalcError> {
+        let vars = self.context.vars();
+        Ok(free_vars(expr)?
+            .into_iter()
+            .filter(|name| vars.get(name).is_none())
+            .map(ToOwned::to_owned)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect())
+    }
+
+    /// Remove all the variables, installing again the std library and the prelude
+    ///
+    /// The RNG, the intrisics data and the output are kept, so the rolls continue
+    /// the same sequence they would have followed without the reset.
+    pub fn reset(&mut self)
+    where
+        InjectedIntrisic: Clone,
+    {
+        self.context.clear_vars();
+        self.std.install(&mut self.context)
+    }
+
+    /// The signatures of the intrisics reachable from the std library, ordered by name
+    ///
+    /// The denied intrisics are left out, and if the engine has no std library the list is empty.
+    pub fn intrisic_signatures(&self) -> Vec<IntrisicSignature>
+    where
+        InjectedIntrisic: Clone,
+    {
+        if self.std.name.is_none() {
+            return vec![];
+        }
+        let mut found = BTreeMap::new();
+        collect_intrisics(&self.std.library(), &mut found);
+        found.values().map(IntrisicSignature::of).collect()
+    }
+
+    /// The variables defined at the top level, in name order
+    ///
+    /// This includes the std library and the prelude, see [`Engine::user_variables`] to skip them.
+    pub fn variables(&self) -> impl Iterator<Item = (&IdentStr, &Value<InjectedIntrisic>)> {
+        self.context.vars().top_level()
+    }
+
+    /// The variables defined at the top level, apart from the std library and the prelude
+    ///
+    /// A prelude name is listed only if it was given a new value.
+    pub fn user_variables(&self) -> impl Iterator<Item = (&IdentStr, &Value<InjectedIntrisic>)>
+    where
+        InjectedIntrisic: Clone,
+    {
+        let std_name = self.std.name.as_deref();
+        let prelude = match self.std.library().remove("prelude") {
+            Some(Value::Map(prelude)) if self.std.prelude && std_name.is_some() => prelude,
+            _ => ValueMap::new(),
+        };
+        self.variables().filter(move |(name, value)| {
+            Some(*name) != std_name && prelude.get(name) != Some(*value)
+        })
+    }
+
+    /// The value of a variable, if it is defined
+    pub fn get_var(&self, name: &IdentStr) -> Option<&Value<InjectedIntrisic>> {
+        self.context.vars().get(name)
+    }
+
+    /// How many rerolls are left, if they are limited
+    pub fn reroll_budget(&self) -> Option<usize> {
+        self.context.reroll_budget()
+    }
+
+    /// Change how many rerolls are left, or remove the limit with `None`
+    pub fn set_reroll_budget(&mut self, reroll_budget: Option<usize>) {
+        self.context.set_reroll_budget(reroll_budget)
+    }
+
+    /// How long a single evaluation can take, if limited
+    pub fn timeout(&self) -> Option<std::time::Duration> {
+        self.context.timeout()
+    }
+
+    /// Change how long a single evaluation can take, or remove the limit with `None`
+    pub fn set_timeout(&mut self, timeout: Option<std::time::Duration>) {
+        self.context.set_timeout(timeout)
+    }
+
+    /// How many times each intrisic was called, by [id](Intrisic::id)
+    ///
+    /// This is `None` if the engine was not built [with the stats](EngineBuilder::with_intrisic_stats).
+    pub fn intrisic_stats(&self) -> Option<&BTreeMap<&'static str, usize>> {
+        self.context.intrisic_stats()
+    }
+
     pub fn injected_intrisics_data(&self) -> &<InjectedIntrisic as InjectedIntr>::Data {
         self.context.injected_intrisics_data()
     }
@@ -247,5 +904,13 @@ impl<RNG, InjectedIntrisic: InjectedIntr> Engine<RNG, InjectedIntrisic> {
     }
 }
 
-pub trait DicesRng: Rng + SeedableRng + Serialize + DeserializeOwned {}
-impl<T> DicesRng for T where T: Rng + SeedableRng + Serialize + DeserializeOwned {}
+/// An RNG that can be used by the engine
+///
+/// It is implemented for the RNGs of `rand_xoshiro`, `rand_chacha` and `rand_pcg`, and for
+/// [`NamedRng`](rngs::NamedRng).
+pub trait DicesRng: Rng + SeedableRng + Serialize + DeserializeOwned {
+    /// Seed again this RNG, from the given seed or from entropy
+    fn reseed(&mut self, seed: Option<u64>) {
+        *self = seed.map_or_else(Self::from_entropy, Self::seed_from_u64)
+    }
+}
@@ -0,0 +1,175 @@
+//! A registry of RNGs that can be chosen by name
+//!
+//! [`NamedRng`] wraps one of the registered algorithms, so configurations and command
+//! lines can pick the RNG with a string instead of fixing its type at compile time.
+//!
+//! The same seed gives reproducible results only with the same algorithm: rolls made with
+//! `chacha8` and seed 42 have nothing in common with the ones made with `xoshiro256++` and
+//! seed 42. The saved state of an RNG (see `std.rng.save`) records the algorithm, so restoring
+//! it also restores the algorithm it was saved from.
+
+use derive_more::{Display, Error};
+use rand::{RngCore, SeedableRng};
+use rand_chacha::{ChaCha12Rng, ChaCha20Rng, ChaCha8Rng};
+use rand_pcg::{Pcg32, Pcg64, Pcg64Mcg};
+use rand_xoshiro::{
+    SplitMix64, Xoroshiro128Plus, Xoroshiro128PlusPlus, Xoroshiro128StarStar, Xoshiro128Plus,
+    Xoshiro128PlusPlus, Xoshiro128StarStar, Xoshiro256Plus, Xoshiro256PlusPlus, Xoshiro256StarStar,
+    Xoshiro512Plus, Xoshiro512PlusPlus, Xoshiro512StarStar,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::DicesRng;
+
+macro_rules! named_rngs {
+    (
+        $(
+            $(#[$attr:meta])*
+            $variant:ident($rng:ty) <=> $name:literal
+        ),*
+    ) => {
+        /// An RNG whose algorithm is chosen at runtime
+        #[derive(Debug, Clone, Serialize, Deserialize)]
+        pub enum NamedRng {
+            $(
+                $(#[$attr])*
+                $variant($rng),
+            )*
+        }
+
+        impl NamedRng {
+            /// The names of all the registered RNGs
+            pub const NAMES: &'static [&'static str] = &[$($name),*];
+
+            /// The name of the algorithm of this RNG
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(
+                        NamedRng::$variant(_) => $name,
+                    )*
+                }
+            }
+
+            /// Build the RNG with the given name, seeded with `seed`
+            pub fn seed_from_u64_named(name: &str, seed: u64) -> Result<Self, UnknownRng> {
+                Ok(match name {
+                    $(
+                        $name => NamedRng::$variant(<$rng>::seed_from_u64(seed)),
+                    )*
+                    _ => return Err(UnknownRng(name.to_owned())),
+                })
+            }
+
+            /// Build the RNG with the given name, seeded from entropy
+            pub fn from_entropy_named(name: &str) -> Result<Self, UnknownRng> {
+                Ok(match name {
+                    $(
+                        $name => NamedRng::$variant(<$rng>::from_entropy()),
+                    )*
+                    _ => return Err(UnknownRng(name.to_owned())),
+                })
+            }
+
+            /// Seed again this RNG, keeping the same algorithm
+            fn reseed_same(&mut self, seed: Option<u64>) {
+                match self {
+                    $(
+                        NamedRng::$variant(rng) => {
+                            *rng = seed.map_or_else(<$rng>::from_entropy, <$rng>::seed_from_u64)
+                        }
+                    )*
+                }
+            }
+        }
+
+        impl RngCore for NamedRng {
+            fn next_u32(&mut self) -> u32 {
+                match self {
+                    $(
+                        NamedRng::$variant(rng) => rng.next_u32(),
+                    )*
+                }
+            }
+
+            fn next_u64(&mut self) -> u64 {
+                match self {
+                    $(
+                        NamedRng::$variant(rng) => rng.next_u64(),
+                    )*
+                }
+            }
+
+            fn fill_bytes(&mut self, dest: &mut [u8]) {
+                match self {
+                    $(
+                        NamedRng::$variant(rng) => rng.fill_bytes(dest),
+                    )*
+                }
+            }
+
+            fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+                match self {
+                    $(
+                        NamedRng::$variant(rng) => rng.try_fill_bytes(dest),
+                    )*
+                }
+            }
+        }
+    };
+}
+
+named_rngs! {
+    /// The default RNG, fast and with a small state
+    Xoshiro256PlusPlus(Xoshiro256PlusPlus) <=> "xoshiro256++",
+    Xoshiro256StarStar(Xoshiro256StarStar) <=> "xoshiro256**",
+    /// A cryptographically secure RNG, with a reduced number of rounds
+    ChaCha8(ChaCha8Rng) <=> "chacha8",
+    /// A cryptographically secure RNG
+    ChaCha20(ChaCha20Rng) <=> "chacha20",
+    /// A permuted congruential generator, with 128 bits of state
+    Pcg(Pcg64) <=> "pcg"
+}
+
+/// The default algorithm is used when the RNG is built without a name
+impl SeedableRng for NamedRng {
+    type Seed = <Xoshiro256PlusPlus as SeedableRng>::Seed;
+
+    fn from_seed(seed: Self::Seed) -> Self {
+        NamedRng::Xoshiro256PlusPlus(Xoshiro256PlusPlus::from_seed(seed))
+    }
+
+    fn seed_from_u64(state: u64) -> Self {
+        // the default algorithm has its own way to expand the seed
+        NamedRng::Xoshiro256PlusPlus(Xoshiro256PlusPlus::seed_from_u64(state))
+    }
+}
+
+impl DicesRng for NamedRng {
+    fn reseed(&mut self, seed: Option<u64>) {
+        self.reseed_same(seed)
+    }
+}
+
+impl DicesRng for SplitMix64 {}
+impl DicesRng for Xoroshiro128Plus {}
+impl DicesRng for Xoroshiro128PlusPlus {}
+impl DicesRng for Xoroshiro128StarStar {}
+impl DicesRng for Xoshiro128Plus {}
+impl DicesRng for Xoshiro128PlusPlus {}
+impl DicesRng for Xoshiro128StarStar {}
+impl DicesRng for Xoshiro256Plus {}
+impl DicesRng for Xoshiro256PlusPlus {}
+impl DicesRng for Xoshiro256StarStar {}
+impl DicesRng for Xoshiro512Plus {}
+impl DicesRng for Xoshiro512PlusPlus {}
+impl DicesRng for Xoshiro512StarStar {}
+impl DicesRng for ChaCha8Rng {}
+impl DicesRng for ChaCha12Rng {}
+impl DicesRng for ChaCha20Rng {}
+impl DicesRng for Pcg32 {}
+impl DicesRng for Pcg64 {}
+impl DicesRng for Pcg64Mcg {}
+
+#[derive(Debug, Clone, Display, Error)]
+#[display("Unknown RNG `{_0}`, the available ones are {}", NamedRng::NAMES.join(", "))]
+pub struct UnknownRng(#[error(not(source))] String);
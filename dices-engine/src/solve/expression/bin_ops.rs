@@ -1,10 +1,11 @@
 use std::mem;
 
-use dices_ast::value::{ValueNull, ValueString};
+use dices_ast::value::{ValueList, ValueMap, ValueNull, ValueString};
 use itertools::Itertools;
 use un_ops::{neg, plus};
 
 use super::*;
+use crate::{MAX_NUMBER_BITS, MAX_REPEATED_LEN};
 
 impl<InjectedIntrisic> Solvable<InjectedIntrisic> for ExpressionBinOp<InjectedIntrisic>
 where
@@ -19,6 +20,7 @@ where
         let ExpressionBinOp {
             op,
             expressions: box [a, b],
+            ..
         } = self;
         let [a, b] = match op.eval_order() {
             Some(EvalOrder::AB) => {
@@ -34,7 +36,8 @@ where
             None => {
                 return Ok((match op {
                     BinOp::Repeat => repeats,
-                    _ => unreachable!("The only special order should be `Repeat`"),
+                    BinOp::Reroll => reroll,
+                    _ => unreachable!("The only special orders should be `Repeat` and `Reroll`"),
                 })(context, a, b)?);
             }
         };
@@ -42,7 +45,9 @@ where
             BinOp::Add => add,
             BinOp::Sub => sub,
             BinOp::Join => join,
-            BinOp::Repeat => unreachable!("`Repeat` should be handled aside"),
+            BinOp::Repeat | BinOp::Reroll => {
+                unreachable!("`Repeat` and `Reroll` should be handled aside")
+            }
             BinOp::Mult => mult,
             BinOp::Rem => rem,
             BinOp::Div => div,
@@ -50,6 +55,11 @@ where
             BinOp::KeepLow => keep_low,
             BinOp::RemoveHigh => remove_high,
             BinOp::RemoveLow => remove_low,
+            BinOp::BitAnd => bit_and,
+            BinOp::BitOr => bit_or,
+            BinOp::BitXor => bit_xor,
+            BinOp::Shl => shl,
+            BinOp::Shr => shr,
         }(context, a, b)?)
     }
 }
@@ -76,6 +86,71 @@ where
     ))
 }
 
+/// Roll the dice again while they match the condition
+///
+/// The condition is solved first. If the dice are repeated, as in `4d6 r 1`, each repeat is
+/// rolled again on its own, otherwise the whole expression is.
+fn reroll<R: DicesRng, InjectedIntrisic>(
+    context: &mut crate::Context<R, InjectedIntrisic>,
+    dice: &Expression<InjectedIntrisic>,
+    condition: &Expression<InjectedIntrisic>,
+) -> Result<Value<InjectedIntrisic>, SolveError<InjectedIntrisic>>
+where
+    InjectedIntrisic: InjectedIntr,
+{
+    let condition = condition.solve(context)?;
+    let Expression::BinOp(ExpressionBinOp {
+        op: BinOp::Repeat,
+        expressions: box [die, n],
+        ..
+    }) = dice
+    else {
+        return reroll_die(context, dice, &condition);
+    };
+    let repeats = n
+        .solve(context)?
+        .to_number()
+        .map_err(SolveError::RepeatTimesNotANumber)?;
+    if repeats < ValueNumber::ZERO {
+        return Err(SolveError::NegativeRepeats(repeats));
+    }
+    Ok(Value::List(
+        (ValueNumber::ZERO..repeats)
+            .map(|_| reroll_die(context, die, &condition))
+            .try_collect()?,
+    ))
+}
+
+/// Roll a single die until it does not match the condition
+fn reroll_die<R: DicesRng, InjectedIntrisic>(
+    context: &mut crate::Context<R, InjectedIntrisic>,
+    die: &Expression<InjectedIntrisic>,
+    condition: &Value<InjectedIntrisic>,
+) -> Result<Value<InjectedIntrisic>, SolveError<InjectedIntrisic>>
+where
+    InjectedIntrisic: InjectedIntr,
+{
+    let max_iterations = context.max_iterations();
+    for _ in 0..max_iterations {
+        let value = die.solve(context)?;
+        let matches = match condition {
+            Value::List(values) => values.contains(&value),
+            Value::Closure(_) | Value::Intrisic(_) => {
+                ExpressionCall::new(condition.clone().into(), Box::new([value.clone().into()]))
+                    .solve(context)?
+                    .to_number()
+                    .map_err(SolveError::RerollConditionIsNotANumber)?
+                    != ValueNumber::ZERO
+            }
+            condition => *condition == value,
+        };
+        if !matches {
+            return Ok(value);
+        }
+    }
+    Err(SolveError::IterationLimitReached(max_iterations))
+}
+
 fn ops_to_numbers<InjectedIntrisic>(
     op: BinOp,
     [a, b]: [Value<InjectedIntrisic>; 2],
@@ -104,6 +179,56 @@ where
     Ok(Value::Number(a + b))
 }
 
+/// Convert the number of times a string or a list of length `len` is repeated by `*`
+fn repeat_count<InjectedIntrisic>(
+    n: Value<InjectedIntrisic>,
+    len: usize,
+) -> Result<usize, SolveError<InjectedIntrisic>>
+where
+    InjectedIntrisic: InjectedIntr,
+{
+    let times = n.to_number().map_err(SolveError::RepeatTimesNotANumber)?;
+    if times < ValueNumber::ZERO {
+        return Err(SolveError::NegativeRepeats(times));
+    }
+    match usize::try_from(times.clone()) {
+        Ok(count)
+            if len
+                .checked_mul(count)
+                .is_some_and(|l| l <= MAX_REPEATED_LEN) =>
+        {
+            Ok(count)
+        }
+        // repeating nothing never grows
+        Ok(count) if len == 0 => Ok(count),
+        _ => Err(SolveError::RepeatedTooLong(times)),
+    }
+}
+
+/// If a value is multiplied element-wise by `*`
+///
+/// Those are numbers and the values always converted to them, or lists and maps containing only
+/// those. Lists with other elements are repeated instead.
+fn is_numeric<InjectedIntrisic>(value: &Value<InjectedIntrisic>) -> bool {
+    match value {
+        Value::Null(_) | Value::Bool(_) | Value::Number(_) => true,
+        Value::List(l) => l.iter().all(is_numeric),
+        Value::Map(m) => m.iter().all(|(_, v)| is_numeric(v)),
+        Value::String(_) | Value::Intrisic(_) | Value::Closure(_) | Value::External(_) => false,
+    }
+}
+
+/// Concatenate `times` copies of a list
+fn repeat_list<InjectedIntrisic>(
+    l: &ValueList<InjectedIntrisic>,
+    times: usize,
+) -> Value<InjectedIntrisic>
+where
+    InjectedIntrisic: InjectedIntr,
+{
+    Value::List((0..times).flat_map(|_| l.iter().cloned()).collect())
+}
+
 pub(super) fn mult<R, InjectedIntrisic>(
     _context: &mut crate::Context<R, InjectedIntrisic>,
     a: Value<InjectedIntrisic>,
@@ -113,6 +238,13 @@ where
     InjectedIntrisic: InjectedIntr,
 {
     match (a, b) {
+        // strings are repeated
+        (Value::String(s), n) | (n, Value::String(s))
+            if !matches!(n, Value::List(_) | Value::Map(_)) =>
+        {
+            let count = repeat_count(n, s.len())?;
+            Ok(Value::String(s.repeat(count)))
+        }
         // scalar and scalar
         (
             a @ (Value::Null(_)
@@ -120,13 +252,15 @@ where
             | Value::Number(_)
             | Value::String(_)
             | Value::Intrisic(_)
-            | Value::Closure(_)),
+            | Value::Closure(_)
+            | Value::External(_)),
             b @ (Value::Null(_)
             | Value::Bool(_)
             | Value::Number(_)
             | Value::String(_)
             | Value::Intrisic(_)
-            | Value::Closure(_)),
+            | Value::Closure(_)
+            | Value::External(_)),
         ) => {
             let [a, b] = ops_to_numbers(BinOp::Mult, [a, b])?;
             Ok(Value::Number(a * b))
@@ -138,9 +272,14 @@ where
             | Value::Number(_)
             | Value::String(_)
             | Value::Intrisic(_)
-            | Value::Closure(_)),
+            | Value::Closure(_)
+            | Value::External(_)),
             Value::List(mut l),
         ) => {
+            if !l.iter().all(is_numeric) {
+                let count = repeat_count(s, l.len())?;
+                return Ok(repeat_list(&l, count));
+            }
             let s: Value<InjectedIntrisic> = s
                 .to_number()
                 .map_err(|source| SolveError::LHSIsNotANumber {
@@ -163,8 +302,13 @@ where
             | Value::Number(_)
             | Value::String(_)
             | Value::Intrisic(_)
-            | Value::Closure(_)),
+            | Value::Closure(_)
+            | Value::External(_)),
         ) => {
+            if !l.iter().all(is_numeric) {
+                let count = repeat_count(s, l.len())?;
+                return Ok(repeat_list(&l, count));
+            }
             let s: Value<InjectedIntrisic> = s
                 .to_number()
                 .map_err(|source| SolveError::RHSIsNotANumber {
@@ -186,7 +330,8 @@ where
             | Value::Number(_)
             | Value::String(_)
             | Value::Intrisic(_)
-            | Value::Closure(_)),
+            | Value::Closure(_)
+            | Value::External(_)),
             Value::Map(mut m),
         ) => {
             let s: Value<InjectedIntrisic> = s
@@ -211,7 +356,8 @@ where
             | Value::Number(_)
             | Value::String(_)
             | Value::Intrisic(_)
-            | Value::Closure(_)),
+            | Value::Closure(_)
+            | Value::External(_)),
         ) => {
             let s: Value<InjectedIntrisic> = s
                 .to_number()
@@ -274,6 +420,9 @@ where
         }
         _ => {
             let [a, b] = ops_to_numbers(BinOp::Div, [a, b])?;
+            if b == ValueNumber::ZERO {
+                return Err(SolveError::DivisionByZero);
+            }
             Ok(Value::Number(a / b))
         }
     }
@@ -304,6 +453,9 @@ where
         }
         _ => {
             let [a, b] = ops_to_numbers(BinOp::Rem, [a, b])?;
+            if b == ValueNumber::ZERO {
+                return Err(SolveError::DivisionByZero);
+            }
             Ok(Value::Number(a % b))
         }
     }
@@ -323,11 +475,13 @@ where
             s1.push_str(&*s2);
             Ok(ValueString::from(s1).into())
         }
-        (Value::Map(mut m1), Value::Map(m2)) => {
-            for (key, value) in m2 {
-                m1.insert(key, value);
+        (Value::Map(m1), Value::Map(m2)) => {
+            // the joined map is a new one, so it is not frozen even if `m1` was
+            let mut joined = ValueMap::new();
+            for (key, value) in m1.into_iter().chain(m2) {
+                joined.insert(key, value);
             }
-            Ok(m1.into())
+            Ok(joined.into())
         }
         (a, b) => {
             let a = a.to_list().map_err(|source| SolveError::LHSIsNotAList {
@@ -472,3 +626,78 @@ where
         .collect();
     Ok(Value::List(a))
 }
+
+fn bit_and<R, InjectedIntrisic>(
+    _context: &mut crate::Context<R, InjectedIntrisic>,
+    a: Value<InjectedIntrisic>,
+    b: Value<InjectedIntrisic>,
+) -> Result<Value<InjectedIntrisic>, SolveError<InjectedIntrisic>>
+where
+    InjectedIntrisic: InjectedIntr,
+{
+    let [a, b] = ops_to_numbers(BinOp::BitAnd, [a, b])?;
+    Ok(Value::Number(a & b))
+}
+
+fn bit_or<R, InjectedIntrisic>(
+    _context: &mut crate::Context<R, InjectedIntrisic>,
+    a: Value<InjectedIntrisic>,
+    b: Value<InjectedIntrisic>,
+) -> Result<Value<InjectedIntrisic>, SolveError<InjectedIntrisic>>
+where
+    InjectedIntrisic: InjectedIntr,
+{
+    let [a, b] = ops_to_numbers(BinOp::BitOr, [a, b])?;
+    Ok(Value::Number(a | b))
+}
+
+fn bit_xor<R, InjectedIntrisic>(
+    _context: &mut crate::Context<R, InjectedIntrisic>,
+    a: Value<InjectedIntrisic>,
+    b: Value<InjectedIntrisic>,
+) -> Result<Value<InjectedIntrisic>, SolveError<InjectedIntrisic>>
+where
+    InjectedIntrisic: InjectedIntr,
+{
+    let [a, b] = ops_to_numbers(BinOp::BitXor, [a, b])?;
+    Ok(Value::Number(a ^ b))
+}
+
+fn shl<R, InjectedIntrisic>(
+    _context: &mut crate::Context<R, InjectedIntrisic>,
+    a: Value<InjectedIntrisic>,
+    b: Value<InjectedIntrisic>,
+) -> Result<Value<InjectedIntrisic>, SolveError<InjectedIntrisic>>
+where
+    InjectedIntrisic: InjectedIntr,
+{
+    const OP: BinOp = BinOp::Shl;
+
+    let [a, b] = ops_to_numbers(OP, [a, b])?;
+    let shift: usize = b
+        .clone()
+        .try_into()
+        .map_err(|source| SolveError::ShiftNeedPositive { op: OP, source })?;
+    // shifting zero never grows it
+    if a != ValueNumber::ZERO && a.bits().saturating_add(shift as u64) > MAX_NUMBER_BITS {
+        return Err(SolveError::ShiftTooLarge(b));
+    }
+    Ok(Value::Number(a << shift))
+}
+
+fn shr<R, InjectedIntrisic>(
+    _context: &mut crate::Context<R, InjectedIntrisic>,
+    a: Value<InjectedIntrisic>,
+    b: Value<InjectedIntrisic>,
+) -> Result<Value<InjectedIntrisic>, SolveError<InjectedIntrisic>>
+where
+    InjectedIntrisic: InjectedIntr,
+{
+    const OP: BinOp = BinOp::Shr;
+
+    let [a, b] = ops_to_numbers(OP, [a, b])?;
+    let b: usize = b
+        .try_into()
+        .map_err(|source| SolveError::ShiftNeedPositive { op: OP, source })?;
+    Ok(Value::Number(a >> b))
+}
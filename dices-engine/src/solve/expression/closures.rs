@@ -8,7 +8,7 @@ use dices_ast::{
         bin_ops::{BinOp, EvalOrder},
         set::{MemberReceiver, Receiver},
         un_ops::UnOp,
-        Expression, ExpressionClosure,
+        Expression, ExpressionClosure, MapKey,
     },
     ident::IdentStr,
     intrisics::InjectedIntr,
@@ -75,7 +75,7 @@ impl<'e> VarUse<'e> {
     ) -> Result<Self, VarUseCalcError> {
         Ok(match expr {
             // const expression do not interact with the variables
-            Expression::Const(_) => Self::none(),
+            Expression::Const(..) => Self::none(),
 
             Expression::List(l) => l
                 .iter()
@@ -85,7 +85,14 @@ impl<'e> VarUse<'e> {
                 .unwrap_or_else(VarUse::none),
             Expression::Map(m) => m
                 .iter()
-                .map(|(_, e)| VarUse::of(e))
+                .flat_map(|(k, e)| {
+                    // computed keys are evaluated before their value
+                    let k = match k {
+                        MapKey::Static(_) => None,
+                        MapKey::Computed(k) => Some(VarUse::of(k)),
+                    };
+                    k.into_iter().chain(once(VarUse::of(e)))
+                })
                 .tree_reduce(maybe_concat)
                 .transpose()?
                 .unwrap_or_else(VarUse::none),
@@ -98,7 +105,9 @@ impl<'e> VarUse<'e> {
             },
 
             Expression::UnOp(un_op) => match un_op.op {
-                UnOp::Plus | UnOp::Neg | UnOp::Dice => Self::of(&un_op.expression)?,
+                UnOp::Plus | UnOp::Neg | UnOp::Dice | UnOp::BitNot | UnOp::Explode => {
+                    Self::of(&un_op.expression)?
+                }
             },
             Expression::BinOp(bin_op) => match bin_op.op.eval_order() {
                 Some(EvalOrder::AB) => Self::concat(
@@ -121,6 +130,18 @@ impl<'e> VarUse<'e> {
                             });
                         }
                     }
+                    BinOp::Reroll => {
+                        // the dice can be rolled any number of times, like a repeat body
+                        let dice_vars = Self::of(&bin_op.expressions[0])?;
+                        if dice_vars.lets.is_empty() {
+                            let cond_vars = Self::of(&bin_op.expressions[1])?;
+                            Self::concat(cond_vars, dice_vars)
+                        } else {
+                            return Err(VarUseCalcError::ConditionalLet {
+                                vars: dice_vars.lets.into_iter().map(ToOwned::to_owned).collect(),
+                            });
+                        }
+                    }
                     _ => unreachable!(),
                 },
             },
@@ -152,6 +173,12 @@ impl<'e> VarUse<'e> {
             Expression::MemberAccess(ma) => {
                 Self::concat(Self::of(&ma.accessed)?, Self::of(&ma.index)?)
             }
+            // the body and the condition are both evaluated at least once
+            Expression::RepeatUntil(ru) => {
+                Self::concat(Self::of(&ru.body)?, Self::of(&ru.condition)?)
+            }
+            // the pattern is fixed, and does not read any variable
+            Expression::Is(is) => Self::of(&is.value)?,
         })
     }
 
@@ -269,6 +296,16 @@ fn maybe_concat<'e>(
     }
 }
 
+/// The variables an expression reads or sets before defining them
+///
+/// These must already be in the context when the expression is evaluated.
+pub(crate) fn free_vars<InjectedIntrisic>(
+    expr: &Expression<InjectedIntrisic>,
+) -> Result<HashSet<&IdentStr>, VarUseCalcError> {
+    let VarUse { reads, sets, .. } = VarUse::of(expr)?;
+    Ok(reads.union(&sets).copied().collect())
+}
+
 fn captures<InjectedIntrisic>(
     c: &ExpressionClosure<InjectedIntrisic>,
 ) -> Result<HashSet<&IdentStr>, VarUseCalcError> {
@@ -1,22 +1,29 @@
 //! Intrisic operations
 
 use std::{
+    collections::{btree_map::Entry, BTreeMap},
     hash::{DefaultHasher, Hash, Hasher},
+    mem,
+    ops::RangeInclusive,
     str::FromStr,
 };
 
 use derive_more::{Display, Error};
 use dices_ast::{
-    expression::{bin_ops::BinOp, Expression, ExpressionBinOp, ExpressionCall},
+    expression::{
+        bin_ops::BinOp, un_ops::UnOp, Expression, ExpressionBinOp, ExpressionCall, ExpressionUnOp,
+    },
     intrisics::{InjectedIntr, Intrisic},
+    matcher::Matcher,
     value::{
         serde::{deserialize_from_value, serialize_to_value},
-        ToListError, ToNumberError, Value, ValueIntrisic, ValueNull,
+        ToListError, ToNumberError, Value, ValueIntrisic, ValueList, ValueMap, ValueNull,
+        ValueNumber, ValueString, ValueType,
     },
 };
-use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
 
-use crate::{solve::Solvable, DicesRng};
+use crate::{solve::Solvable, DicesRng, MAX_NUMBER_BITS};
 
 use super::SolveError;
 
@@ -25,11 +32,20 @@ pub enum IntrisicError<Injected>
 where
     Injected: InjectedIntr,
 {
-    #[display("Wrong number of params given to the intrisic {}: expected {}, given {given}", called.name(), param_num(called))]
+    #[display("Wrong number of params given to the intrisic {}: expected {}, given {given}", called.name(), ParamNum(expected))]
     WrongParamNum {
         called: Intrisic<Injected>,
+        expected: RangeInclusive<usize>,
         given: usize,
     },
+    #[display("`{}` expects params matching {expected}, but was given {given}", called.name())]
+    ParamsMismatch {
+        called: Intrisic<Injected>,
+        expected: Matcher<Injected>,
+        given: ValueList<Injected>,
+    },
+    #[display("The intrisic {} is not allowed in this engine", called.name())]
+    Denied { called: Intrisic<Injected> },
     #[display("Expression called failed to evaluate")]
     CallFailed(#[error(source)] SolveError<Injected>),
     #[display("Error during summing")]
@@ -44,12 +60,72 @@ where
     ToNumber(#[error(source)] ToNumberError),
     #[display("Cannot convert to a list")]
     ToList(#[error(source)] ToListError),
-    #[display("`parse` must be called on a string, not on {_0}")]
-    CannotParseNonString(#[error(not(source))] Value<Injected>),
-    #[display("`from_json` must be called on a string, not on {_0}")]
-    JsonMustBeString(#[error(not(source))] Value<Injected>),
+    #[display("Error during mapping")]
+    MapFailed(#[error(source)] SolveError<Injected>),
+    #[display("Error during filtering")]
+    FilterFailed(#[error(source)] SolveError<Injected>),
+    #[display("The filtering function must return a number")]
+    FilterResultNotANumber(#[error(source)] ToNumberError),
+    #[display("Error during partitioning")]
+    PartitionFailed(#[error(source)] SolveError<Injected>),
+    #[display("The partitioning function must return a number")]
+    PartitionResultNotANumber(#[error(source)] ToNumberError),
+    #[display("Error during sorting")]
+    SortByFailed(#[error(source)] SolveError<Injected>),
+    #[display("Error during grouping")]
+    GroupByFailed(#[error(source)] SolveError<Injected>),
+    #[display("The number of elements to sample must be a number")]
+    SampleSizeNotANumber(#[error(source)] ToNumberError),
+    #[display("Cannot sample {k} elements from a list of {len}")]
+    SampleTooLarge { k: ValueNumber, len: usize },
+    #[display("No rerolls are left")]
+    NoRerollsLeft,
+    #[display("The label `{label}` must be cancelled by another label, not {given}")]
+    CancelledByNotALabel {
+        label: ValueString,
+        given: Value<Injected>,
+    },
+    #[display("The number of elements to `{}` must be a number", _0.name())]
+    CountNotANumber(Intrisic<Injected>, #[error(source)] ToNumberError),
+    #[display("The number of elements to `{}` must not be negative (given {_1})", _0.name())]
+    NegativeCount(Intrisic<Injected>, #[error(not(source))] ValueNumber),
+    #[display("Only a list with a single element can become a scalar, not one of {_0} elements")]
+    NotAScalar(#[error(not(source))] usize),
+    #[display("The number of samples must be a number")]
+    SamplesNotANumber(#[error(source)] ToNumberError),
+    #[display(
+        "The number of samples must be between 0 and {max}, as a loop iterations (given {given})"
+    )]
+    TooManySamples { given: ValueNumber, max: usize },
+    #[display("Error during sampling")]
+    DistFailed(#[error(source)] SolveError<Injected>),
+    #[display("Error during the roll")]
+    RollFailed(#[error(source)] SolveError<Injected>),
+    #[display("Cannot compute the mean of an empty list")]
+    MeanOfEmptyList,
+    #[display(
+        "`{first}` and `{second}` are different values, but would be counted under the same label"
+    )]
+    SameLabel {
+        first: Value<Injected>,
+        second: Value<Injected>,
+    },
+    #[display("Cannot compute `mod` with a divisor of zero")]
+    ModByZero,
+    #[display("Cannot raise to the negative power {_0}")]
+    NegativeExponent(#[error(not(source))] ValueNumber),
+    #[display("The exponent {_0} is too large")]
+    ExponentTooLarge(#[error(not(source))] ValueNumber),
+    #[display("The path `{_0}` is not present in the map")]
+    MissingPath(#[error(not(source))] ValueString),
+    #[display("Cannot set `{path}`: `{key}` is not a map")]
+    SetPathThroughNonMap { path: ValueString, key: ValueString },
+    #[display("Cannot write to the output")]
+    Output(#[error(source)] std::io::Error),
     #[display("Failed to parse string")]
     ParseFailed(#[error(source)] <Value<Injected> as FromStr>::Err),
+    #[display("Invalid pattern")]
+    InvalidPattern(#[error(source)] <Matcher<Injected> as FromStr>::Err),
 
     #[display("{_0}")]
     Injected(#[error(source)] Injected::Error),
@@ -57,6 +133,30 @@ where
     JsonError(#[error(source)] serde_json::Error),
     #[display("Invalid RNG state")]
     InvalidRngState(#[error(source)] dices_ast::value::serde::DeserializeFromValueError),
+    #[display("Cannot make a replay token")]
+    CannotMakeReplayToken(#[error(source)] serde_json::Error),
+    #[display("Invalid replay token")]
+    InvalidReplayToken(#[error(source)] serde_json::Error),
+    #[cfg(feature = "eval_str")]
+    #[display("Cannot parse the source in the replay token")]
+    InvalidReplaySource(#[error(source)] dices_ast::expression::ParseError),
+    #[cfg(not(feature = "eval_str"))]
+    #[display("Replaying a token needs the `eval_str` feature, to evaluate its source")]
+    ReplayUnavailable,
+    #[display("The source in the replay token does not give a closure")]
+    ReplayNotAClosure,
+}
+
+/// What is needed to replay a call with the same rolls
+///
+/// The closure called is kept as source, so the token can be read, and replaying it cannot do more than
+/// evaluating the same source would.
+#[derive(Serialize, Deserialize)]
+struct Replay<R> {
+    /// The state of the RNG before the call
+    rng: R,
+    /// The source of the closure called
+    source: String,
 }
 
 pub(super) fn call<R: DicesRng, Injected>(
@@ -67,40 +167,40 @@ pub(super) fn call<R: DicesRng, Injected>(
 where
     Injected: InjectedIntr,
 {
+    if context.is_denied(intrisic.0.name()) {
+        return Err(IntrisicError::Denied { called: intrisic.0 });
+    }
     match intrisic.into() {
         // Variadics
         Intrisic::Call => {
-            let [called, params] = match Box::<[_; 2]>::try_from(params) {
-                Ok(box [a, b]) => [a, b],
-                Err(box ref s) => {
-                    return Err(IntrisicError::WrongParamNum {
-                        called: Intrisic::Call,
-                        given: s.len(),
-                    })
-                }
-            };
+            let [called, params] =
+                match_params(Intrisic::Call, [Matcher::Any, Matcher::Any], params)?;
 
-            ExpressionCall {
-                called: Box::new(called.into()),
-                params: params
+            ExpressionCall::new(
+                called.into(),
+                params
                     .to_list()
                     .map_err(IntrisicError::CallParamsNotAList)?
                     .into_iter()
                     .map(Expression::from)
                     .collect(),
-            }
+            )
             .solve(context)
             .map_err(IntrisicError::CallFailed)
         }
-        Intrisic::Sum => params
-            .into_vec()
-            .into_iter()
-            .try_reduce(|e1, e2| {
-                Expression::BinOp(ExpressionBinOp::new(BinOp::Add, e1.into(), e2.into()))
-                    .solve(context)
-            })
-            .map(|r| r.unwrap_or(Value::Number(0.into())))
-            .map_err(IntrisicError::SumFailed),
+        Intrisic::Sum => {
+            // a single list is summed starting from 0, while any other single value is returned as is
+            let start = matches!(&*params, [Value::List(_)]).then(|| Value::Number(0.into()));
+            start
+                .into_iter()
+                .chain(params.into_vec())
+                .try_reduce(|e1, e2| {
+                    Expression::BinOp(ExpressionBinOp::new(BinOp::Add, e1.into(), e2.into()))
+                        .solve(context)
+                })
+                .map(|r| r.unwrap_or(Value::Number(0.into())))
+                .map_err(IntrisicError::SumFailed)
+        }
         Intrisic::Join => params
             .into_vec()
             .into_iter()
@@ -120,119 +220,601 @@ where
             .map(|r| r.unwrap_or(Value::Number(1.into())))
             .map_err(IntrisicError::MultFailed),
 
-        // Conversions
-        Intrisic::ToNumber => {
-            let [value] = match Box::<[_; 1]>::try_from(params) {
-                Ok(box [a]) => [a],
-                Err(box ref s) => {
-                    return Err(IntrisicError::WrongParamNum {
-                        called: Intrisic::ToNumber,
-                        given: s.len(),
-                    })
+        // Lists
+        Intrisic::Map => {
+            let [list, fun] = match_params(Intrisic::Map, [Matcher::Any, Matcher::Any], params)?;
+            let list = list.to_list().map_err(IntrisicError::ToList)?;
+
+            list.into_iter()
+                .map(|el| {
+                    ExpressionCall::new(fun.clone().into(), Box::new([el.into()]))
+                        .solve(context)
+                        .map_err(IntrisicError::MapFailed)
+                })
+                .try_collect()
+                .map(Value::List)
+        }
+        Intrisic::Filter => {
+            let [list, fun] = match_params(Intrisic::Filter, [Matcher::Any, Matcher::Any], params)?;
+            let list = list.to_list().map_err(IntrisicError::ToList)?;
+
+            let mut filtered = vec![];
+            for el in list {
+                let keep = ExpressionCall::new(fun.clone().into(), Box::new([el.clone().into()]))
+                    .solve(context)
+                    .map_err(IntrisicError::FilterFailed)?
+                    .to_number()
+                    .map_err(IntrisicError::FilterResultNotANumber)?;
+                if keep != ValueNumber::ZERO {
+                    filtered.push(el)
                 }
+            }
+            Ok(Value::List(filtered.into_iter().collect()))
+        }
+        Intrisic::Partition => {
+            let [list, fun] =
+                match_params(Intrisic::Partition, [Matcher::Any, Matcher::Any], params)?;
+            let list = list.to_list().map_err(IntrisicError::ToList)?;
+
+            let (mut matching, mut non_matching) = (vec![], vec![]);
+            for el in list {
+                let matches =
+                    ExpressionCall::new(fun.clone().into(), Box::new([el.clone().into()]))
+                        .solve(context)
+                        .map_err(IntrisicError::PartitionFailed)?
+                        .to_number()
+                        .map_err(IntrisicError::PartitionResultNotANumber)?;
+                if matches != ValueNumber::ZERO {
+                    matching.push(el)
+                } else {
+                    non_matching.push(el)
+                }
+            }
+            Ok(Value::List(
+                [
+                    Value::List(matching.into_iter().collect()),
+                    Value::List(non_matching.into_iter().collect()),
+                ]
+                .into_iter()
+                .collect(),
+            ))
+        }
+        Intrisic::SortBy => {
+            let [list, fun] = match_params(Intrisic::SortBy, [Matcher::Any, Matcher::Any], params)?;
+            let list = list.to_list().map_err(IntrisicError::ToList)?;
+            sort_by_key(context, list, fun)
+        }
+        Intrisic::Sort => {
+            let len = params.len();
+            let mut params = params.into_vec().into_iter();
+            let (list, fun) = match (params.next(), params.next()) {
+                (Some(list), fun) if len <= 2 => (list, fun),
+                _ => return Err(IntrisicError::wrong_param_num(Intrisic::Sort, len)),
             };
+            let mut list = list.to_list().map_err(IntrisicError::ToList)?;
+            match fun {
+                Some(fun) => sort_by_key(context, list, fun),
+                None => {
+                    list.sort();
+                    Ok(Value::List(list))
+                }
+            }
+        }
+        Intrisic::Reverse => {
+            let [list] = match_params(Intrisic::Reverse, [Matcher::Any], params)?;
+            let mut list = list.to_list().map_err(IntrisicError::ToList)?;
+            list.reverse();
+            Ok(Value::List(list))
+        }
+        Intrisic::GroupBy => {
+            let [list, fun] =
+                match_params(Intrisic::GroupBy, [Matcher::Any, Matcher::Any], params)?;
+            let list = list.to_list().map_err(IntrisicError::ToList)?;
+
+            let mut groups: Vec<(ValueString, Vec<Value<Injected>>)> = vec![];
+            for el in list {
+                let key = ExpressionCall::new(fun.clone().into(), Box::new([el.clone().into()]))
+                    .solve(context)
+                    .map_err(IntrisicError::GroupByFailed)?;
+                let key = match key {
+                    Value::String(key) => key,
+                    // map keys are strings, so other values are converted
+                    key => key.to_string().into(),
+                };
+                match groups.iter_mut().find(|(k, _)| *k == key) {
+                    Some((_, group)) => group.push(el),
+                    None => groups.push((key, vec![el])),
+                }
+            }
+            Ok(Value::Map(
+                groups
+                    .into_iter()
+                    .map(|(key, group)| (key, Value::List(group.into_iter().collect())))
+                    .collect(),
+            ))
+        }
+
+        Intrisic::Sample => {
+            let [list, k] = match_params(Intrisic::Sample, [Matcher::Any, Matcher::Any], params)?;
+            let mut list = Vec::from_iter(list.to_list().map_err(IntrisicError::ToList)?);
+            let k = k.to_number().map_err(IntrisicError::SampleSizeNotANumber)?;
+            let len = list.len();
+            let k = usize::try_from(k.clone())
+                .ok()
+                .filter(|k| *k <= len)
+                .ok_or(IntrisicError::SampleTooLarge { k, len })?;
+
+            // partial Fisher-Yates shuffle, the drawn elements end up at the start in draw order
+            for i in 0..k {
+                let j = context.rng().gen_range(i..len);
+                list.swap(i, j);
+            }
+            list.truncate(k);
+            Ok(Value::List(list.into_iter().collect()))
+        }
+
+        Intrisic::Tally => {
+            let [list] = match_params(Intrisic::Tally, [Matcher::Any], params)?;
+            let list = list.to_list().map_err(IntrisicError::ToList)?;
+            Ok(counts_to_map(count_labels(list)?))
+        }
+        intr @ (Intrisic::Product | Intrisic::Mean) => {
+            let [list] = match_params(intr.clone(), [Matcher::Any], params)?;
+            let numbers: Vec<_> = list
+                .to_list()
+                .map_err(IntrisicError::ToList)?
+                .into_iter()
+                .map(|el| el.to_number().map_err(IntrisicError::ToNumber))
+                .try_collect()?;
+            Ok(Value::Number(if matches!(intr, Intrisic::Product) {
+                numbers
+                    .into_iter()
+                    .fold(ValueNumber::from(1), |acc, n| acc * n)
+            } else {
+                if numbers.is_empty() {
+                    return Err(IntrisicError::MeanOfEmptyList);
+                }
+                let len = ValueNumber::from(numbers.len());
+                numbers
+                    .into_iter()
+                    .fold(ValueNumber::ZERO, |acc, n| acc + n)
+                    / len
+            }))
+        }
+        intr @ (Intrisic::Take | Intrisic::Drop) => {
+            let [list, n] = match_params(intr.clone(), [Matcher::Any, Matcher::Any], params)?;
+            let list = list.to_list().map_err(IntrisicError::ToList)?;
+            let n = n
+                .to_number()
+                .map_err(|err| IntrisicError::CountNotANumber(intr.clone(), err))?;
+            if n < ValueNumber::ZERO {
+                return Err(IntrisicError::NegativeCount(intr, n));
+            }
+            // counts too large for an `usize` are beyond any list length
+            let n = usize::try_from(n).unwrap_or(usize::MAX);
+            Ok(Value::List(if matches!(intr, Intrisic::Take) {
+                list.into_iter().take(n).collect()
+            } else {
+                list.into_iter().skip(n).collect()
+            }))
+        }
+        intr @ (Intrisic::First | Intrisic::Last) => {
+            let [list] = match_params(intr.clone(), [Matcher::Any], params)?;
+            let list = list.to_list().map_err(IntrisicError::ToList)?;
+            let el = if matches!(intr, Intrisic::First) {
+                list.into_iter().next()
+            } else {
+                list.into_iter().last()
+            };
+            Ok(el.unwrap_or(Value::Null(ValueNull)))
+        }
+
+        intr @ (Intrisic::Cap | Intrisic::FloorAt) => {
+            let [value, bound] = match_params(intr.clone(), [Matcher::Any, Matcher::Any], params)?;
+            let bound = bound.to_number().map_err(IntrisicError::ToNumber)?;
+            clamp(value, &bound, matches!(intr, Intrisic::Cap))
+        }
+
+        intr @ (Intrisic::Advantage | Intrisic::Disadvantage) => {
+            let faces = match Box::<[_; 1]>::try_from(params) {
+                Ok(box [faces]) => faces,
+                Err(box []) => Value::Number(20.into()),
+                Err(box ref s) => return Err(IntrisicError::wrong_param_num(intr, s.len())),
+            };
+            let keep = if matches!(intr, Intrisic::Advantage) {
+                BinOp::KeepHigh
+            } else {
+                BinOp::KeepLow
+            };
+            // `2dX kh 1`, keeping the rolls to report them
+            let rolls = Expression::BinOp(ExpressionBinOp::new(
+                BinOp::Repeat,
+                ExpressionUnOp::new(UnOp::Dice, faces.into()).into(),
+                Value::Number(2.into()).into(),
+            ))
+            .solve(context)
+            .map_err(IntrisicError::RollFailed)?;
+            let kept = Expression::BinOp(ExpressionBinOp::new(
+                keep,
+                rolls.clone().into(),
+                Value::Number(1.into()).into(),
+            ))
+            .solve(context)
+            .map_err(IntrisicError::RollFailed)?;
+            let (Value::List(rolls), Value::List(kept)) = (rolls, kept) else {
+                unreachable!("Repeating and filtering always give lists")
+            };
+            let result = kept.into_iter().next().expect("One of the rolls is kept");
+            let dropped = if rolls[0] == result {
+                rolls[1].clone()
+            } else {
+                rolls[0].clone()
+            };
+            Ok(Value::Map(ValueMap::from_iter([
+                ("result".into(), result),
+                ("rolls".into(), Value::List(rolls)),
+                ("dropped".into(), dropped),
+            ])))
+        }
+
+        Intrisic::Dist => {
+            let len = params.len();
+            let mut params = params.into_vec().into_iter();
+            let (fun, samples) = match (params.next(), params.next()) {
+                (Some(fun), samples) if len <= 2 => (fun, samples),
+                _ => return Err(IntrisicError::wrong_param_num(Intrisic::Dist, len)),
+            };
+            // the samples are capped as a loop would be
+            let max = context.max_iterations();
+            let samples = match samples {
+                Some(samples) => {
+                    let samples = samples
+                        .to_number()
+                        .map_err(IntrisicError::SamplesNotANumber)?;
+                    usize::try_from(samples.clone())
+                        .ok()
+                        .filter(|n| *n <= max)
+                        .ok_or(IntrisicError::TooManySamples {
+                            given: samples,
+                            max,
+                        })?
+                }
+                None => DEFAULT_DIST_SAMPLES.min(max),
+            };
+            let outcomes: ValueList<_> = (0..samples)
+                .map(|_| {
+                    ExpressionCall::new(fun.clone().into(), Box::new([]))
+                        .solve(context)
+                        .map_err(IntrisicError::DistFailed)
+                })
+                .try_collect()?;
+            Ok(counts_to_map(count_labels(outcomes)?))
+        }
+
+        // Math
+        Intrisic::Mod => {
+            let [a, b] = match_params(Intrisic::Mod, [Matcher::Any, Matcher::Any], params)?;
+            let a = a.to_number().map_err(IntrisicError::ToNumber)?;
+            let b = b.to_number().map_err(IntrisicError::ToNumber)?;
+            a.checked_rem_euclid(&b)
+                .map(Value::Number)
+                .ok_or(IntrisicError::ModByZero)
+        }
+        Intrisic::Pow => {
+            let [base, exp] = match_params(Intrisic::Pow, [Matcher::Any, Matcher::Any], params)?;
+            let base = base.to_number().map_err(IntrisicError::ToNumber)?;
+            let exp = exp.to_number().map_err(IntrisicError::ToNumber)?;
+            if exp < ValueNumber::ZERO {
+                return Err(IntrisicError::NegativeExponent(exp));
+            }
+            // the result needs about `bits(base) * exp` bits, apart from `0`, `1` and `-1`
+            let too_large = base.bits() > 1
+                && u64::try_from(exp.clone()).map_or(true, |exp| {
+                    base.bits().saturating_mul(exp) > MAX_NUMBER_BITS
+                });
+            let exp = match u32::try_from(exp.clone()) {
+                Ok(exp) if !too_large => exp,
+                _ => return Err(IntrisicError::ExponentTooLarge(exp)),
+            };
+            Ok(Value::Number(base.pow(exp)))
+        }
+        intr @ (Intrisic::Gcd | Intrisic::Lcm) => {
+            let is_gcd = matches!(intr, Intrisic::Gcd);
+            let [a, b] = match_params(intr.clone(), [Matcher::Any, Matcher::Any], params)?;
+            let a = a.to_number().map_err(IntrisicError::ToNumber)?;
+            let b = b.to_number().map_err(IntrisicError::ToNumber)?;
+            Ok(Value::Number(if is_gcd { a.gcd(&b) } else { a.lcm(&b) }))
+        }
+
+        // Maps
+        Intrisic::Get => {
+            let [Value::Map(map), Value::String(key), default] = match_params(
+                Intrisic::Get,
+                [
+                    Matcher::Type(ValueType::Map),
+                    Matcher::Type(ValueType::String),
+                    Matcher::Any,
+                ],
+                params,
+            )?
+            else {
+                unreachable!("The params were matched")
+            };
+            Ok(map.get(&key).cloned().unwrap_or(default))
+        }
+        Intrisic::GetPath => {
+            // the default is optional
+            let (map, path, default) = if params.len() == 3 {
+                let [map, Value::String(path), default] = match_params(
+                    Intrisic::GetPath,
+                    [
+                        Matcher::Type(ValueType::Map),
+                        Matcher::Type(ValueType::String),
+                        Matcher::Any,
+                    ],
+                    params,
+                )?
+                else {
+                    unreachable!("The params were matched")
+                };
+                (map, path, Some(default))
+            } else {
+                let [map, Value::String(path)] = match_params(
+                    Intrisic::GetPath,
+                    [
+                        Matcher::Type(ValueType::Map),
+                        Matcher::Type(ValueType::String),
+                    ],
+                    params,
+                )?
+                else {
+                    unreachable!("The params were matched")
+                };
+                (map, path, None)
+            };
+            let mut current = &map;
+            for key in path.split('.') {
+                match current {
+                    Value::Map(map) if map.contains(key) => current = map.get(key).unwrap(),
+                    // a missing path gives the default, if one was provided
+                    _ => return default.ok_or_else(|| IntrisicError::MissingPath(path.clone())),
+                }
+            }
+            Ok(current.clone())
+        }
+        Intrisic::SetPath => {
+            let [Value::Map(map), Value::String(path), value] = match_params(
+                Intrisic::SetPath,
+                [
+                    Matcher::Type(ValueType::Map),
+                    Matcher::Type(ValueType::String),
+                    Matcher::Any,
+                ],
+                params,
+            )?
+            else {
+                unreachable!("The params were matched")
+            };
+            let keys: Vec<&str> = path.split('.').collect();
+            Ok(Value::Map(set_path(map, &keys, value).map_err(|key| {
+                IntrisicError::SetPathThroughNonMap {
+                    key: key.into(),
+                    path: path.clone(),
+                }
+            })?))
+        }
+        Intrisic::Freeze => {
+            let [Value::Map(mut map)] =
+                match_params(Intrisic::Freeze, [Matcher::Type(ValueType::Map)], params)?
+            else {
+                unreachable!("The params were matched")
+            };
+            map.freeze();
+            Ok(Value::Map(map))
+        }
+
+        // Dice
+        Intrisic::CritCheck => {
+            let [fun] = match_params(Intrisic::CritCheck, [Matcher::Any], params)?;
+            let (total, rolls) = context.recording_rolls(|context| {
+                ExpressionCall::new(fun.into(), Box::new([]))
+                    .solve(context)
+                    .map_err(IntrisicError::CallFailed)
+            });
+            let total = total?;
+            let nat_max = rolls.iter().any(|roll| roll.result == roll.faces);
+            let nat_min = rolls.iter().any(|roll| roll.result == ValueNumber::from(1));
+            Ok(Value::Map(ValueMap::from_iter([
+                ("nat_max".into(), Value::Bool(nat_max.into())),
+                ("nat_min".into(), Value::Bool(nat_min.into())),
+                ("total".into(), total),
+            ])))
+        }
+        Intrisic::Resolve => {
+            let [pool, Value::Map(cancels)] = match_params(
+                Intrisic::Resolve,
+                [Matcher::Any, Matcher::Type(ValueType::Map)],
+                params,
+            )?
+            else {
+                unreachable!("The params were matched")
+            };
+            // the pool can be a list of labels, or their counts
+            let mut counts = match pool {
+                Value::Map(counts) => counts
+                    .into_iter()
+                    .map(|(label, count)| Ok((label, count.to_number()?)))
+                    .try_collect()
+                    .map_err(IntrisicError::ToNumber)?,
+                pool => count_labels(pool.to_list().map_err(IntrisicError::ToList)?)?,
+            };
+            for (label, opposite) in cancels {
+                let Value::String(opposite) = opposite else {
+                    return Err(IntrisicError::CancelledByNotALabel {
+                        label,
+                        given: opposite,
+                    });
+                };
+                if label == opposite {
+                    continue;
+                }
+                let (Some(a), Some(b)) = (counts.get(&label), counts.get(&opposite)) else {
+                    continue;
+                };
+                let cancelled = a.clone().min(b.clone());
+                for side in [&label, &opposite] {
+                    *counts.get_mut(side).unwrap() -= cancelled.clone();
+                }
+            }
+            counts.retain(|_, count| *count > ValueNumber::ZERO);
+            Ok(counts_to_map(counts))
+        }
+        Intrisic::Reroll => {
+            let [fun] = match_params(Intrisic::Reroll, [Matcher::Any], params)?;
+            if !context.spend_reroll() {
+                return Err(IntrisicError::NoRerollsLeft);
+            }
+            ExpressionCall::new(fun.into(), Box::new([]))
+                .solve(context)
+                .map_err(IntrisicError::CallFailed)
+        }
+
+        // Conversions
+        Intrisic::ToNumber => {
+            let [value] = match_params(Intrisic::ToNumber, [Matcher::Any], params)?;
             value
                 .to_number()
                 .map(Into::into)
                 .map_err(IntrisicError::ToNumber)
         }
         Intrisic::ToList => {
-            let [value] = match Box::<[_; 1]>::try_from(params) {
-                Ok(box [a]) => [a],
-                Err(box ref s) => {
-                    return Err(IntrisicError::WrongParamNum {
-                        called: Intrisic::ToList,
-                        given: s.len(),
-                    })
-                }
-            };
+            let [value] = match_params(Intrisic::ToList, [Matcher::Any], params)?;
             value
                 .to_list()
                 .map(Into::into)
                 .map_err(IntrisicError::ToList)
         }
         Intrisic::ToString => {
-            let [value] = match Box::<[_; 1]>::try_from(params) {
-                Ok(box [a]) => [a],
-                Err(box ref s) => {
-                    return Err(IntrisicError::WrongParamNum {
-                        called: Intrisic::ToString,
-                        given: s.len(),
-                    })
-                }
-            };
+            let [value] = match_params(Intrisic::ToString, [Matcher::Any], params)?;
             Ok(Value::String(value.to_string().into()))
         }
-        Intrisic::Parse => {
-            let [value] = match Box::<[_; 1]>::try_from(params) {
-                Ok(box [Value::String(s)]) => [s],
-                Ok(box [a]) => return Err(IntrisicError::CannotParseNonString(a)),
-                Err(box ref s) => {
-                    return Err(IntrisicError::WrongParamNum {
-                        called: Intrisic::Parse,
-                        given: s.len(),
-                    })
+        Intrisic::Repr => {
+            let [value] = match_params(Intrisic::Repr, [Matcher::Any], params)?;
+            Ok(Value::String(
+                value.source(context.std_name()).to_string().into(),
+            ))
+        }
+        Intrisic::Scalar => {
+            let [value] = match_params(Intrisic::Scalar, [Matcher::Any], params)?;
+            match value {
+                Value::List(list) if list.len() == 1 => {
+                    Ok(list.into_iter().next().expect("The list has one element"))
                 }
+                Value::List(list) => Err(IntrisicError::NotAScalar(list.len())),
+                value => Ok(value),
+            }
+        }
+        Intrisic::Parse => {
+            let [Value::String(value)] =
+                match_params(Intrisic::Parse, [Matcher::Type(ValueType::String)], params)?
+            else {
+                unreachable!("The params were matched")
             };
             value.trim().parse().map_err(IntrisicError::ParseFailed)
         }
+        Intrisic::Matches => {
+            let [value, Value::String(pattern)] = match_params(
+                Intrisic::Matches,
+                [Matcher::Any, Matcher::Type(ValueType::String)],
+                params,
+            )?
+            else {
+                unreachable!("The params were matched")
+            };
+            let matcher: Matcher<Injected> =
+                pattern.parse().map_err(IntrisicError::InvalidPattern)?;
+            Ok(Value::Bool(matcher.is_match(&value).into()))
+        }
 
+        Intrisic::Print => {
+            for value in params.iter() {
+                writeln!(context.output(), "{value}").map_err(IntrisicError::Output)?
+            }
+            Ok(Value::Null(ValueNull))
+        }
         Intrisic::ToJson => {
-            let [value] = match Box::<[_; 1]>::try_from(params) {
-                Ok(box [v]) => [v],
-                Err(box ref s) => {
-                    return Err(IntrisicError::WrongParamNum {
-                        called: Intrisic::ToJson,
-                        given: s.len(),
-                    })
-                }
-            };
+            let [value] = match_params(Intrisic::ToJson, [Matcher::Any], params)?;
             serde_json::to_string(&value)
                 .map(|s| Value::String(s.into()))
                 .map_err(IntrisicError::JsonError)
         }
         Intrisic::FromJson => {
-            let [value] = match Box::<[_; 1]>::try_from(params) {
-                Ok(box [Value::String(s)]) => [s],
-                Ok(box [a]) => return Err(IntrisicError::JsonMustBeString(a)),
-                Err(box ref s) => {
-                    return Err(IntrisicError::WrongParamNum {
-                        called: Intrisic::FromJson,
-                        given: s.len(),
-                    })
-                }
+            let [Value::String(value)] = match_params(
+                Intrisic::FromJson,
+                [Matcher::Type(ValueType::String)],
+                params,
+            )?
+            else {
+                unreachable!("The params were matched")
             };
             serde_json::from_str(&value).map_err(IntrisicError::JsonError)
         }
 
         Intrisic::SeedRNG => {
-            *context.rng() = if params.is_empty() {
+            context.rng().reseed(if params.is_empty() {
                 // if no parameter is given, seed from entropy
-                SeedableRng::from_entropy()
+                None
             } else {
                 // Hash all the parameters
                 let mut hasher = DefaultHasher::new();
                 params.hash(&mut hasher);
-                SeedableRng::seed_from_u64(hasher.finish())
-            };
+                Some(hasher.finish())
+            });
             Ok(Value::Null(ValueNull))
         }
         Intrisic::SaveRNG => Ok(serialize_to_value(context.rng())
             .expect("The RNG should be always serializable to a value")),
         Intrisic::RestoreRNG => {
-            let [value] = match Box::<[_; 1]>::try_from(params) {
-                Ok(box [v]) => [v],
-                Err(box ref s) => {
-                    return Err(IntrisicError::WrongParamNum {
-                        called: Intrisic::RestoreRNG,
-                        given: s.len(),
-                    })
-                }
-            };
+            let [value] = match_params(Intrisic::RestoreRNG, [Matcher::Any], params)?;
             *context.rng() =
                 deserialize_from_value(value).map_err(IntrisicError::InvalidRngState)?;
             Ok(Value::Null(ValueNull))
         }
+        Intrisic::ReplayToken => {
+            let [called] = match_params(
+                Intrisic::ReplayToken,
+                [Matcher::Type(ValueType::Closure)],
+                params,
+            )?;
+            let source = called.source(context.std_name()).to_string();
+            let token = serde_json::to_string(&Replay {
+                rng: &*context.rng(),
+                source,
+            })
+            .map_err(IntrisicError::CannotMakeReplayToken)?;
+            // the call is made, so the rolls are the ones the token will replay
+            ExpressionCall::new(called.into(), Box::new([]))
+                .solve(context)
+                .map_err(IntrisicError::CallFailed)?;
+            Ok(Value::String(token.into()))
+        }
+        Intrisic::Replay => {
+            let [Value::String(token)] =
+                match_params(Intrisic::Replay, [Matcher::Type(ValueType::String)], params)?
+            else {
+                unreachable!("The params were matched")
+            };
+            let Replay { rng, source }: Replay<R> =
+                serde_json::from_str(&token).map_err(IntrisicError::InvalidReplayToken)?;
+            let called = replayed_closure(context, &source)?;
+            // the replay does not change the rolls that will come after it
+            let rng = std::mem::replace(context.rng(), rng);
+            let res = ExpressionCall::new(called.into(), Box::new([]))
+                .solve(context)
+                .map_err(IntrisicError::CallFailed);
+            *context.rng() = rng;
+            res
+        }
 
         Intrisic::Injected(injected) => injected
             .call(context.injected_intrisics_data_mut(), params)
@@ -240,19 +822,319 @@ where
     }
 }
 
-fn param_num<Injected>(intr: &Intrisic<Injected>) -> usize {
-    match intr {
-        Intrisic::Call => 2,
-        Intrisic::ToString | Intrisic::Parse | Intrisic::ToNumber | Intrisic::ToList => 1,
+/// Evaluate the source of a replay token, giving the closure to call
+#[cfg(feature = "eval_str")]
+fn replayed_closure<R: DicesRng, Injected: InjectedIntr>(
+    context: &mut crate::Context<R, Injected>,
+    source: &str,
+) -> Result<Value<Injected>, IntrisicError<Injected>> {
+    // the source is evaluated as if it was written by the user, so it is bound by the same limits
+    let exprs =
+        dices_ast::parse_file::<Injected>(source).map_err(IntrisicError::InvalidReplaySource)?;
+    let called = match &**exprs {
+        [expr @ (Expression::Closure(_) | Expression::Scope(_))] => context
+            .scoped(|context| expr.solve(context))
+            .map_err(IntrisicError::CallFailed)?,
+        _ => return Err(IntrisicError::ReplayNotAClosure),
+    };
+    if !matches!(called, Value::Closure(_)) {
+        return Err(IntrisicError::ReplayNotAClosure);
+    }
+    Ok(called)
+}
+#[cfg(not(feature = "eval_str"))]
+fn replayed_closure<R: DicesRng, Injected: InjectedIntr>(
+    _: &mut crate::Context<R, Injected>,
+    _: &str,
+) -> Result<Value<Injected>, IntrisicError<Injected>> {
+    Err(IntrisicError::ReplayUnavailable)
+}
+
+/// Check the params of an intrisic against the shape it expects
+///
+/// Gives [`IntrisicError::WrongParamNum`] if the number of params is wrong, and
+/// [`IntrisicError::ParamsMismatch`] if one of them does not match.
+fn match_params<Injected, const N: usize>(
+    called: Intrisic<Injected>,
+    expected: [Matcher<Injected>; N],
+    params: Box<[Value<Injected>]>,
+) -> Result<[Value<Injected>; N], IntrisicError<Injected>>
+where
+    Injected: InjectedIntr,
+{
+    let box params = Box::<[_; N]>::try_from(params)
+        .map_err(|s| IntrisicError::wrong_param_num(called.clone(), s.len()))?;
+    if expected.iter().zip(&params).all(|(m, p)| m.is_match(p)) {
+        Ok(params)
+    } else {
+        Err(IntrisicError::ParamsMismatch {
+            called,
+            expected: Matcher::List(Box::new(expected)),
+            given: params.into_iter().collect(),
+        })
+    }
+}
+
+/// Number of samples taken by `dist`, if not given
+const DEFAULT_DIST_SAMPLES: usize = 1000;
+
+/// Sort a list by the keys computed by calling `fun` on each element
+///
+/// The sort is stable, so elements with the same key keep their order.
+fn sort_by_key<R: DicesRng, Injected: InjectedIntr>(
+    context: &mut crate::Context<R, Injected>,
+    list: ValueList<Injected>,
+    fun: Value<Injected>,
+) -> Result<Value<Injected>, IntrisicError<Injected>> {
+    let mut keyed: Vec<_> = list
+        .into_iter()
+        .map(|el| {
+            ExpressionCall::new(fun.clone().into(), Box::new([el.clone().into()]))
+                .solve(context)
+                .map(|key| (key, el))
+                .map_err(IntrisicError::SortByFailed)
+        })
+        .try_collect()?;
+    keyed.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Ok(Value::List(keyed.into_iter().map(|(_, el)| el).collect()))
+}
+
+/// Bound the numbers in a value from above if `is_cap`, otherwise from below
+///
+/// Lists and maps are bounded element by element, the other values are converted to numbers.
+fn clamp<Injected: InjectedIntr>(
+    value: Value<Injected>,
+    bound: &ValueNumber,
+    is_cap: bool,
+) -> Result<Value<Injected>, IntrisicError<Injected>> {
+    Ok(match value {
+        Value::List(list) => Value::List(
+            list.into_iter()
+                .map(|el| clamp(el, bound, is_cap))
+                .try_collect()?,
+        ),
+        Value::Map(mut map) => {
+            for (_, el) in map.iter_mut() {
+                *el = clamp(mem::replace(el, Value::Null(ValueNull)), bound, is_cap)?;
+            }
+            Value::Map(map)
+        }
+        value => {
+            let n = value.to_number().map_err(IntrisicError::ToNumber)?;
+            Value::Number(if is_cap {
+                n.min(bound.clone())
+            } else {
+                n.max(bound.clone())
+            })
+        }
+    })
+}
+
+/// Count how many times each label appears in a list
+///
+/// Map keys are strings, so the values that are not strings are converted to their text.
+/// The values are counted first, so each distinct one is converted only once.
+fn count_labels<Injected: InjectedIntr>(
+    list: ValueList<Injected>,
+) -> Result<BTreeMap<ValueString, ValueNumber>, IntrisicError<Injected>> {
+    let mut counts: BTreeMap<Value<Injected>, usize> = BTreeMap::new();
+    for el in list {
+        *counts.entry(el).or_default() += 1;
+    }
+    // the value each label came from, to catch different values with the same text, like `1` and `"1"`
+    let mut labels: BTreeMap<ValueString, (Value<Injected>, ValueNumber)> = BTreeMap::new();
+    for (el, count) in counts {
+        let label = match &el {
+            Value::String(label) => label.clone(),
+            el => el.to_string().into(),
+        };
+        match labels.entry(label) {
+            Entry::Vacant(entry) => {
+                entry.insert((el, ValueNumber::from(count)));
+            }
+            Entry::Occupied(entry) => {
+                return Err(IntrisicError::SameLabel {
+                    first: entry.remove().0,
+                    second: el,
+                })
+            }
+        }
+    }
+    Ok(labels
+        .into_iter()
+        .map(|(label, (_, count))| (label, count))
+        .collect())
+}
+
+/// Make a map from the counts of the labels
+fn counts_to_map<Injected>(counts: BTreeMap<ValueString, ValueNumber>) -> Value<Injected> {
+    Value::Map(
+        counts
+            .into_iter()
+            .map(|(label, count)| (label, Value::Number(count)))
+            .collect(),
+    )
+}
+
+/// Replace the value at the end of a path of keys, creating the missing maps along the way
+///
+/// Fails with the key of the first value on the path that is not a map.
+fn set_path<'k, Injected>(
+    mut map: ValueMap<Injected>,
+    keys: &[&'k str],
+    value: Value<Injected>,
+) -> Result<ValueMap<Injected>, &'k str> {
+    let (key, rest) = keys.split_first().expect("A path has at least one key");
+    let value = if rest.is_empty() {
+        value
+    } else {
+        match map.remove(key) {
+            Some(Value::Map(inner)) => Value::Map(set_path(inner, rest, value)?),
+            None => Value::Map(set_path(ValueMap::new(), rest, value)?),
+            Some(_) => return Err(key),
+        }
+    };
+    map.insert((*key).into(), value);
+    Ok(map)
+}
+
+/// The number of params accepted by an intrisic
+///
+/// Gives `None` for the intrisics that accept any number of params, and for the injected ones.
+pub fn arity<Injected>(intr: &Intrisic<Injected>) -> Option<RangeInclusive<usize>> {
+    Some(match intr {
+        Intrisic::Call
+        | Intrisic::Map
+        | Intrisic::Filter
+        | Intrisic::Partition
+        | Intrisic::SortBy
+        | Intrisic::GroupBy
+        | Intrisic::Sample
+        | Intrisic::Take
+        | Intrisic::Drop
+        | Intrisic::Mod
+        | Intrisic::Pow
+        | Intrisic::Gcd
+        | Intrisic::Lcm
+        | Intrisic::Matches => 2..=2,
+        Intrisic::Get | Intrisic::SetPath => 3..=3,
+        Intrisic::Resolve | Intrisic::Cap | Intrisic::FloorAt => 2..=2,
+        Intrisic::GetPath => 2..=3,
+        Intrisic::Sort | Intrisic::Dist => 1..=2,
+        Intrisic::Reverse => 1..=1,
+        Intrisic::ToString
+        | Intrisic::Repr
+        | Intrisic::Scalar
+        | Intrisic::Parse
+        | Intrisic::ToNumber
+        | Intrisic::ToList
+        | Intrisic::First
+        | Intrisic::Last
+        | Intrisic::Tally
+        | Intrisic::Product
+        | Intrisic::Mean
+        | Intrisic::Freeze
+        | Intrisic::CritCheck
+        | Intrisic::Reroll => 1..=1,
         Intrisic::Sum
         | Intrisic::Join
         | Intrisic::Mult
+        | Intrisic::Print
         | Intrisic::Injected(_)
-        | Intrisic::SeedRNG => {
-            panic!("These have no fixed param number")
+        | Intrisic::SeedRNG => return None,
+        Intrisic::ToJson | Intrisic::FromJson => 1..=1,
+        Intrisic::RestoreRNG | Intrisic::ReplayToken | Intrisic::Replay => 1..=1,
+        Intrisic::SaveRNG => 0..=0,
+        Intrisic::Advantage | Intrisic::Disadvantage => 0..=1,
+    })
+}
+
+/// If calling the intrisic has effects on the engine, beyond computing its result
+///
+/// Those are the ones writing to the output, using the RNG or spending rerolls. The intrisics calling closures are
+/// not counted, as the effects come from the closure. Injected intrisics are always assumed to
+/// have effects.
+pub fn is_effectful<Injected>(intr: &Intrisic<Injected>) -> bool {
+    match intr {
+        Intrisic::Print
+        | Intrisic::Sample
+        | Intrisic::SeedRNG
+        | Intrisic::SaveRNG
+        | Intrisic::RestoreRNG
+        | Intrisic::ReplayToken
+        | Intrisic::Reroll
+        | Intrisic::Advantage
+        | Intrisic::Disadvantage
+        | Intrisic::Injected(_) => true,
+        Intrisic::Sum
+        | Intrisic::Join
+        | Intrisic::Mult
+        | Intrisic::ToNumber
+        | Intrisic::ToList
+        | Intrisic::ToString
+        | Intrisic::Parse
+        | Intrisic::Repr
+        | Intrisic::Scalar
+        | Intrisic::Matches
+        | Intrisic::Call
+        | Intrisic::Get
+        | Intrisic::GetPath
+        | Intrisic::SetPath
+        | Intrisic::Freeze
+        | Intrisic::CritCheck
+        | Intrisic::Resolve
+        | Intrisic::Cap
+        | Intrisic::FloorAt
+        | Intrisic::Replay
+        | Intrisic::Map
+        | Intrisic::Filter
+        | Intrisic::SortBy
+        | Intrisic::Sort
+        | Intrisic::Reverse
+        | Intrisic::Dist
+        | Intrisic::GroupBy
+        | Intrisic::Tally
+        | Intrisic::Product
+        | Intrisic::Mean
+        | Intrisic::Partition
+        | Intrisic::Take
+        | Intrisic::Drop
+        | Intrisic::First
+        | Intrisic::Last
+        | Intrisic::Mod
+        | Intrisic::Pow
+        | Intrisic::Gcd
+        | Intrisic::Lcm
+        | Intrisic::ToJson
+        | Intrisic::FromJson => false,
+    }
+}
+
+impl<Injected: InjectedIntr> IntrisicError<Injected> {
+    /// The error for an intrisic called with the wrong number of params
+    ///
+    /// The expected number comes from the [`arity`] of the intrisic, so this must not be used for
+    /// the ones that accept any number of params.
+    fn wrong_param_num(called: Intrisic<Injected>, given: usize) -> Self {
+        let expected = arity(&called)
+            .expect("Only intrisics with an arity can be given the wrong number of params");
+        IntrisicError::WrongParamNum {
+            called,
+            expected,
+            given,
+        }
+    }
+}
+
+/// Display an accepted number of params
+struct ParamNum<'r>(&'r RangeInclusive<usize>);
+impl std::fmt::Display for ParamNum<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (start, end) = self.0.clone().into_inner();
+        if start == end {
+            write!(f, "{start}")
+        } else {
+            write!(f, "from {start} to {end}")
         }
-        Intrisic::ToJson | Intrisic::FromJson => 1,
-        Intrisic::RestoreRNG => 1,
-        Intrisic::SaveRNG => 0,
     }
 }
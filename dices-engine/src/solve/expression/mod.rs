@@ -1,23 +1,27 @@
 //! Implementations of Solvable on all types of expressions
 
-use closures::VarUseCalcError;
+use std::ops::Range;
+
 use derive_more::{Debug, Display, Error};
 use nunny::NonEmpty;
 
+pub(crate) use closures::free_vars;
+pub use closures::VarUseCalcError;
 use dices_ast::{
     expression::{
         bin_ops::{BinOp, EvalOrder},
         set::{MemberReceiver, Receiver},
-        Expression, ExpressionBinOp, ExpressionCall, ExpressionList, ExpressionMap,
-        ExpressionMemberAccess, ExpressionRef, ExpressionScope, ExpressionSet, ExpressionUnOp,
+        Expression, ExpressionBinOp, ExpressionCall, ExpressionIs, ExpressionList, ExpressionMap,
+        ExpressionMemberAccess, ExpressionRef, ExpressionRepeatUntil, ExpressionScope,
+        ExpressionSet, ExpressionUnOp, MapKey, Position, Span,
     },
     ident::IdentStr,
     intrisics::InjectedIntr,
-    value::{ToListError, ToNumberError, Value, ValueClosure, ValueNull, ValueNumber},
+    value::{ToListError, ToNumberError, Value, ValueClosure, ValueMap, ValueNull, ValueNumber},
 };
-pub use intrisics::IntrisicError;
+pub use intrisics::{arity, is_effectful, IntrisicError};
 
-use crate::{solve::Solvable, DicesRng};
+use crate::{solve::Solvable, DicesRng, Trace};
 
 #[derive(Debug, Display, Error)]
 pub enum SolveError<InjectedIntrisic: InjectedIntr> {
@@ -25,6 +29,11 @@ pub enum SolveError<InjectedIntrisic: InjectedIntr> {
     RepeatTimesNotANumber(#[error(source)] ToNumberError),
     #[display("The number of repeats must be positive (given {_0})")]
     NegativeRepeats(#[error(not(source))] ValueNumber),
+    #[display(
+        "Repeating {_0} times would build a value longer than {}",
+        crate::MAX_REPEATED_LEN
+    )]
+    RepeatedTooLong(#[error(not(source))] ValueNumber),
     #[display("The operator {op} needs a number at is right")]
     RHSIsNotANumber {
         op: BinOp,
@@ -62,6 +71,16 @@ pub enum SolveError<InjectedIntrisic: InjectedIntr> {
         op: BinOp,
         source: <usize as TryFrom<ValueNumber>>::Error,
     },
+    #[display("The shift operator {} needs a positive number at his right", op)]
+    ShiftNeedPositive {
+        op: BinOp,
+        source: <usize as TryFrom<ValueNumber>>::Error,
+    },
+    #[display(
+        "Shifting left by {_0} would build a number larger than {} bits",
+        crate::MAX_NUMBER_BITS
+    )]
+    ShiftTooLarge(#[error(not(source))] ValueNumber),
     #[display("The number of dice faces must be a number")]
     FacesAreNotANumber {
         #[error(source)]
@@ -69,6 +88,20 @@ pub enum SolveError<InjectedIntrisic: InjectedIntr> {
     },
     #[display("The number of dice faces must be positive (given {faces})")]
     FacesMustBePositive { faces: ValueNumber },
+    #[display("The weights of the labeled faces must be numbers")]
+    FaceWeightIsNotANumber {
+        #[error(source)]
+        source: ToNumberError,
+    },
+    #[display("The weight of the face `{label}` must not be negative (given {weight})")]
+    FaceWeightMustNotBeNegative {
+        label: dices_ast::value::ValueString,
+        weight: ValueNumber,
+    },
+    #[display("An exploding die needs at least two faces, or it would explode forever")]
+    ExplodingSingleFace,
+    #[display("A labeled die needs at least a face with a positive weight")]
+    NoFaceCanBeRolled,
     #[display("Cannot convert into a number")]
     CannotMakeANumber {
         #[error(source)]
@@ -78,6 +111,10 @@ pub enum SolveError<InjectedIntrisic: InjectedIntr> {
     MultNeedAScalar,
     #[display("Undefined variable {_0}")]
     InvalidReference(#[error(not(source))] Box<IdentStr>),
+    #[display("Division by zero")]
+    DivisionByZero,
+    #[display("`{_0}` is not a valid variable name")]
+    InvalidName(#[error(not(source))] String),
     #[display("{_0} is not callable")]
     NotCallable(#[error(not(source))] Value<InjectedIntrisic>),
     #[display("Error during intrisic call")]
@@ -98,8 +135,56 @@ pub enum SolveError<InjectedIntrisic: InjectedIntr> {
     StringIndexOutOfRange { idx: ValueNumber, len: usize },
     #[display("Index {idx} out of range for list of lenght {len}")]
     ListIndexOutOfRange { idx: ValueNumber, len: usize },
+    #[display("The loop condition must be a number")]
+    ConditionIsNotANumber(#[error(source)] ToNumberError),
+    #[display("The reroll predicate must return a number")]
+    RerollConditionIsNotANumber(#[error(source)] ToNumberError),
+    #[display("The loop did not end after {_0} iterations")]
+    IterationLimitReached(#[error(not(source))] usize),
+    #[display("The evaluation did not end in {_0:?}")]
+    TimedOut(#[error(not(source))] std::time::Duration),
+    #[display("The map is frozen, and cannot be changed")]
+    FrozenMap,
     #[display("Key not found: \"{_0}\"")]
     MissingKey(#[error(not(source))] dices_ast::value::ValueString),
+    #[display("No expression was given to evaluate")]
+    NothingToEvaluate,
+    #[display("Error at {position} in the source")]
+    At {
+        span: Range<usize>,
+        position: Position,
+        #[error(source)]
+        source: Box<RecursionGuard<SolveError<InjectedIntrisic>>>,
+    },
+}
+impl<InjectedIntrisic: InjectedIntr> SolveError<InjectedIntrisic> {
+    /// Mark the error as happening in the given span of the source, if known
+    fn at(self, span: &Span) -> Self {
+        match (span.range(), span.start()) {
+            (Some(span), Some(position)) => SolveError::At {
+                span,
+                position,
+                source: Box::new(RecursionGuard::new(self)),
+            },
+            _ => self,
+        }
+    }
+
+    /// The span of the source where the error happened, if known
+    pub fn span(&self) -> Option<Range<usize>> {
+        match self {
+            SolveError::At { span, .. } => Some(span.clone()),
+            _ => None,
+        }
+    }
+
+    /// The position in the source where the error happened, if known
+    pub fn position(&self) -> Option<Position> {
+        match self {
+            SolveError::At { position, .. } => Some(*position),
+            _ => None,
+        }
+    }
 }
 impl<InjectedIntrisic: InjectedIntr> From<!> for SolveError<InjectedIntrisic> {
     fn from(value: !) -> Self {
@@ -120,22 +205,45 @@ where
         &self,
         context: &mut crate::Context<R, InjectedIntrisic>,
     ) -> Result<Value<InjectedIntrisic>, Self::Error> {
-        Ok(match self {
-            Expression::Const(e) => e.solve(context)?,
-            Expression::List(e) => e.solve(context)?,
-            Expression::Map(e) => e.solve(context)?,
-            Expression::Closure(e) => e.solve(context)?,
-            Expression::UnOp(e) => e.solve(context)?,
-            Expression::BinOp(e) => e.solve(context)?,
-            Expression::Call(e) => e.solve(context)?,
-            Expression::Scope(e) => e.solve(context)?,
-            Expression::Set(e) => e.solve(context)?,
-            Expression::Ref(e) => e.solve(context)?,
-            Expression::MemberAccess(e) => e.solve(context)?,
-        })
+        if let Some(timeout) = context.timed_out() {
+            return Err(SolveError::TimedOut(timeout));
+        }
+        if context.is_tracing() {
+            let (value, steps) = context.tracing(|context| solve_step(self, context));
+            let value = value?;
+            context.record_step(Trace {
+                expression: self.clone(),
+                value: value.clone(),
+                steps,
+            });
+            return Ok(value);
+        }
+        solve_step(self, context)
     }
 }
 
+/// Solve a single expression, without tracing it
+fn solve_step<R: DicesRng, InjectedIntrisic: InjectedIntr>(
+    expr: &Expression<InjectedIntrisic>,
+    context: &mut crate::Context<R, InjectedIntrisic>,
+) -> Result<Value<InjectedIntrisic>, SolveError<InjectedIntrisic>> {
+    Ok(match expr {
+        Expression::Const(e, _) => e.solve(context)?,
+        Expression::List(e) => e.solve(context)?,
+        Expression::Map(e) => e.solve(context)?,
+        Expression::Closure(e) => e.solve(context)?,
+        Expression::UnOp(e) => e.solve(context)?,
+        Expression::BinOp(e) => e.solve(context)?,
+        Expression::Call(e) => e.solve(context)?,
+        Expression::Scope(e) => e.solve(context)?,
+        Expression::Set(e) => e.solve(context)?,
+        Expression::Ref(e) => e.solve(context)?,
+        Expression::MemberAccess(e) => e.solve(context)?,
+        Expression::RepeatUntil(e) => e.solve(context)?,
+        Expression::Is(e) => e.solve(context)?,
+    })
+}
+
 impl<InjectedIntrisic> Solvable<InjectedIntrisic> for ExpressionList<InjectedIntrisic>
 where
     InjectedIntrisic: InjectedIntr,
@@ -146,8 +254,24 @@ where
         &self,
         context: &mut crate::Context<R, InjectedIntrisic>,
     ) -> Result<Value<InjectedIntrisic>, Self::Error> {
+        if !context.coalesce_errors() {
+            return Ok(Value::List(
+                self.iter().map(|i| i.solve(context)).try_collect()?,
+            ));
+        }
+        // each element becomes `<|ok: value|>` or `<|err: message|>`
         Ok(Value::List(
-            self.iter().map(|i| i.solve(context)).try_collect()?,
+            self.iter()
+                .map(|i| {
+                    let (key, value) = match i.solve(context) {
+                        Ok(value) => ("ok", value),
+                        // running out of time stops the whole evaluation
+                        Err(err @ SolveError::TimedOut(_)) => return Err(err),
+                        Err(err) => ("err", Value::String(err.to_string().into())),
+                    };
+                    Ok(Value::Map(ValueMap::from_iter([(key.into(), value)])))
+                })
+                .try_collect()?,
         ))
     }
 }
@@ -164,7 +288,16 @@ where
     ) -> Result<Value<InjectedIntrisic>, Self::Error> {
         Ok(Value::Map(
             self.iter()
-                .map(|(k, v)| v.solve(context).map(|v| (k.clone(), v)))
+                .map(|(k, v)| {
+                    let k = match k {
+                        MapKey::Static(k) => k.clone(),
+                        MapKey::Computed(k) => match k.solve(context)? {
+                            Value::String(k) => k,
+                            k => return Err(SolveError::MapIsIndexedByStrings(k)),
+                        },
+                    };
+                    v.solve(context).map(|v| (k, v))
+                })
                 .try_collect()?,
         ))
     }
@@ -188,13 +321,17 @@ where
         let Self {
             called: box called,
             params: box params,
+            ..
         } = self;
         let called = called.solve(context)?;
         let params: Box<_> = params.iter().map(|p| p.solve(context)).try_collect()?;
 
         match called {
-            Value::Intrisic(intrisic) => intrisics::call(intrisic, context, params)
-                .map_err(|err| SolveError::IntrisicError(Box::new(RecursionGuard::new(err)))),
+            Value::Intrisic(intrisic) => {
+                context.count_intrisic_call(intrisic.0.id());
+                intrisics::call(intrisic, context, params)
+                    .map_err(|err| SolveError::IntrisicError(Box::new(RecursionGuard::new(err))))
+            }
             Value::Closure(box ValueClosure {
                 params: params_names,
                 captures,
@@ -224,6 +361,47 @@ where
     }
 }
 
+impl<InjectedIntrisic> Solvable<InjectedIntrisic> for ExpressionRepeatUntil<InjectedIntrisic>
+where
+    InjectedIntrisic: InjectedIntr,
+{
+    type Error = SolveError<InjectedIntrisic>;
+
+    fn solve<R: DicesRng>(
+        &self,
+        context: &mut crate::Context<R, InjectedIntrisic>,
+    ) -> Result<Value<InjectedIntrisic>, Self::Error> {
+        let max_iterations = context.max_iterations();
+        for _ in 0..max_iterations {
+            let value = self.body.solve(context)?;
+            let condition = self
+                .condition
+                .solve(context)?
+                .to_number()
+                .map_err(SolveError::ConditionIsNotANumber)?;
+            if condition != ValueNumber::ZERO {
+                return Ok(value);
+            }
+        }
+        Err(SolveError::IterationLimitReached(max_iterations))
+    }
+}
+
+impl<InjectedIntrisic> Solvable<InjectedIntrisic> for ExpressionIs<InjectedIntrisic>
+where
+    InjectedIntrisic: InjectedIntr,
+{
+    type Error = SolveError<InjectedIntrisic>;
+
+    fn solve<R: DicesRng>(
+        &self,
+        context: &mut crate::Context<R, InjectedIntrisic>,
+    ) -> Result<Value<InjectedIntrisic>, Self::Error> {
+        let value = self.value.solve(context)?;
+        Ok(Value::Bool(self.pattern.is_match(&value).into()))
+    }
+}
+
 impl<InjectedIntrisic> Solvable<InjectedIntrisic> for ExpressionMemberAccess<InjectedIntrisic>
 where
     InjectedIntrisic: InjectedIntr,
@@ -239,56 +417,64 @@ where
         // then for the index
         let index = self.index.solve(context)?;
         // finally, try to index
-        match (accessed, index) {
-            (Value::String(s), n) => {
-                let n = n
-                    .to_number()
-                    .map_err(SolveError::StringIsIndexedByNumbers)?;
-                let ch = if n >= ValueNumber::ZERO {
-                    usize::try_from(n.clone())
-                        .ok()
-                        .and_then(|n| s.chars().nth(n))
-                } else {
-                    usize::try_from(n.clone().abs() - 1.into())
-                        .ok()
-                        .and_then(|n| s.chars().nth_back(n))
-                };
-                if let Some(ch) = ch {
-                    Ok(Value::String(ch.to_string().into()))
-                } else {
-                    Err(SolveError::StringIndexOutOfRange {
-                        idx: n.into(),
-                        len: s.chars().count(),
-                    })
-                }
-            }
-            (Value::List(l), n) => {
-                let n = n
-                    .to_number()
-                    .map_err(SolveError::StringIsIndexedByNumbers)?;
-                let ch = if n >= ValueNumber::ZERO {
-                    usize::try_from(n.clone()).ok().and_then(|n| l.get(n))
-                } else {
-                    usize::try_from(n.clone() + ValueNumber::from(l.len()))
-                        .ok()
-                        .and_then(|n| l.get(n))
-                };
-                if let Some(ch) = ch {
-                    Ok(ch.clone())
-                } else {
-                    Err(SolveError::ListIndexOutOfRange {
-                        idx: n.into(),
-                        len: l.len(),
-                    })
-                }
+        index_value(accessed, index).map_err(|err| err.at(&self.span))
+    }
+}
+
+/// Read a member of a value
+fn index_value<InjectedIntrisic: InjectedIntr>(
+    accessed: Value<InjectedIntrisic>,
+    index: Value<InjectedIntrisic>,
+) -> Result<Value<InjectedIntrisic>, SolveError<InjectedIntrisic>> {
+    match (accessed, index) {
+        (Value::String(s), n) => {
+            let n = n
+                .to_number()
+                .map_err(SolveError::StringIsIndexedByNumbers)?;
+            let ch = if n >= ValueNumber::ZERO {
+                usize::try_from(n.clone())
+                    .ok()
+                    .and_then(|n| s.chars().nth(n))
+            } else {
+                usize::try_from(n.clone().abs() - 1.into())
+                    .ok()
+                    .and_then(|n| s.chars().nth_back(n))
+            };
+            if let Some(ch) = ch {
+                Ok(Value::String(ch.to_string().into()))
+            } else {
+                Err(SolveError::StringIndexOutOfRange {
+                    idx: n.into(),
+                    len: s.chars().count(),
+                })
             }
-            (Value::Map(m), Value::String(s)) => {
-                m.get(&s).cloned().ok_or_else(|| SolveError::MissingKey(s))
+        }
+        (Value::List(l), n) => {
+            let n = n
+                .to_number()
+                .map_err(SolveError::StringIsIndexedByNumbers)?;
+            let ch = if n >= ValueNumber::ZERO {
+                usize::try_from(n.clone()).ok().and_then(|n| l.get(n))
+            } else {
+                usize::try_from(n.clone() + ValueNumber::from(l.len()))
+                    .ok()
+                    .and_then(|n| l.get(n))
+            };
+            if let Some(ch) = ch {
+                Ok(ch.clone())
+            } else {
+                Err(SolveError::ListIndexOutOfRange {
+                    idx: n.into(),
+                    len: l.len(),
+                })
             }
-            (Value::Map(_), idx) => Err(SolveError::MapIsIndexedByStrings(idx)),
-
-            (accessed, _) => Err(SolveError::CannotIndex(accessed)),
         }
+        (Value::Map(m), Value::String(s)) => {
+            m.get(&s).cloned().ok_or_else(|| SolveError::MissingKey(s))
+        }
+        (Value::Map(_), idx) => Err(SolveError::MapIsIndexedByStrings(idx)),
+
+        (accessed, _) => Err(SolveError::CannotIndex(accessed)),
     }
 }
 
@@ -360,6 +546,7 @@ where
                                 Err(SolveError::ListIndexOutOfRange { idx: n.into(), len })
                             }
                         }
+                        (Value::Map(m), _) if m.is_frozen() => Err(SolveError::FrozenMap),
                         (Value::Map(m), Value::String(s)) => {
                             Ok(m.entry(s).or_insert(Value::Null(ValueNull)))
                         }
@@ -19,18 +19,28 @@ use std::{
 use derive_more::derive::{AsMut, AsRef, Constructor, From};
 use dices_ast::intrisics::InjectedIntr;
 
-use super::IntrisicError;
+use super::{IntrisicError, SolveError};
 
 #[derive(AsRef, AsMut, From, Constructor)]
 #[repr(transparent)]
 pub struct RecursionGuard<T>(pub T);
 
-impl<T> Debug for RecursionGuard<T>
+// `Debug` is implemented only on the wrapped errors, so it does not depend on
+// `T: Debug` and does not recurse when `SolveError` contains itself
+impl<Injected> Debug for RecursionGuard<IntrisicError<Injected>>
 where
-    T: Debug,
+    Injected: InjectedIntr + Debug,
 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        self.0.fmt(f)
+        Debug::fmt(&self.0, f)
+    }
+}
+impl<Injected> Debug for RecursionGuard<SolveError<Injected>>
+where
+    Injected: InjectedIntr + Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&self.0, f)
     }
 }
 impl<T> Display for RecursionGuard<T>
@@ -50,3 +60,12 @@ where
         <IntrisicError<Injected> as Error>::source(&self.0)
     }
 }
+
+impl<Injected> Error for RecursionGuard<SolveError<Injected>>
+where
+    Injected: InjectedIntr + Debug + 'static,
+{
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        <SolveError<Injected> as Error>::source(&self.0)
+    }
+}
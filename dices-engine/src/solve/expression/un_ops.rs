@@ -1,5 +1,7 @@
+use std::mem;
+
 use bin_ops::{add, mult};
-use dices_ast::expression::un_ops::UnOp;
+use dices_ast::{expression::un_ops::UnOp, value::ValueMap};
 use itertools::Itertools;
 use rand::Rng;
 
@@ -18,12 +20,15 @@ where
         let ExpressionUnOp {
             op,
             expression: box a,
+            ..
         } = self;
         let a = a.solve(context)?;
         Ok(match op {
             UnOp::Plus => plus,
             UnOp::Neg => neg,
             UnOp::Dice => dice,
+            UnOp::BitNot => bit_not,
+            UnOp::Explode => explode,
         }(context, a)?)
     }
 }
@@ -39,7 +44,8 @@ pub(crate) fn plus<R, InjectedIntrisic: InjectedIntr>(
         | Value::Number(_)
         | Value::String(_)
         | Value::Intrisic(_)
-        | Value::Closure(_) => a
+        | Value::Closure(_)
+        | Value::External(_) => a
             .to_number()
             .map_err(|source| SolveError::CannotMakeANumber { source })?
             .into(),
@@ -66,13 +72,58 @@ pub(super) fn neg<R, InjectedIntrisic>(
 where
     InjectedIntrisic: InjectedIntr,
 {
-    // delegating to the mult op
+    // delegating to the mult op, but strings are negated as numbers, not repeated
+    let a = match a {
+        Value::String(s) => {
+            Value::Number(
+                s.to_number()
+                    .map_err(|source| SolveError::RHSIsNotANumber {
+                        op: BinOp::Mult,
+                        source,
+                    })?,
+            )
+        }
+        Value::List(mut l) => {
+            for el in l.iter_mut() {
+                *el = neg(context, mem::replace(el, ValueNull.into()))?;
+            }
+            return Ok(l.into());
+        }
+        Value::Map(mut m) => {
+            for (_, el) in m.iter_mut() {
+                *el = neg(context, mem::replace(el, ValueNull.into()))?;
+            }
+            return Ok(m.into());
+        }
+        a => a,
+    };
     mult(context, Value::Number((-1).into()), a)
 }
 
 fn dice<R: Rng, InjectedIntrisic: InjectedIntr>(
     context: &mut crate::Context<R, InjectedIntrisic>,
     a: Value<InjectedIntrisic>,
+) -> Result<Value<InjectedIntrisic>, SolveError<InjectedIntrisic>> {
+    if let Value::Map(faces) = a {
+        return labeled_dice(context, faces);
+    }
+    let a = a
+        .to_number()
+        .map_err(|source| SolveError::FacesAreNotANumber { source })?;
+
+    if a <= ValueNumber::ZERO {
+        return Err(SolveError::FacesMustBePositive { faces: a });
+    }
+
+    let result = context.rng().gen_range(ValueNumber::from(1)..=a.clone());
+    context.record_roll(&a, &result);
+    Ok(Value::Number(result))
+}
+
+/// Roll a die, rolling it again and adding while it lands on its highest face
+fn explode<R: Rng, InjectedIntrisic: InjectedIntr>(
+    context: &mut crate::Context<R, InjectedIntrisic>,
+    a: Value<InjectedIntrisic>,
 ) -> Result<Value<InjectedIntrisic>, SolveError<InjectedIntrisic>> {
     let a = a
         .to_number()
@@ -81,8 +132,66 @@ fn dice<R: Rng, InjectedIntrisic: InjectedIntr>(
     if a <= ValueNumber::ZERO {
         return Err(SolveError::FacesMustBePositive { faces: a });
     }
+    if a == ValueNumber::from(1) {
+        return Err(SolveError::ExplodingSingleFace);
+    }
+
+    let mut total = ValueNumber::ZERO;
+    for _ in 0..context.max_iterations() {
+        let result = context.rng().gen_range(ValueNumber::from(1)..=a.clone());
+        context.record_roll(&a, &result);
+        let exploded = result == a;
+        total += result;
+        if !exploded {
+            return Ok(Value::Number(total));
+        }
+    }
+    Err(SolveError::IterationLimitReached(context.max_iterations()))
+}
+
+/// Roll a die with labeled faces, each with its weight
+fn labeled_dice<R: Rng, InjectedIntrisic: InjectedIntr>(
+    context: &mut crate::Context<R, InjectedIntrisic>,
+    faces: ValueMap<InjectedIntrisic>,
+) -> Result<Value<InjectedIntrisic>, SolveError<InjectedIntrisic>> {
+    let faces: Vec<_> = faces
+        .into_iter()
+        .map(|(label, weight)| {
+            let weight = weight
+                .to_number()
+                .map_err(|source| SolveError::FaceWeightIsNotANumber { source })?;
+            if weight < ValueNumber::ZERO {
+                return Err(SolveError::FaceWeightMustNotBeNegative {
+                    label: label.clone(),
+                    weight,
+                });
+            }
+            Ok((label, weight))
+        })
+        .try_collect()?;
+    let total = faces.iter().fold(ValueNumber::ZERO, |total, (_, weight)| {
+        total + weight.clone()
+    });
+    if total == ValueNumber::ZERO {
+        return Err(SolveError::NoFaceCanBeRolled);
+    }
 
-    Ok(Value::Number(
-        context.rng().gen_range(ValueNumber::from(1)..=a),
-    ))
+    let mut roll = context.rng().gen_range(ValueNumber::from(1)..=total);
+    for (label, weight) in faces {
+        if roll <= weight {
+            return Ok(Value::String(label));
+        }
+        roll -= weight;
+    }
+    unreachable!("The roll is at most the total weight")
+}
+
+fn bit_not<R, InjectedIntrisic: InjectedIntr>(
+    _context: &mut crate::Context<R, InjectedIntrisic>,
+    a: Value<InjectedIntrisic>,
+) -> Result<Value<InjectedIntrisic>, SolveError<InjectedIntrisic>> {
+    let a = a
+        .to_number()
+        .map_err(|source| SolveError::CannotMakeANumber { source })?;
+    Ok(Value::Number(!a))
 }
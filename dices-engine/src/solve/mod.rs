@@ -1,4 +1,4 @@
-pub(crate) use expression::solve_multiple;
+pub(crate) use expression::{free_vars, solve_multiple};
 
 use dices_ast::{intrisics::InjectedIntr, value::Value};
 
@@ -7,7 +7,7 @@ use crate::{Context, DicesRng};
 mod expression;
 mod value;
 
-pub use expression::{IntrisicError, SolveError};
+pub use expression::{arity, is_effectful, IntrisicError, SolveError, VarUseCalcError};
 
 pub(super) trait Solvable<InjectedIntrisic: InjectedIntr> {
     type Error;
@@ -0,0 +1,114 @@
+use std::fmt::Display;
+
+use dices_ast::{
+    ident::IdentStr,
+    value::{ExternalValue, ToNumberError, ValueExternal},
+    Value,
+};
+use rand::SeedableRng;
+use rand_xoshiro::Xoshiro256PlusPlus;
+
+use super::{engine, eval};
+use crate::{EngineBuilder, SharedOutput, SolveError};
+
+/// A playing card, that the engine knows nothing about
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct Card {
+    rank: u8,
+    suit: char,
+}
+impl Display for Card {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", self.rank, self.suit)
+    }
+}
+impl ExternalValue for Card {
+    const TYPE_NAME: &'static str = "card";
+}
+
+fn card(rank: u8, suit: char) -> Value {
+    Value::External(ValueExternal::new(Card { rank, suit }))
+}
+
+#[test]
+fn stored_and_printed() {
+    let output = SharedOutput::default();
+    let mut engine = EngineBuilder::new()
+        .with_rng(Xoshiro256PlusPlus::seed_from_u64(42))
+        .with_output_writer(output.clone())
+        .build();
+    engine
+        .context
+        .vars_mut()
+        .let_(IdentStr::new_boxed("drawn".into()).unwrap(), card(7, '♥'));
+    let hand = eval(
+        &mut engine,
+        "let hand = [drawn, drawn]; print(drawn, hand); hand",
+    )
+    .unwrap();
+    assert_eq!(output.take(), "7♥\n[7♥, 7♥]\n");
+    let Value::List(hand) = hand else {
+        panic!("The hand should be a list")
+    };
+    assert_eq!(
+        hand[0].as_external().unwrap().downcast_ref::<Card>(),
+        Some(&Card {
+            rank: 7, suit: '♥'
+        })
+    )
+}
+
+#[test]
+fn compared_and_hashed() {
+    use std::collections::BTreeSet;
+
+    assert_eq!(card(7, '♥'), card(7, '♥'));
+    assert_ne!(card(7, '♥'), card(8, '♥'));
+    assert!(card(7, '♥') < card(8, '♥'));
+    assert_ne!(card(7, '♥'), Value::Number(7.into()));
+    let set = BTreeSet::from([card(7, '♥'), card(7, '♥'), card(1, '♠')]);
+    assert_eq!(set.len(), 2)
+}
+
+#[test]
+fn not_a_number() {
+    let mut engine = engine();
+    engine
+        .context
+        .vars_mut()
+        .let_(IdentStr::new_boxed("c".into()).unwrap(), card(7, '♥'));
+    assert!(matches!(
+        eval(&mut engine, "c + 1"),
+        Err(SolveError::CannotMakeANumber {
+            source: ToNumberError::External("card")
+        })
+    ))
+}
+
+#[cfg(not(feature = "rayon"))]
+#[test]
+fn need_not_be_thread_safe() {
+    use std::rc::Rc;
+
+    /// A value shared with the host, that cannot be sent between threads
+    #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    struct Shared(Rc<u8>);
+    impl Display for Shared {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "shared {}", self.0)
+        }
+    }
+    impl ExternalValue for Shared {
+        const TYPE_NAME: &'static str = "shared";
+    }
+
+    let mut engine = engine();
+    engine.context.vars_mut().let_(
+        IdentStr::new_boxed("s".into()).unwrap(),
+        Value::External(ValueExternal::new(Shared(Rc::new(3)))),
+    );
+    assert_eq!(
+        eval(&mut engine, "[s, s]").unwrap().to_string(),
+        "[shared 3, shared 3]"
+    )
+}
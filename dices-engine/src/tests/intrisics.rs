@@ -0,0 +1,950 @@
+use std::borrow::Cow;
+
+use dices_ast::{ident::IdentStr, value::ValueNull, Value};
+use rand::SeedableRng;
+use rand_xoshiro::Xoshiro256PlusPlus;
+
+use super::{engine, eval};
+use crate::{EngineBuilder, IntrisicError, SharedOutput, SolveError, MAX_NUMBER_BITS};
+
+#[test]
+fn get_present_key() {
+    let mut engine = engine();
+    assert_eq!(
+        eval(&mut engine, r#"get(<|a: 1, b: 2|>, "b", 0)"#).unwrap(),
+        Value::Number(2.into())
+    )
+}
+
+#[test]
+fn get_absent_key_returns_default() {
+    let mut engine = engine();
+    assert_eq!(
+        eval(&mut engine, r#"get(<|a: 1, b: 2|>, "c", 0)"#).unwrap(),
+        Value::Number(0.into())
+    )
+}
+
+#[test]
+fn get_non_string_key() {
+    let mut engine = engine();
+    assert!(matches!(
+        eval(&mut engine, r#"get(<|a: 1, b: 2|>, 1, 0)"#),
+        Err(SolveError::IntrisicError(err)) if matches!(err.0, IntrisicError::ParamsMismatch { .. })
+    ))
+}
+
+#[test]
+fn get_non_map() {
+    let mut engine = engine();
+    assert!(matches!(
+        eval(&mut engine, r#"get([1, 2], "a", 0)"#),
+        Err(SolveError::IntrisicError(err)) if matches!(err.0, IntrisicError::ParamsMismatch { .. })
+    ))
+}
+
+#[test]
+fn mismatch_describes_the_expected_params() {
+    let mut engine = engine();
+    let Err(SolveError::IntrisicError(err)) = eval(&mut engine, r#"parse(42)"#) else {
+        panic!("`parse` should not accept a number")
+    };
+    assert_eq!(
+        err.0.to_string(),
+        "`parse` expects params matching [string], but was given [42]"
+    )
+}
+
+#[test]
+fn mismatch_keeps_the_param_count_error() {
+    let mut engine = engine();
+    assert!(matches!(
+        eval(&mut engine, r#"parse("1", "2")"#),
+        Err(SolveError::IntrisicError(err)) if matches!(err.0, IntrisicError::WrongParamNum { given: 2, .. })
+    ))
+}
+
+#[test]
+fn wrong_param_num_gives_the_arity() {
+    let mut engine = engine();
+    let Err(SolveError::IntrisicError(err)) = eval(&mut engine, "pow(2)") else {
+        panic!("`pow` should not accept a single param")
+    };
+    assert!(matches!(
+        &err.0,
+        IntrisicError::WrongParamNum { expected, given: 1, .. } if *expected == (2..=2)
+    ));
+    assert_eq!(
+        err.0.to_string(),
+        "Wrong number of params given to the intrisic pow: expected 2, given 1"
+    )
+}
+
+#[test]
+fn get_path_deep() {
+    let mut engine = engine();
+    assert_eq!(
+        eval(&mut engine, r#"get_path(<|a: <|b: <|c: 3|>|>|>, "a.b.c")"#).unwrap(),
+        Value::Number(3.into())
+    )
+}
+
+#[test]
+fn get_path_missing() {
+    let mut engine = engine();
+    assert!(matches!(
+        eval(&mut engine, r#"get_path(<|a: <|b: 2|>|>, "a.c")"#),
+        Err(SolveError::IntrisicError(err)) if matches!(err.0, IntrisicError::MissingPath(_))
+    ));
+    assert_eq!(
+        eval(&mut engine, r#"get_path(<|a: <|b: 2|>|>, "a.b.c", null)"#).unwrap(),
+        Value::Null(ValueNull)
+    )
+}
+
+#[test]
+fn get_path_checks_its_params() {
+    let mut engine = engine();
+    for cmd in [
+        r#"get_path([1], "a")"#,
+        r#"get_path(<|a: 1|>, 1)"#,
+        r#"get_path(<|a: 1|>, 1, null)"#,
+    ] {
+        assert!(
+            matches!(
+                eval(&mut engine, cmd),
+                Err(SolveError::IntrisicError(err)) if matches!(err.0, IntrisicError::ParamsMismatch { .. })
+            ),
+            "`{cmd}` should not match the params"
+        )
+    }
+    assert!(matches!(
+        eval(&mut engine, r#"get_path(<|a: 1|>, "a", null, null)"#),
+        Err(SolveError::IntrisicError(err)) if matches!(err.0, IntrisicError::WrongParamNum { given: 4, .. })
+    ))
+}
+
+#[test]
+fn set_path_creates_intermediates() {
+    let mut engine = engine();
+    assert_eq!(
+        eval(
+            &mut engine,
+            r#"let m = <|a: <|x: 1|>|>; let n = set_path(m, "a.b.c", 3); [m, n]"#
+        )
+        .unwrap(),
+        eval(
+            &mut engine,
+            r#"[<|a: <|x: 1|>|>, <|a: <|x: 1, b: <|c: 3|>|>|>]"#
+        )
+        .unwrap()
+    )
+}
+
+#[test]
+fn set_path_through_non_map() {
+    let mut engine = engine();
+    assert!(matches!(
+        eval(&mut engine, r#"set_path(<|a: 1|>, "a.b", 3)"#),
+        Err(SolveError::IntrisicError(err)) if matches!(err.0, IntrisicError::SetPathThroughNonMap { .. })
+    ))
+}
+
+#[test]
+fn sort_by_derived_key() {
+    let mut engine = engine();
+    assert_eq!(
+        eval(&mut engine, r#"sort_by([3, -5, 1, -2], |x| x * x)"#).unwrap(),
+        eval(&mut engine, r#"[1, -2, 3, -5]"#).unwrap()
+    )
+}
+
+#[test]
+fn sort_by_is_stable() {
+    let mut engine = engine();
+    assert_eq!(
+        eval(&mut engine, r#"sort_by([4, 1, 2, 3], |x| x % 2)"#).unwrap(),
+        eval(&mut engine, r#"[4, 2, 1, 3]"#).unwrap()
+    )
+}
+
+#[test]
+fn group_by_parity() {
+    let mut engine = engine();
+    assert_eq!(
+        eval(&mut engine, r#"group_by([1, 2, 3, 4, 5], |x| x % 2)"#).unwrap(),
+        eval(&mut engine, r#"<|"0": [2, 4], "1": [1, 3, 5]|>"#).unwrap()
+    )
+}
+
+#[test]
+fn group_by_rolls() {
+    let mut engine = engine();
+    let Value::List(groups) = eval(
+        &mut engine,
+        r#"
+        let rolls = 10d6;
+        let groups = group_by(rolls, |x| x % 2);
+        [
+            [get(groups, "0", []), [x for x in rolls if 1 - x % 2]],
+            [get(groups, "1", []), [x for x in rolls if x % 2]]
+        ]
+        "#,
+    )
+    .unwrap() else {
+        panic!("The test should return a list")
+    };
+    for pair in groups.iter() {
+        let Value::List(pair) = pair else {
+            panic!("The test should return pairs")
+        };
+        assert_eq!(pair[0], pair[1])
+    }
+}
+
+#[test]
+fn sample_is_deterministic_and_distinct() {
+    let Value::List(first) = eval(&mut engine(), "sample([1, 2, 3, 4, 5, 6, 7, 8], 5)").unwrap()
+    else {
+        panic!("`sample` should return a list")
+    };
+    let second = eval(&mut engine(), "sample([1, 2, 3, 4, 5, 6, 7, 8], 5)").unwrap();
+    assert_eq!(Value::List(first.clone()), second);
+
+    assert_eq!(first.len(), 5);
+    let mut drawn: Vec<_> = first.iter().collect();
+    drawn.sort();
+    drawn.dedup();
+    assert_eq!(drawn.len(), 5, "Sampled elements should be distinct")
+}
+
+#[test]
+fn sample_too_many() {
+    let mut engine = engine();
+    assert!(matches!(
+        eval(&mut engine, "sample([1, 2, 3], 4)"),
+        Err(SolveError::IntrisicError(err)) if matches!(err.0, IntrisicError::SampleTooLarge { .. })
+    ))
+}
+
+#[test]
+fn partition_by_predicate() {
+    let mut engine = engine();
+    assert_eq!(
+        eval(&mut engine, "partition([1, 2, 3, 4, 5], |x| x % 2)").unwrap(),
+        eval(&mut engine, "[[1, 3, 5], [2, 4]]").unwrap()
+    )
+}
+
+#[test]
+fn partition_all_or_nothing() {
+    let mut engine = engine();
+    assert_eq!(
+        eval(&mut engine, "partition([1, 2, 3], |x| true)").unwrap(),
+        eval(&mut engine, "[[1, 2, 3], []]").unwrap()
+    );
+    assert_eq!(
+        eval(&mut engine, "partition([1, 2, 3], |x| false)").unwrap(),
+        eval(&mut engine, "[[], [1, 2, 3]]").unwrap()
+    )
+}
+
+#[test]
+fn partition_not_callable() {
+    let mut engine = engine();
+    assert!(matches!(
+        eval(&mut engine, "partition([1, 2, 3], 5)"),
+        Err(SolveError::IntrisicError(err)) if matches!(
+            &err.0,
+            IntrisicError::PartitionFailed(SolveError::NotCallable(_))
+        )
+    ))
+}
+
+#[test]
+fn tally_counts() {
+    let mut engine = engine();
+    assert_eq!(
+        eval(&mut engine, "tally([1, 1, 2])").unwrap(),
+        eval(&mut engine, r#"<|"1": 2, "2": 1|>"#).unwrap()
+    )
+}
+
+#[test]
+fn tally_nested_values() {
+    let mut engine = engine();
+    assert_eq!(
+        eval(&mut engine, r#"tally([[1, 2], "a", [1, 2], <|b: 3|>])"#).unwrap(),
+        eval(&mut engine, r#"<|"[1, 2]": 2, a: 1, "<|b: 3|>": 1|>"#).unwrap()
+    )
+}
+
+#[test]
+fn tally_rejects_values_with_the_same_text() {
+    let mut engine = engine();
+    assert!(matches!(
+        eval(&mut engine, r#"tally([1, "1", 1, 2])"#),
+        Err(SolveError::IntrisicError(err)) if matches!(err.0, IntrisicError::SameLabel { .. })
+    ));
+    // the same values are still counted together
+    assert_eq!(
+        eval(&mut engine, r#"tally([1, 1, [1, 2], 2, [1, 2]])"#).unwrap(),
+        eval(&mut engine, r#"<|"1": 2, "2": 1, "[1, 2]": 2|>"#).unwrap()
+    )
+}
+
+#[test]
+fn sum_of_a_list() {
+    let mut engine = engine();
+    assert_eq!(
+        eval(&mut engine, "sum([1, 2, 3])").unwrap(),
+        Value::Number(6.into())
+    );
+    assert_eq!(
+        eval(&mut engine, "sum([])").unwrap(),
+        Value::Number(0.into())
+    );
+    assert!(matches!(
+        eval(&mut engine, r#"sum([1, "a"])"#),
+        Err(SolveError::IntrisicError(err)) if matches!(err.0, IntrisicError::SumFailed(_))
+    ));
+    // a single value that is not a list is returned as is
+    assert_eq!(
+        eval(&mut engine, r#"sum("a")"#).unwrap(),
+        Value::String("a".into())
+    )
+}
+
+#[test]
+fn product_of_a_list() {
+    let mut engine = engine();
+    assert_eq!(
+        eval(&mut engine, "product([2, 3, 4])").unwrap(),
+        Value::Number(24.into())
+    );
+    assert_eq!(
+        eval(&mut engine, "product([])").unwrap(),
+        Value::Number(1.into())
+    );
+    assert!(matches!(
+        eval(&mut engine, r#"product([1, "a"])"#),
+        Err(SolveError::IntrisicError(err)) if matches!(err.0, IntrisicError::ToNumber(_))
+    ))
+}
+
+#[test]
+fn mean_of_a_list() {
+    let mut engine = engine();
+    assert_eq!(
+        eval(&mut engine, "mean([1, 2, 4])").unwrap(),
+        Value::Number(2.into())
+    );
+    assert_eq!(
+        eval(&mut engine, "mean([-1, -2, -4])").unwrap(),
+        Value::Number((-2).into())
+    );
+    assert!(matches!(
+        eval(&mut engine, "mean([])"),
+        Err(SolveError::IntrisicError(err)) if matches!(err.0, IntrisicError::MeanOfEmptyList)
+    ));
+    assert!(matches!(
+        eval(&mut engine, r#"mean([1, "a"])"#),
+        Err(SolveError::IntrisicError(err)) if matches!(err.0, IntrisicError::ToNumber(_))
+    ))
+}
+
+#[test]
+fn take_beyond_length() {
+    let mut engine = engine();
+    assert_eq!(
+        eval(&mut engine, "take([1, 2, 3], 2)").unwrap(),
+        eval(&mut engine, "[1, 2]").unwrap()
+    );
+    assert_eq!(
+        eval(&mut engine, "take([1, 2, 3], 10)").unwrap(),
+        eval(&mut engine, "[1, 2, 3]").unwrap()
+    )
+}
+
+#[test]
+fn drop_beyond_length() {
+    let mut engine = engine();
+    assert_eq!(
+        eval(&mut engine, "drop([1, 2, 3], 2)").unwrap(),
+        eval(&mut engine, "[3]").unwrap()
+    );
+    assert_eq!(
+        eval(&mut engine, "drop([1, 2, 3], 10)").unwrap(),
+        eval(&mut engine, "[]").unwrap()
+    )
+}
+
+#[test]
+fn take_negative() {
+    let mut engine = engine();
+    assert!(matches!(
+        eval(&mut engine, "take([1, 2, 3], -1)"),
+        Err(SolveError::IntrisicError(err)) if matches!(err.0, IntrisicError::NegativeCount(..))
+    ))
+}
+
+#[test]
+fn first_and_last() {
+    let mut engine = engine();
+    assert_eq!(
+        eval(&mut engine, "[first([1, 2, 3]), last([1, 2, 3])]").unwrap(),
+        eval(&mut engine, "[1, 3]").unwrap()
+    );
+    assert_eq!(
+        eval(&mut engine, "first([])").unwrap(),
+        Value::Null(ValueNull)
+    );
+    assert_eq!(
+        eval(&mut engine, "last([])").unwrap(),
+        Value::Null(ValueNull)
+    )
+}
+
+#[test]
+fn print_to_output_writer() {
+    let output = SharedOutput::default();
+    let mut engine = EngineBuilder::new()
+        .with_rng(Xoshiro256PlusPlus::seed_from_u64(42))
+        .with_output_writer(output.clone())
+        .build();
+    assert_eq!(
+        eval(&mut engine, r#"print("hello", 3); print([1, 2])"#).unwrap(),
+        Value::Null(ValueNull)
+    );
+    assert_eq!(output.take(), "\"hello\"\n3\n[1, 2]\n")
+}
+
+#[test]
+fn repr_parses_back() {
+    let mut engine = engine();
+    for src in [
+        "42",
+        "-7",
+        r#""a \"quoted\" string\n""#,
+        r#"[1, "two", [3], null, true]"#,
+        r#"<|a: 1, "not an ident": [2, 3], nested: <|b: "c"|>|>"#,
+    ] {
+        let value = eval(&mut engine, src).unwrap();
+        let reparsed = eval(&mut engine, &format!("parse(repr({src}))")).unwrap();
+        assert_eq!(reparsed, value, "`{src}` did not roundtrip")
+    }
+}
+
+#[test]
+fn repr_evaluates_back() {
+    let mut engine = engine();
+    for src in [
+        "null",
+        "true",
+        "-7",
+        r#""a \"quoted\" string\n""#,
+        r#"[1, "two", [3], null]"#,
+        r#"<|a: 1, "not an ident": [2, 3]|>"#,
+        "std.intrisics.freeze(<|a: 1|>)",
+        "std.intrisics.sum",
+        "[std.lists.map, |x| x + 1]",
+        "{let y = 3; let z = |x| x * y; <|f: |x, w| z(x) - w|>}",
+    ] {
+        let value = eval(&mut engine, src).unwrap();
+        let Value::String(repr) = eval(&mut engine, &format!("repr({src})")).unwrap() else {
+            panic!("`repr` should give a string")
+        };
+        assert_eq!(
+            eval(&mut engine, &repr).unwrap(),
+            value,
+            "`{src}` did not roundtrip through `{}`",
+            *repr
+        )
+    }
+}
+
+#[test]
+fn repr_uses_the_std_name() {
+    let mut engine = EngineBuilder::new()
+        .with_rng(Xoshiro256PlusPlus::seed_from_u64(42))
+        .with_std_named(Cow::Borrowed(IdentStr::new("lib").unwrap()))
+        .build();
+    assert_eq!(
+        eval(&mut engine, "lib.intrisics.repr(lib.intrisics.sum)").unwrap(),
+        Value::String("lib.intrisics.sum".into())
+    )
+}
+
+#[test]
+fn mod_is_never_negative() {
+    let mut engine = engine();
+    for (src, res) in [
+        ("mod(7, 3)", 1),
+        ("mod(-1, 3)", 2),
+        ("mod(-7, 3)", 2),
+        ("mod(7, -3)", 1),
+        ("mod(-7, -3)", 2),
+        ("mod(-6, 3)", 0),
+    ] {
+        assert_eq!(
+            eval(&mut engine, src).unwrap(),
+            Value::Number(res.into()),
+            "`{src}` gave the wrong result"
+        )
+    }
+}
+
+#[test]
+fn mod_by_zero() {
+    let mut engine = engine();
+    assert!(matches!(
+        eval(&mut engine, "mod(5, 0)"),
+        Err(SolveError::IntrisicError(err)) if matches!(err.0, IntrisicError::ModByZero)
+    ))
+}
+
+#[test]
+fn pow_of_two() {
+    let mut engine = engine();
+    assert_eq!(
+        eval(&mut engine, "pow(2, 10)").unwrap(),
+        Value::Number(1024.into())
+    )
+}
+
+#[test]
+fn pow_does_not_overflow() {
+    let mut engine = engine();
+    assert_eq!(
+        eval(&mut engine, "pow(2, 128)").unwrap(),
+        eval(&mut engine, "0xffff_ffff_ffff_ffff_ffff_ffff_ffff_ffff + 1").unwrap()
+    )
+}
+
+#[test]
+fn pow_with_negative_exponent() {
+    let mut engine = engine();
+    assert!(matches!(
+        eval(&mut engine, "pow(2, -1)"),
+        Err(SolveError::IntrisicError(err)) if matches!(err.0, IntrisicError::NegativeExponent(_))
+    ))
+}
+
+#[test]
+fn pow_with_too_large_exponent() {
+    let mut engine = engine();
+    assert!(matches!(
+        eval(&mut engine, "pow(2, 0x1_0000_0000)"),
+        Err(SolveError::IntrisicError(err)) if matches!(err.0, IntrisicError::ExponentTooLarge(_))
+    ))
+}
+
+#[test]
+fn pow_with_too_large_result() {
+    let mut engine = engine();
+    let max_exp = MAX_NUMBER_BITS / 2;
+    assert!(eval(&mut engine, &format!("pow(2, {max_exp})")).is_ok());
+    assert!(matches!(
+        eval(&mut engine, &format!("pow(2, {})", max_exp + 1)),
+        Err(SolveError::IntrisicError(err)) if matches!(err.0, IntrisicError::ExponentTooLarge(_))
+    ));
+    // powers of `1` and `-1` never grow
+    assert_eq!(
+        eval(&mut engine, "pow(-1, 0xffff_ffff)").unwrap(),
+        Value::Number((-1).into())
+    )
+}
+
+#[test]
+fn gcd_ignores_signs() {
+    let mut engine = engine();
+    for (src, res) in [
+        ("gcd(12, 18)", 6),
+        ("gcd(-12, 18)", 6),
+        ("gcd(12, -18)", 6),
+        ("gcd(0, 7)", 7),
+        ("gcd(7, 0)", 7),
+        ("gcd(0, -7)", 7),
+    ] {
+        assert_eq!(
+            eval(&mut engine, src).unwrap(),
+            Value::Number(res.into()),
+            "`{src}` gave the wrong result"
+        )
+    }
+}
+
+#[test]
+fn lcm_ignores_signs() {
+    let mut engine = engine();
+    for (src, res) in [("lcm(4, 6)", 12), ("lcm(-4, 6)", 12), ("lcm(0, 6)", 0)] {
+        assert_eq!(
+            eval(&mut engine, src).unwrap(),
+            Value::Number(res.into()),
+            "`{src}` gave the wrong result"
+        )
+    }
+}
+
+#[test]
+fn lcm_does_not_overflow() {
+    let mut engine = engine();
+    assert_eq!(
+        eval(
+            &mut engine,
+            "lcm(0xffff_ffff_ffff_ffff_ffff_ffff_ffff_ffff, 0x1_0000_0000_0000_0000_0000_0000_0000_0000)"
+        )
+        .unwrap(),
+        eval(
+            &mut engine,
+            "0xffff_ffff_ffff_ffff_ffff_ffff_ffff_ffff * 0x1_0000_0000_0000_0000_0000_0000_0000_0000"
+        )
+        .unwrap()
+    )
+}
+
+#[test]
+fn frozen_map_rejects_set() {
+    let mut engine = engine();
+    eval(
+        &mut engine,
+        "let rules = freeze(<|crit: 20, dc: <|easy: 10|>|>)",
+    )
+    .unwrap();
+    assert!(matches!(
+        eval(&mut engine, "rules.crit = 19"),
+        Err(SolveError::FrozenMap)
+    ));
+    // nested maps are reached through the frozen one
+    assert!(matches!(
+        eval(&mut engine, "rules.dc.easy = 5"),
+        Err(SolveError::FrozenMap)
+    ));
+    assert_eq!(
+        eval(&mut engine, "rules").unwrap(),
+        eval(&mut engine, "<|crit: 20, dc: <|easy: 10|>|>").unwrap()
+    );
+    // the variable can still be replaced
+    assert_eq!(
+        eval(&mut engine, "rules = 3").unwrap(),
+        Value::Number(3.into())
+    )
+}
+
+#[test]
+fn copy_of_frozen_map_can_be_refrozen() {
+    let mut engine = engine();
+    eval(&mut engine, "let rules = freeze(<|crit: 20|>)").unwrap();
+    eval(&mut engine, "let house = <||> ~ rules; house.crit = 19").unwrap();
+    eval(&mut engine, "house = freeze(house)").unwrap();
+    assert!(matches!(
+        eval(&mut engine, "house.crit = 18"),
+        Err(SolveError::FrozenMap)
+    ));
+    assert_eq!(
+        eval(&mut engine, "[rules.crit, house.crit]").unwrap(),
+        eval(&mut engine, "[20, 19]").unwrap()
+    )
+}
+
+#[test]
+fn crit_check_natural_max() {
+    let mut engine = engine();
+    // with this seed, the fourth d20 lands on 20
+    eval(&mut engine, "d20 ^ 3").unwrap();
+    assert_eq!(
+        eval(&mut engine, "crit_check(|| d20 + 5)").unwrap(),
+        eval(&mut engine, "<|nat_max: true, nat_min: false, total: 25|>").unwrap()
+    )
+}
+
+#[test]
+fn crit_check_no_crit() {
+    let mut engine = engine();
+    // with this seed, the first d20 lands on 11
+    assert_eq!(
+        eval(&mut engine, "crit_check(|| d20 + 5)").unwrap(),
+        eval(&mut engine, "<|nat_max: false, nat_min: false, total: 16|>").unwrap()
+    )
+}
+
+#[test]
+fn reroll_budget_is_exhausted() {
+    let mut engine = EngineBuilder::new()
+        .with_rng(Xoshiro256PlusPlus::seed_from_u64(42))
+        .with_reroll_budget(2)
+        .build();
+    eval(&mut engine, "reroll(|| d20); reroll(|| d20)").unwrap();
+    assert_eq!(engine.reroll_budget(), Some(0));
+    assert!(matches!(
+        eval(&mut engine, "reroll(|| d20)"),
+        Err(SolveError::IntrisicError(err)) if matches!(err.0, IntrisicError::NoRerollsLeft)
+    ))
+}
+
+#[test]
+fn reroll_budget_persists_across_commands() {
+    let mut engine = EngineBuilder::new()
+        .with_rng(Xoshiro256PlusPlus::seed_from_u64(42))
+        .with_reroll_budget(3)
+        .build();
+    let mut expected = super::engine();
+    for _ in 0..3 {
+        assert_eq!(
+            eval(&mut engine, "reroll(|| d20)").unwrap(),
+            eval(&mut expected, "d20").unwrap()
+        );
+    }
+    assert_eq!(engine.reroll_budget(), Some(0));
+    engine.set_reroll_budget(Some(1));
+    assert!(eval(&mut engine, "reroll(|| d20)").is_ok())
+}
+
+#[test]
+fn rerolls_are_unlimited_by_default() {
+    let mut engine = engine();
+    assert_eq!(engine.reroll_budget(), None);
+    assert!(eval(&mut engine, "reroll(|| d20) ^ 100").is_ok())
+}
+
+#[test]
+fn resolve_full_cancellation() {
+    let mut engine = engine();
+    assert_eq!(
+        eval(
+            &mut engine,
+            r#"resolve(["success", "failure", "failure", "success"], <|success: "failure"|>)"#
+        )
+        .unwrap(),
+        eval(&mut engine, "<||>").unwrap()
+    )
+}
+
+#[test]
+fn resolve_partial_netting() {
+    let mut engine = engine();
+    assert_eq!(
+        eval(
+            &mut engine,
+            r#"resolve(
+                ["success", "success", "failure", "advantage", "threat", "threat", "triumph"],
+                <|success: "failure", advantage: "threat"|>
+            )"#
+        )
+        .unwrap(),
+        eval(&mut engine, "<|success: 1, threat: 1, triumph: 1|>").unwrap()
+    );
+    // the counts can be given directly
+    assert_eq!(
+        eval(
+            &mut engine,
+            r#"resolve(<|success: 2, failure: 5|>, <|failure: "success"|>)"#
+        )
+        .unwrap(),
+        eval(&mut engine, "<|failure: 3|>").unwrap()
+    )
+}
+
+#[test]
+fn resolve_no_overlap() {
+    let mut engine = engine();
+    assert_eq!(
+        eval(
+            &mut engine,
+            r#"resolve(["success", "advantage", "success"], <|success: "failure", advantage: "threat"|>)"#
+        )
+        .unwrap(),
+        eval(&mut engine, "<|advantage: 1, success: 2|>").unwrap()
+    )
+}
+
+#[test]
+fn resolve_cancel_table_needs_labels() {
+    let mut engine = engine();
+    assert!(matches!(
+        eval(&mut engine, r#"resolve(["success"], <|success: 1|>)"#),
+        Err(SolveError::IntrisicError(err)) if matches!(err.0, IntrisicError::CancelledByNotALabel { .. })
+    ))
+}
+
+#[test]
+fn scalar_unwraps_single_element_lists() {
+    let mut engine = engine();
+    assert_eq!(
+        eval(&mut engine, "scalar([5])").unwrap(),
+        Value::Number(5.into())
+    );
+    // keeping the highest die gives a list
+    assert!(matches!(
+        eval(&mut engine, "scalar(3d6 kh 1)").unwrap(),
+        Value::Number(_)
+    ));
+}
+
+#[test]
+fn scalar_leaves_scalars_unchanged() {
+    let mut engine = engine();
+    assert_eq!(
+        eval(&mut engine, "scalar(3)").unwrap(),
+        Value::Number(3.into())
+    );
+    assert_eq!(
+        eval(&mut engine, r#"scalar("a")"#).unwrap(),
+        Value::String("a".into())
+    );
+}
+
+#[test]
+fn scalar_of_many_elements_fails() {
+    let mut engine = engine();
+    for (cmd, len) in [("scalar([1, 2])", 2), ("scalar([])", 0)] {
+        assert!(matches!(
+            eval(&mut engine, cmd),
+            Err(SolveError::IntrisicError(err)) if matches!(err.0, IntrisicError::NotAScalar(l) if l == len)
+        ))
+    }
+}
+
+#[test]
+fn cap_bounds_each_die() {
+    let mut engine = engine();
+    let rolls = eval(&mut engine, "cap(4d6, 3)").unwrap();
+    let rolls = rolls.as_list().unwrap();
+    assert_eq!(rolls.len(), 4);
+    assert!(rolls.iter().all(|roll| {
+        let roll = i64::try_from(roll.clone()).unwrap();
+        (1..=3).contains(&roll)
+    }));
+}
+
+#[test]
+fn cap_and_floor_at_bound_a_sum() {
+    let mut engine = engine();
+    assert_eq!(
+        eval(&mut engine, "cap(+[6, 6, 6], 10)").unwrap(),
+        Value::Number(10.into())
+    );
+    assert_eq!(
+        eval(&mut engine, "cap(+[1, 2], 10)").unwrap(),
+        Value::Number(3.into())
+    );
+    assert_eq!(
+        eval(&mut engine, "floor_at(+[1, 2], 5)").unwrap(),
+        Value::Number(5.into())
+    );
+    assert_eq!(
+        eval(&mut engine, "floor_at(+[6, 6], 5)").unwrap(),
+        Value::Number(12.into())
+    );
+}
+
+#[test]
+fn advantage_keeps_the_highest_roll() {
+    let mut engine = engine();
+    for cmd in ["advantage()", "advantage(6)", "disadvantage(8)"] {
+        let roll = eval(&mut engine, cmd).unwrap();
+        let roll = roll.as_map().unwrap();
+        let rolls = roll.get("rolls").unwrap().as_list().unwrap();
+        let (result, dropped) = (roll.get("result").unwrap(), roll.get("dropped").unwrap());
+        assert_eq!(rolls.len(), 2);
+        assert!(rolls.contains(result) && rolls.contains(dropped));
+        if cmd.starts_with("advantage") {
+            assert!(result >= dropped, "`{cmd}` kept the lowest roll")
+        } else {
+            assert!(result <= dropped, "`{cmd}` kept the highest roll")
+        }
+    }
+}
+
+#[test]
+fn advantage_takes_at_most_the_faces() {
+    let mut engine = engine();
+    assert!(matches!(
+        eval(&mut engine, "advantage(20, 20)"),
+        Err(SolveError::IntrisicError(err)) if matches!(err.0, IntrisicError::WrongParamNum { given: 2, .. })
+    ));
+    assert!(matches!(
+        eval(&mut engine, "disadvantage(0)"),
+        Err(SolveError::IntrisicError(err)) if matches!(err.0, IntrisicError::RollFailed(_))
+    ));
+}
+
+#[test]
+fn sort_uses_the_value_ordering() {
+    let mut engine = engine();
+    assert_eq!(
+        eval(&mut engine, "sort([3, 1, 2])").unwrap(),
+        eval(&mut engine, "[1, 2, 3]").unwrap()
+    );
+    assert_eq!(
+        eval(&mut engine, r#"sort(["a", 3, null, [1], true])"#).unwrap(),
+        eval(&mut engine, r#"[null, true, 3, "a", [1]]"#).unwrap()
+    );
+    assert_eq!(
+        eval(&mut engine, "reverse(sort([3, 1, 2]))").unwrap(),
+        eval(&mut engine, "[3, 2, 1]").unwrap()
+    );
+}
+
+#[test]
+fn sort_by_a_key() {
+    let mut engine = engine();
+    assert_eq!(
+        eval(&mut engine, "sort([3, -5, 1, -2], |x| x * x)").unwrap(),
+        eval(&mut engine, "[1, -2, 3, -5]").unwrap()
+    );
+}
+
+#[test]
+fn failing_sort_leaves_the_list_untouched() {
+    let mut engine = engine();
+    eval(&mut engine, "let l = [2, 0, 1]").unwrap();
+    assert!(matches!(
+        eval(&mut engine, "sort(l, |x| 6 / x)"),
+        Err(SolveError::IntrisicError(err)) if matches!(err.0, IntrisicError::SortByFailed(_))
+    ));
+    assert_eq!(
+        eval(&mut engine, "l").unwrap(),
+        eval(&mut engine, "[2, 0, 1]").unwrap()
+    );
+    assert!(matches!(
+        eval(&mut engine, "sort(l, |x| x, 3)"),
+        Err(SolveError::IntrisicError(err)) if matches!(err.0, IntrisicError::WrongParamNum { given: 3, .. })
+    ));
+}
+
+#[test]
+fn dist_counts_the_outcomes() {
+    let mut engine = engine();
+    let dist = eval(&mut engine, "dist(|| d4, 200)").unwrap();
+    let dist = dist.as_map().unwrap();
+    let mut total = 0;
+    for (outcome, count) in dist.iter() {
+        assert!(["1", "2", "3", "4"].contains(&&***outcome));
+        total += i64::try_from(count.clone()).unwrap();
+    }
+    assert_eq!(total, 200);
+    // by default a thousand samples are taken
+    let dist = eval(&mut engine, "+dist(|| 3)").unwrap();
+    assert_eq!(dist, Value::Number(1000.into()));
+}
+
+#[test]
+fn dist_is_capped() {
+    let mut engine = EngineBuilder::new()
+        .with_rng(Xoshiro256PlusPlus::seed_from_u64(42))
+        .with_max_iterations(100)
+        .build();
+    assert_eq!(
+        eval(&mut engine, "+dist(|| d6)").unwrap(),
+        Value::Number(100.into())
+    );
+    assert!(matches!(
+        eval(&mut engine, "dist(|| d6, 101)"),
+        Err(SolveError::IntrisicError(err)) if matches!(err.0, IntrisicError::TooManySamples { max: 100, .. })
+    ));
+    assert!(matches!(
+        eval(&mut engine, "dist(|| d0)"),
+        Err(SolveError::IntrisicError(err)) if matches!(err.0, IntrisicError::DistFailed(_))
+    ));
+}
@@ -0,0 +1,64 @@
+use std::time::Duration;
+
+use dices_ast::Value;
+use rand::SeedableRng;
+use rand_xoshiro::Xoshiro256PlusPlus;
+
+use super::{engine, eval};
+use crate::{EngineBuilder, SolveError};
+
+#[test]
+fn repeat_until_stops_when_condition_is_true() {
+    let mut engine = engine();
+    assert_eq!(
+        eval(&mut engine, "let x = 0; repeat x = x + 1 until x / 5").unwrap(),
+        Value::Number(5.into())
+    )
+}
+
+#[test]
+fn repeat_until_evaluates_body_at_least_once() {
+    let mut engine = engine();
+    assert_eq!(
+        eval(&mut engine, "let x = 0; repeat x = x + 1 until true; x").unwrap(),
+        Value::Number(1.into())
+    )
+}
+
+#[test]
+fn repeat_until_hits_the_iteration_cap() {
+    let mut engine = EngineBuilder::new()
+        .with_rng(Xoshiro256PlusPlus::seed_from_u64(42))
+        .with_max_iterations(100)
+        .build();
+    assert!(matches!(
+        eval(&mut engine, "let x = 0; repeat x = x + 1 until false"),
+        Err(SolveError::IterationLimitReached(100))
+    ));
+    // the loop run exactly up to the cap
+    assert_eq!(eval(&mut engine, "x").unwrap(), Value::Number(100.into()))
+}
+
+#[test]
+fn repeat_until_condition_must_be_a_number() {
+    let mut engine = engine();
+    assert!(matches!(
+        eval(&mut engine, "repeat 1 until [1, 2]"),
+        Err(SolveError::ConditionIsNotANumber(_))
+    ))
+}
+
+#[test]
+fn slow_evaluations_time_out() {
+    let mut engine = EngineBuilder::new()
+        .with_rng(Xoshiro256PlusPlus::seed_from_u64(42))
+        .with_max_iterations(usize::MAX)
+        .with_timeout(Duration::from_millis(50))
+        .build();
+    assert!(matches!(
+        eval(&mut engine, "repeat 1 until false"),
+        Err(SolveError::TimedOut(timeout)) if timeout == Duration::from_millis(50)
+    ));
+    // each evaluation has its own time
+    assert_eq!(eval(&mut engine, "1 + 1").unwrap(), Value::Number(2.into()))
+}
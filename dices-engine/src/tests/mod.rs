@@ -0,0 +1,238 @@
+//! Tests of the engine behaviour that cannot be checked by the manual examples
+
+use dices_ast::{intrisics::NoInjectedIntrisics, Value};
+use rand::SeedableRng;
+use rand_xoshiro::Xoshiro256PlusPlus;
+
+use crate::{Engine, SolveError};
+
+mod external;
+mod intrisics;
+mod loops;
+mod operators;
+#[cfg(feature = "eval_str")]
+mod reader;
+mod rngs;
+mod sandboxing;
+mod serialization;
+mod signatures;
+mod undefined_refs;
+
+/// Create a new engine with a fixed seed
+fn engine() -> Engine<Xoshiro256PlusPlus, NoInjectedIntrisics> {
+    Engine::new_with_rng(Xoshiro256PlusPlus::seed_from_u64(42))
+}
+
+/// Evaluate a command in the given engine
+fn eval(
+    engine: &mut Engine<Xoshiro256PlusPlus, NoInjectedIntrisics>,
+    cmd: &str,
+) -> Result<Value, SolveError<NoInjectedIntrisics>> {
+    let exprs = dices_ast::parse_file(cmd).expect("The test command should be parseable");
+    engine.eval_multiple(&exprs)
+}
+
+#[test]
+fn eval_slice_of_many_expressions() {
+    let exprs = dices_ast::parse_file("let x = 2; let y = x * 3; y + 1").unwrap();
+    let exprs: Vec<_> = exprs.iter().cloned().collect();
+    assert_eq!(
+        engine().eval_slice(&exprs).unwrap(),
+        Value::Number(7.into())
+    )
+}
+
+#[test]
+fn eval_slice_empty() {
+    assert!(matches!(
+        engine().eval_slice(&[]),
+        Err(SolveError::NothingToEvaluate)
+    ))
+}
+
+#[test]
+fn failing_member_access_reports_span() {
+    let Err(SolveError::At {
+        span,
+        position,
+        source,
+    }) = eval(&mut engine(), "let m = <|a: 1|>; m.b")
+    else {
+        panic!("The access should fail with a span")
+    };
+    assert_eq!(span, 18..21);
+    assert_eq!((position.line, position.column), (1, 19));
+    assert!(matches!(source.0, SolveError::MissingKey(key) if **key == *"b"));
+
+    let Err(err) = eval(&mut engine(), "[1, 2][5]") else {
+        panic!("The access should fail")
+    };
+    assert_eq!(err.span(), Some(0..9));
+}
+
+#[test]
+fn errors_are_shown_at_line_and_column() {
+    let Err(err) = eval(
+        &mut engine(),
+        "let m = <|a: 1|>;
+let n = 2;
+  m.b",
+    ) else {
+        panic!("The access should fail")
+    };
+    assert_eq!(err.to_string(), "Error at 3:3 in the source")
+}
+
+#[test]
+fn map_with_computed_key() {
+    assert_eq!(
+        eval(&mut engine(), r#"let k = "a" ~ "b"; <|(k): 1, c: 2|>.ab"#).unwrap(),
+        Value::Number(1.into())
+    );
+    // closures capture the variables used in the keys
+    assert_eq!(
+        eval(
+            &mut engine(),
+            r#"let k = "x"; let f = |v| <|(k): v|>; f(3).x"#
+        )
+        .unwrap(),
+        Value::Number(3.into())
+    )
+}
+
+#[test]
+fn map_computed_key_must_be_a_string() {
+    assert!(matches!(
+        eval(&mut engine(), "let k = 1; <|(k): 1|>"),
+        Err(SolveError::MapIsIndexedByStrings(Value::Number(_)))
+    ))
+}
+
+#[test]
+fn reset_keeps_the_rng() {
+    let mut expected = engine();
+    eval(&mut expected, "d100").unwrap();
+    let expected = eval(&mut expected, "d100 ^ 3").unwrap();
+
+    let mut engine = engine();
+    eval(&mut engine, "let x = d100").unwrap();
+    engine.reset();
+    assert!(matches!(
+        eval(&mut engine, "x"),
+        Err(SolveError::InvalidReference(_))
+    ));
+    // the std library and the prelude are available again
+    assert_eq!(eval(&mut engine, "d100 ^ 3").unwrap(), expected)
+}
+
+#[test]
+fn call_by_name() {
+    let mut engine = engine();
+    assert_eq!(
+        engine
+            .call(
+                "sum",
+                vec![Value::Number(1.into()), Value::Number(2.into())]
+            )
+            .unwrap(),
+        Value::Number(3.into())
+    );
+    eval(
+        &mut engine,
+        "let bonus = 2; let attack = |roll| roll + bonus",
+    )
+    .unwrap();
+    assert_eq!(
+        engine
+            .call("attack", vec![Value::Number(15.into())])
+            .unwrap(),
+        Value::Number(17.into())
+    );
+}
+
+#[test]
+fn call_by_name_fails_clearly() {
+    let mut engine = engine();
+    eval(&mut engine, "let x = 3").unwrap();
+    assert!(matches!(
+        engine.call("missing", vec![]),
+        Err(SolveError::InvalidReference(name)) if &**name == "missing"
+    ));
+    assert!(matches!(
+        engine.call("x", vec![]),
+        Err(SolveError::NotCallable(Value::Number(_)))
+    ));
+    assert!(matches!(
+        engine.call("not a name", vec![]),
+        Err(SolveError::InvalidName(_))
+    ));
+}
+
+#[test]
+fn explain_records_the_steps() {
+    let expr = dices_ast::parse_file("2 + 3 * 4").unwrap();
+    let trace = engine().explain(expr.first()).unwrap();
+    assert_eq!(trace.value, Value::Number(14.into()));
+    let [two, mult] = &*trace.steps else {
+        panic!("The sum should have two steps, not {:?}", trace.steps)
+    };
+    assert_eq!(two.value, Value::Number(2.into()));
+    // the multiplication is solved before the sum can be
+    assert_eq!(mult.value, Value::Number(12.into()));
+    let values: Vec<_> = mult.steps.iter().map(|step| step.value.clone()).collect();
+    assert_eq!(values, [Value::Number(3.into()), Value::Number(4.into())]);
+    assert!(mult.steps.iter().all(|step| step.steps.is_empty()));
+}
+
+#[test]
+fn underscore_discards_the_value() {
+    let mut engine = engine();
+    // the value is still evaluated, with all its effects
+    assert_eq!(
+        eval(&mut engine, "let x = 1; let _ = (x = 5); _ = x + 1; x").unwrap(),
+        Value::Number(5.into())
+    );
+    // but no variable is created
+    assert!(dices_ast::ident::IdentStr::new("_").is_none());
+    assert!(matches!(
+        engine.call("_", vec![]),
+        Err(SolveError::InvalidName(_))
+    ));
+}
+
+#[test]
+fn variables_are_listed() {
+    let mut engine = engine();
+    eval(
+        &mut engine,
+        "let hp = 12; let name = \"orc\"; { let inner = 1 }",
+    )
+    .unwrap();
+    let names: Vec<_> = engine
+        .variables()
+        .map(|(name, _)| name.to_string())
+        .collect();
+    assert!(names.iter().any(|name| name == "std"));
+    assert!(names.iter().any(|name| name == "sum"));
+    assert!(!names.iter().any(|name| name == "inner"));
+
+    let name = dices_ast::ident::IdentStr::new("hp").unwrap();
+    assert_eq!(
+        engine.get_var(name),
+        Some(&Value::from(dices_ast::value::ValueNumber::from(12)))
+    );
+    let missing = dices_ast::ident::IdentStr::new("mp").unwrap();
+    assert_eq!(engine.get_var(missing), None);
+}
+
+#[test]
+fn user_variables_skip_the_std_library() {
+    let mut engine = engine();
+    eval(&mut engine, "let hp = 12; let sum = 3").unwrap();
+    let names: Vec<_> = engine
+        .user_variables()
+        .map(|(name, _)| name.to_string())
+        .collect();
+    // `sum` was given a new value, so it is not the prelude one anymore
+    assert_eq!(names, ["hp", "sum"]);
+}
@@ -0,0 +1,396 @@
+use dices_ast::{expression::bin_ops::BinOp, Value};
+use rand::SeedableRng;
+use rand_xoshiro::Xoshiro256PlusPlus;
+
+use super::{engine, eval};
+use crate::{EngineBuilder, IntrisicError, SolveError, MAX_NUMBER_BITS, MAX_REPEATED_LEN};
+
+#[test]
+fn shift_by_negative() {
+    let mut engine = engine();
+    assert!(matches!(
+        eval(&mut engine, "1 << -1"),
+        Err(SolveError::ShiftNeedPositive { op: BinOp::Shl, .. })
+    ));
+    assert!(matches!(
+        eval(&mut engine, "1 >> -1"),
+        Err(SolveError::ShiftNeedPositive { op: BinOp::Shr, .. })
+    ))
+}
+
+#[test]
+fn shift_past_64_bits() {
+    let mut engine = engine();
+    assert_eq!(
+        eval(&mut engine, "(1 << 100) >> 98").unwrap(),
+        Value::Number(4.into())
+    )
+}
+
+#[test]
+fn shift_with_too_large_result() {
+    let mut engine = engine();
+    assert!(eval(&mut engine, &format!("1 << {}", MAX_NUMBER_BITS - 1)).is_ok());
+    assert!(matches!(
+        eval(&mut engine, &format!("1 << {}", MAX_NUMBER_BITS)),
+        Err(SolveError::ShiftTooLarge(_))
+    ));
+    assert_eq!(
+        eval(&mut engine, "0 << 0xffffffff").unwrap(),
+        Value::Number(0.into())
+    )
+}
+
+#[test]
+fn bitwise_on_lists() {
+    let mut engine = engine();
+    assert!(matches!(
+        eval(&mut engine, "[1, 2] & 3"),
+        Err(SolveError::LHSIsNotANumber {
+            op: BinOp::BitAnd,
+            ..
+        })
+    ))
+}
+
+#[cfg(feature = "eval_str")]
+#[test]
+fn custom_operator() {
+    use dices_ast::intrisics::Intrisic;
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256PlusPlus;
+
+    use crate::EngineBuilder;
+
+    let mut engine = EngineBuilder::new()
+        .with_rng(Xoshiro256PlusPlus::seed_from_u64(42))
+        .with_operator("<>", Intrisic::Pow)
+        .unwrap()
+        .build();
+    assert_eq!(
+        engine.eval_str("2 <> 10").unwrap(),
+        Value::Number(1024.into())
+    );
+    assert_eq!(
+        engine.eval_str("2 <> 3 + 1").unwrap(),
+        Value::Number(16.into())
+    );
+    assert!(engine.eval_str("2 <?> 3").is_err())
+}
+
+#[cfg(feature = "eval_str")]
+#[test]
+fn custom_operator_with_invalid_symbol() {
+    use dices_ast::intrisics::Intrisic;
+
+    use crate::EngineBuilder;
+
+    assert!(EngineBuilder::new()
+        .with_operator("+", Intrisic::Pow)
+        .is_err())
+}
+
+#[test]
+fn is_matches_list_shapes() {
+    let mut engine = engine();
+    assert_eq!(
+        eval(&mut engine, "[1, 2] is [_, _]").unwrap(),
+        Value::Bool(true.into())
+    );
+    assert_eq!(
+        eval(&mut engine, "[1, [2, 3]] is [number, [_, 1..=6]]").unwrap(),
+        Value::Bool(true.into())
+    );
+    assert_eq!(
+        eval(&mut engine, "[1, 2, 3] is [_, _]").unwrap(),
+        Value::Bool(false.into())
+    )
+}
+
+#[test]
+fn is_matches_literals() {
+    let mut engine = engine();
+    assert_eq!(
+        eval(&mut engine, "let x = 3; x is 3 || 4").unwrap(),
+        Value::Bool(true.into())
+    );
+    assert_eq!(
+        eval(&mut engine, r#"<|a: "b"|> is <|a: "b"|>"#).unwrap(),
+        Value::Bool(true.into())
+    );
+    assert_eq!(
+        eval(&mut engine, "5 is !(3 || 4)").unwrap(),
+        Value::Bool(true.into())
+    );
+    assert_eq!(
+        eval(&mut engine, r#""a" is 3"#).unwrap(),
+        Value::Bool(false.into())
+    )
+}
+
+#[test]
+fn matches_with_invalid_pattern() {
+    let mut engine = engine();
+    assert!(matches!(
+        eval(&mut engine, r#"matches(1, "[_")"#),
+        Err(SolveError::IntrisicError(err)) if matches!(err.0, IntrisicError::InvalidPattern(_))
+    ))
+}
+
+#[test]
+fn matches_with_unknown_type() {
+    let mut engine = engine();
+    assert_eq!(
+        eval(&mut engine, r#"matches(5, "number")"#).unwrap(),
+        Value::Bool(true.into())
+    );
+    assert!(matches!(
+        eval(&mut engine, r#"matches(5, "nubmer")"#),
+        Err(SolveError::IntrisicError(err)) if matches!(err.0, IntrisicError::InvalidPattern(_))
+    ))
+}
+
+#[test]
+fn string_repetition() {
+    let mut engine = engine();
+    assert_eq!(
+        eval(&mut engine, r#""ab" * 3"#).unwrap(),
+        Value::String("ababab".into())
+    );
+    assert_eq!(
+        eval(&mut engine, r#"2 * "ab""#).unwrap(),
+        Value::String("abab".into())
+    );
+    assert_eq!(
+        eval(&mut engine, r#""ab" * 0"#).unwrap(),
+        Value::String("".into())
+    );
+    assert!(matches!(
+        eval(&mut engine, r#""ab" * -1"#),
+        Err(SolveError::NegativeRepeats(_))
+    ));
+    assert!(matches!(
+        eval(&mut engine, r#""ab" * "cd""#),
+        Err(SolveError::RepeatTimesNotANumber(_))
+    ))
+}
+
+#[test]
+fn repetition_with_too_long_result() {
+    let mut engine = engine();
+    assert!(eval(&mut engine, &format!(r#""ab" * {}"#, MAX_REPEATED_LEN / 2)).is_ok());
+    assert!(matches!(
+        eval(
+            &mut engine,
+            &format!(r#""ab" * {}"#, MAX_REPEATED_LEN / 2 + 1)
+        ),
+        Err(SolveError::RepeatedTooLong(_))
+    ));
+    assert!(matches!(
+        eval(&mut engine, r#"["a"] * (1 << 100)"#),
+        Err(SolveError::RepeatedTooLong(_))
+    ));
+    assert_eq!(
+        eval(&mut engine, r#""" * 0xffffffff"#).unwrap(),
+        Value::String("".into())
+    )
+}
+
+#[test]
+fn list_multiplication() {
+    let mut engine = engine();
+    // lists of numbers are multiplied element-wise
+    assert_eq!(
+        eval(&mut engine, "[1, [2, true]] * 3").unwrap(),
+        eval(&mut engine, "[3, [6, 3]]").unwrap()
+    );
+    // other lists are repeated
+    assert_eq!(
+        eval(&mut engine, r#"["a", 1] * 2"#).unwrap(),
+        eval(&mut engine, r#"["a", 1, "a", 1]"#).unwrap()
+    );
+    assert_eq!(
+        eval(&mut engine, r#"2 * [[1], "b"]"#).unwrap(),
+        eval(&mut engine, r#"[[1], "b", [1], "b"]"#).unwrap()
+    );
+    assert_eq!(
+        eval(&mut engine, r#"["a"] * 0"#).unwrap(),
+        eval(&mut engine, "[]").unwrap()
+    );
+    assert!(matches!(
+        eval(&mut engine, r#"["a"] * -1"#),
+        Err(SolveError::NegativeRepeats(_))
+    ))
+}
+
+#[test]
+fn labeled_dice_follow_the_weights() {
+    let mut engine = engine();
+    let Value::Map(counts) = eval(
+        &mut engine,
+        "tally(d<|success: 3, advantage: 2, blank: 1, never: 0|> ^ 6000)",
+    )
+    .unwrap() else {
+        panic!("`tally` should give a map")
+    };
+    let count = |label: &str| match counts.get(label) {
+        Some(Value::Number(n)) => i64::try_from(n.clone()).unwrap(),
+        None => 0,
+        Some(v) => panic!("Unexpected count {v}"),
+    };
+    assert_eq!(
+        counts.len(),
+        3,
+        "Only the labels with a weight can be rolled"
+    );
+    assert_eq!(count("success") + count("advantage") + count("blank"), 6000);
+    // the expected counts are 3000, 2000 and 1000
+    assert!((2850..=3150).contains(&count("success")));
+    assert!((1850..=2150).contains(&count("advantage")));
+    assert!((900..=1100).contains(&count("blank")));
+}
+
+#[test]
+fn labeled_dice_need_a_face() {
+    let mut engine = engine();
+    assert!(matches!(
+        eval(&mut engine, "d<|a: 0|>"),
+        Err(SolveError::NoFaceCanBeRolled)
+    ));
+    assert!(matches!(
+        eval(&mut engine, "d<||>"),
+        Err(SolveError::NoFaceCanBeRolled)
+    ));
+    assert!(matches!(
+        eval(&mut engine, "d<|a: -1, b: 2|>"),
+        Err(SolveError::FaceWeightMustNotBeNegative { .. })
+    ))
+}
+
+#[test]
+fn division_by_zero_fails() {
+    let mut engine = engine();
+    assert!(matches!(
+        eval(&mut engine, "1 / 0"),
+        Err(SolveError::DivisionByZero)
+    ));
+    assert!(matches!(
+        eval(&mut engine, "[4, 2] % 0"),
+        Err(SolveError::DivisionByZero)
+    ));
+}
+
+#[test]
+fn lists_can_coalesce_errors() {
+    // by default, the first error fails the whole list
+    assert!(matches!(
+        eval(&mut engine(), "[1, 2 / 0, 3]"),
+        Err(SolveError::DivisionByZero)
+    ));
+
+    let mut engine = EngineBuilder::new()
+        .with_rng(Xoshiro256PlusPlus::seed_from_u64(42))
+        .with_coalesce_errors()
+        .build();
+    let results = eval(&mut engine, "[1, 2 / 0, 3]")
+        .unwrap()
+        .into_list()
+        .unwrap();
+    let results: Vec<_> = results
+        .iter()
+        .map(|result| {
+            let result = result.as_map().unwrap();
+            assert_eq!(result.len(), 1);
+            result.iter().next().unwrap()
+        })
+        .map(|(key, value)| (&***key, value.to_string()))
+        .collect();
+    assert_eq!(
+        results,
+        [
+            ("ok", "1".to_owned()),
+            ("err", "\"Division by zero\"".to_owned()),
+            ("ok", "3".to_owned())
+        ]
+    );
+}
+
+#[test]
+fn exploding_dice_add_their_rolls() {
+    let mut engine = engine();
+    let rolls = eval(&mut engine, "1000d!4").unwrap();
+    let rolls = rolls.as_list().unwrap();
+    assert_eq!(rolls.len(), 1000);
+    // a total that is a multiple of the faces would have exploded again
+    assert!(rolls.iter().all(|roll| {
+        let roll = i64::try_from(roll.clone()).unwrap();
+        roll >= 1 && roll % 4 != 0
+    }));
+    assert!(rolls.iter().any(|roll| {
+        let roll = i64::try_from(roll.clone()).unwrap();
+        (5..=7).contains(&roll)
+    }));
+}
+
+#[test]
+fn exploding_dice_need_two_faces() {
+    let mut engine = engine();
+    assert!(matches!(
+        eval(&mut engine, "d!1"),
+        Err(SolveError::ExplodingSingleFace)
+    ));
+    assert!(matches!(
+        eval(&mut engine, "d!0"),
+        Err(SolveError::FacesMustBePositive { .. })
+    ));
+    assert!(matches!(
+        eval(&mut engine, "d!\"a\""),
+        Err(SolveError::FacesAreNotANumber { .. })
+    ));
+}
+
+/// Roll many dice with a reroll condition, checking which results are left
+fn rerolled(cond: &str) -> Vec<i64> {
+    let mut engine = engine();
+    let rolls = eval(&mut engine, &format!("1000d6 r {cond}")).unwrap();
+    let mut rolls: Vec<i64> = Vec::try_from(rolls)
+        .unwrap()
+        .into_iter()
+        .map(|roll| i64::try_from(roll).unwrap())
+        .collect();
+    assert_eq!(rolls.len(), 1000);
+    rolls.sort();
+    rolls.dedup();
+    rolls
+}
+
+#[test]
+fn reroll_on_a_value() {
+    assert_eq!(rerolled("1"), [2, 3, 4, 5, 6]);
+}
+
+#[test]
+fn reroll_on_a_list() {
+    assert_eq!(rerolled("[1, 2]"), [3, 4, 5, 6]);
+}
+
+#[test]
+fn reroll_on_a_predicate() {
+    assert_eq!(rerolled("(|x| x is 4..=6)"), [1, 2, 3]);
+}
+
+#[test]
+fn reroll_always_matching_is_stopped() {
+    let mut engine = EngineBuilder::new()
+        .with_rng(Xoshiro256PlusPlus::seed_from_u64(42))
+        .with_max_iterations(100)
+        .build();
+    assert!(matches!(
+        eval(&mut engine, "3d6 r (|x| true)"),
+        Err(SolveError::IterationLimitReached(100))
+    ));
+    assert!(matches!(
+        eval(&mut engine, r#"d6 r (|x| "no")"#),
+        Err(SolveError::RerollConditionIsNotANumber(_))
+    ));
+}
@@ -0,0 +1,67 @@
+use std::io::BufReader;
+
+use dices_ast::Value;
+
+use super::engine;
+use crate::EvalReaderError;
+
+#[test]
+fn evaluates_all_statements() {
+    let mut engine = engine();
+    let src = "let x = 1;\nlet y = x + 1;\nx + y";
+    assert_eq!(
+        engine.eval_reader(src.as_bytes()).unwrap(),
+        Value::Number(3.into())
+    )
+}
+
+#[test]
+fn statements_across_buffer_boundaries() {
+    let mut engine = engine();
+    let src = "let x = {\n  let a = 1;\n  a + 1\n};\nlet l = (\n {1; 2});x + l";
+    // a tiny buffer splits the statements in many reads
+    let reader = BufReader::with_capacity(3, src.as_bytes());
+    assert_eq!(engine.eval_reader(reader).unwrap(), Value::Number(4.into()))
+}
+
+#[test]
+fn errors_report_the_line() {
+    let mut engine = engine();
+    let src = "let x = 1;\nx + 1;\n\nundefined_var;\nx";
+    match engine.eval_reader(src.as_bytes()) {
+        Err(EvalReaderError::Solve { line: 4, .. }) => (),
+        res => panic!("Expected an evaluation error on line 4, got {res:?}"),
+    }
+
+    let src = "1;\n2 +;\n3";
+    match engine.eval_reader(src.as_bytes()) {
+        Err(EvalReaderError::Parse { line: 2, source }) => {
+            assert_eq!(source.location.line, 2)
+        }
+        res => panic!("Expected a syntax error on line 2, got {res:?}"),
+    }
+}
+
+#[test]
+fn newline_separated_statements() {
+    let mut engine = engine();
+    let src = "let x = 1\nlet y = [\n  x,\n  2\n]\n\nundefined_var\nx";
+    match engine.eval_reader(src.as_bytes()) {
+        Err(EvalReaderError::Solve { line: 7, .. }) => (),
+        res => panic!("Expected an evaluation error on line 7, got {res:?}"),
+    }
+}
+
+#[test]
+fn spans_are_positions_in_the_whole_source() {
+    let mut engine = engine();
+    let src = "let m = <|a: 1|>;
+let n = 2; m.b";
+    match engine.eval_reader(src.as_bytes()) {
+        Err(EvalReaderError::Solve { source, .. }) => {
+            assert_eq!(source.span(), Some(29..32));
+            assert_eq!(source.to_string(), "Error at 2:12 in the source")
+        }
+        res => panic!("Expected an evaluation error, got {res:?}"),
+    }
+}
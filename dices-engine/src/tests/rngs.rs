@@ -0,0 +1,210 @@
+use dices_ast::{intrisics::NoInjectedIntrisics, Value};
+
+use super::{engine, eval};
+use crate::{rngs::NamedRng, Engine, IntrisicError, SolveError};
+
+/// Roll some dices with the named RNG, seeded with `seed`
+fn rolls(name: &str, seed: u64, cmd: &str) -> Value {
+    let mut engine: Engine<_, NoInjectedIntrisics> =
+        Engine::new_with_rng(NamedRng::seed_from_u64_named(name, seed).unwrap());
+    let exprs = dices_ast::parse_file(cmd).expect("The test command should be parseable");
+    engine.eval_multiple(&exprs).unwrap()
+}
+
+#[test]
+fn every_rng_is_deterministic() {
+    for name in NamedRng::NAMES {
+        assert_eq!(
+            rolls(name, 42, "10d100"),
+            rolls(name, 42, "10d100"),
+            "`{name}` gave different rolls with the same seed"
+        )
+    }
+}
+
+#[test]
+fn names_roundtrip() {
+    for name in NamedRng::NAMES {
+        assert_eq!(
+            NamedRng::seed_from_u64_named(name, 0).unwrap().name(),
+            *name
+        )
+    }
+}
+
+#[test]
+fn unknown_rng() {
+    assert!(matches!(
+        NamedRng::seed_from_u64_named("dice_bag", 0),
+        Err(err) if err.to_string().contains("`dice_bag`")
+    ))
+}
+
+#[test]
+fn seeding_keeps_the_algorithm() {
+    for name in NamedRng::NAMES {
+        assert_eq!(
+            rolls(name, 0, "std.rng.seed(42); 10d100"),
+            rolls(name, 1, "std.rng.seed(42); 10d100"),
+            "Seeding `{name}` from inside the engine is not deterministic"
+        );
+    }
+    assert_ne!(
+        rolls("chacha8", 0, "std.rng.seed(42); 10d100"),
+        rolls("xoshiro256++", 0, "std.rng.seed(42); 10d100"),
+        "Seeding from inside the engine changed the algorithm"
+    )
+}
+
+#[test]
+fn restoring_keeps_the_algorithm() {
+    let Value::List(rolls) = rolls(
+        "chacha20",
+        7,
+        "let state = std.rng.save(); let a = 10d100; std.rng.restore(state); [a, 10d100]",
+    ) else {
+        panic!("The result should be a list")
+    };
+    assert_eq!(rolls[0], rolls[1])
+}
+
+#[cfg(not(feature = "eval_str"))]
+#[test]
+fn replay_needs_eval_str() {
+    let token = eval(&mut engine(), "std.rng.replay_token(|| 10d100)").unwrap();
+    assert!(matches!(
+        eval(&mut engine(), &format!("std.rng.replay({token})")),
+        Err(SolveError::IntrisicError(err)) if matches!(err.0, IntrisicError::ReplayUnavailable)
+    ))
+}
+
+#[cfg(feature = "eval_str")]
+#[test]
+fn replay_reproduces_the_rolls() {
+    let Value::List(results) = rolls(
+        "chacha20",
+        7,
+        "let f = || 10d100; \
+         let state = std.rng.save(); let rolled = f(); std.rng.restore(state); \
+         let token = std.rng.replay_token(f); \
+         [rolled, std.rng.replay(token), std.rng.replay(token)]",
+    ) else {
+        panic!("The result should be a list")
+    };
+    assert_eq!(results[0], results[1]);
+    assert_eq!(results[0], results[2])
+}
+
+#[cfg(feature = "eval_str")]
+#[test]
+fn replay_does_not_change_the_next_rolls() {
+    let expected = rolls("xoshiro256++", 3, "let a = 10d100; 10d100");
+    assert_eq!(
+        rolls(
+            "xoshiro256++",
+            3,
+            "let token = std.rng.replay_token(|| 10d100); std.rng.replay(token); 10d100"
+        ),
+        expected
+    )
+}
+
+#[cfg(feature = "eval_str")]
+/// Make a replay token, and change the source in it
+fn tampered_token(source: &str) -> Value {
+    let Value::String(token) = eval(&mut engine(), "std.rng.replay_token(|| 10d100)").unwrap()
+    else {
+        panic!("The token should be a string")
+    };
+    let mut token: serde_json::Value = serde_json::from_str(&token).unwrap();
+    assert_eq!(
+        token["source"], "|| 10d100",
+        "The token should contain the source"
+    );
+    token["source"] = source.into();
+    Value::String(token.to_string().into())
+}
+
+#[cfg(feature = "eval_str")]
+#[test]
+fn replay_rejects_sources_that_are_not_closures() {
+    for source in ["10d100", "let f = || 10d100", "|| 1; || 2"] {
+        let cmd = format!("std.rng.replay({})", tampered_token(source));
+        assert!(
+            matches!(
+                eval(&mut engine(), &cmd),
+                Err(SolveError::IntrisicError(err)) if matches!(err.0, IntrisicError::ReplayNotAClosure)
+            ),
+            "`{source}` was replayed"
+        )
+    }
+}
+
+#[cfg(feature = "eval_str")]
+#[test]
+fn replay_respects_the_denied_intrisics() {
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256PlusPlus;
+
+    use crate::EngineBuilder;
+
+    let token = tampered_token(
+        r#"|| std.conversions.from_json("{\"$type\":\"intrisic\",\"$intrisic\":\"to_json\"}")(1)"#,
+    );
+    let cmd = format!("std.rng.replay({token})");
+    assert!(eval(&mut engine(), &cmd).is_ok());
+    let mut engine = EngineBuilder::new()
+        .with_rng(Xoshiro256PlusPlus::seed_from_u64(42))
+        .deny_intrisic("to_json")
+        .unwrap()
+        .build();
+    assert!(matches!(
+        eval(&mut engine, &cmd),
+        Err(SolveError::IntrisicError(err)) if matches!(err.0, IntrisicError::CallFailed(_))
+    ))
+}
+
+#[test]
+fn batch_is_deterministic_per_seed() {
+    let expr = dices_ast::parse_file("let rolled = 10d100").unwrap();
+    let mut engine: Engine<_, NoInjectedIntrisics> =
+        Engine::new_with_rng(NamedRng::seed_from_u64_named("xoshiro256++", 0).unwrap());
+    let results = engine.eval_batch(&expr[0], &[1, 2, 1]).unwrap();
+    assert_eq!(results[0], results[2]);
+    assert_ne!(results[0], results[1]);
+    // the variables defined in the batch are dropped
+    assert!(engine
+        .eval(&dices_ast::parse_file("rolled").unwrap()[0])
+        .is_err())
+}
+
+#[test]
+fn batch_does_not_change_the_engine_rng() {
+    let expected = rolls("xoshiro256++", 0, "10d100");
+    let mut engine: Engine<_, NoInjectedIntrisics> =
+        Engine::new_with_rng(NamedRng::seed_from_u64_named("xoshiro256++", 0).unwrap());
+    engine
+        .eval_batch(&dices_ast::parse_file("10d100").unwrap()[0], &[1, 2, 3])
+        .unwrap();
+    assert_eq!(
+        engine
+            .eval(&dices_ast::parse_file("10d100").unwrap()[0])
+            .unwrap(),
+        expected
+    )
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn parallel_batch_matches_the_sequential_one() {
+    let expr = &dices_ast::parse_file("let a = roll(3); a ~ [sum(a)]").unwrap()[0];
+    let seeds: Vec<u64> = (0..64).collect();
+    let mut engine: Engine<_, NoInjectedIntrisics> =
+        Engine::new_with_rng(NamedRng::seed_from_u64_named("xoshiro256++", 0).unwrap());
+    engine
+        .eval(&dices_ast::parse_file("let roll = |n| d20 ^ n").unwrap()[0])
+        .unwrap();
+    let parallel = engine.eval_batch_par(expr, &seeds).unwrap();
+    let sequential = engine.eval_batch(expr, &seeds).unwrap();
+    assert_eq!(parallel, sequential)
+}
@@ -0,0 +1,138 @@
+use dices_ast::{
+    intrisics::Intrisic,
+    value::{Value, ValueMap},
+};
+use rand::SeedableRng;
+use rand_xoshiro::Xoshiro256PlusPlus;
+
+use super::eval;
+use crate::{EngineBuilder, IntrisicError, SolveError};
+
+#[test]
+fn denied_intrisic_is_unresolvable() {
+    let mut engine = EngineBuilder::new()
+        .with_rng(Xoshiro256PlusPlus::seed_from_u64(42))
+        .deny_intrisic("to_json")
+        .unwrap()
+        .build();
+    assert!(matches!(
+        eval(&mut engine, "to_json(1)"),
+        Err(SolveError::InvalidReference(_))
+    ));
+    assert!(eval(&mut engine, "std.intrisics.to_json").is_err());
+    assert!(eval(&mut engine, "std.conversions.to_json").is_err());
+    // the other intrisics are still there
+    assert_eq!(
+        eval(&mut engine, "std.conversions.from_json(\"1\")").unwrap(),
+        Value::Number(1.into())
+    )
+}
+
+#[test]
+fn allow_only_some_intrisics() {
+    let mut engine = EngineBuilder::new()
+        .with_rng(Xoshiro256PlusPlus::seed_from_u64(42))
+        .allow_only(["sum", "map"])
+        .unwrap()
+        .build();
+    assert_eq!(
+        eval(&mut engine, "map([1, 2], |x| sum(x, 1))").unwrap(),
+        eval(&mut engine, "[2, 3]").unwrap()
+    );
+    assert!(matches!(
+        eval(&mut engine, "join([1], [2])"),
+        Err(SolveError::InvalidReference(_))
+    ))
+}
+
+#[test]
+fn denied_intrisic_cannot_be_deserialized() {
+    let mut engine = EngineBuilder::new()
+        .with_rng(Xoshiro256PlusPlus::seed_from_u64(42))
+        .deny_intrisic("to_json")
+        .unwrap()
+        .build();
+    assert!(matches!(
+        eval(
+            &mut engine,
+            r#"std.conversions.from_json("{\"$type\":\"intrisic\",\"$intrisic\":\"to_json\"}")(1)"#
+        ),
+        Err(SolveError::IntrisicError(err)) if matches!(err.0, IntrisicError::Denied { called: Intrisic::ToJson })
+    ))
+}
+
+#[test]
+fn denied_intrisic_cannot_be_desugared_to() {
+    let mut engine = EngineBuilder::new()
+        .with_rng(Xoshiro256PlusPlus::seed_from_u64(42))
+        .allow_only(["sum"])
+        .unwrap()
+        .build();
+    assert!(matches!(
+        eval(&mut engine, "[x + 1 for x in [1, 2]]"),
+        Err(SolveError::IntrisicError(err)) if matches!(err.0, IntrisicError::Denied { called: Intrisic::Map })
+    ));
+    assert!(matches!(
+        eval(&mut engine, "[x for x in [1, 2] if x]"),
+        Err(SolveError::IntrisicError(err)) if matches!(err.0, IntrisicError::Denied { called: Intrisic::Filter })
+    ));
+    // `is` is solved by the engine itself, and does not need `matches`
+    assert_eq!(
+        eval(&mut engine, "1 is 1").unwrap(),
+        Value::Bool(true.into())
+    );
+}
+
+#[test]
+fn deny_unknown_intrisic() {
+    assert!(EngineBuilder::new()
+        .deny_intrisic("not_an_intrisic")
+        .is_err());
+    assert!(EngineBuilder::new()
+        .allow_only(["sum", "not_an_intrisic"])
+        .is_err())
+}
+
+#[test]
+fn custom_std_map() {
+    let std = ValueMap::from_iter([
+        (
+            "dice".into(),
+            ValueMap::from_iter([("total".into(), Value::Intrisic(Intrisic::Sum.into()))]).into(),
+        ),
+        (
+            "prelude".into(),
+            ValueMap::from_iter([("add".into(), Value::Intrisic(Intrisic::Sum.into()))]).into(),
+        ),
+    ]);
+    let mut engine = EngineBuilder::new()
+        .with_rng(Xoshiro256PlusPlus::seed_from_u64(42))
+        .with_std_map(std)
+        .build();
+    assert_eq!(
+        eval(&mut engine, "std.dice.total(1, 2)").unwrap(),
+        Value::Number(3.into())
+    );
+    assert_eq!(
+        eval(&mut engine, "add(1, 2)").unwrap(),
+        Value::Number(3.into())
+    );
+    // the default library is gone
+    assert!(matches!(
+        eval(&mut engine, "sum(1, 2)"),
+        Err(SolveError::InvalidReference(_))
+    ));
+}
+
+#[test]
+fn custom_std_map_without_prelude() {
+    let std = ValueMap::from_iter([("answer".into(), Value::Number(42.into()))]);
+    let mut engine = EngineBuilder::new()
+        .with_rng(Xoshiro256PlusPlus::seed_from_u64(42))
+        .with_std_map(std)
+        .build();
+    assert_eq!(
+        eval(&mut engine, "std.answer").unwrap(),
+        Value::Number(42.into())
+    );
+}
@@ -0,0 +1,28 @@
+use dices_ast::{expression::ExpressionCall, Value};
+
+use super::{engine, eval};
+
+#[test]
+fn capturing_closure_roundtrip() {
+    let closure = eval(
+        &mut engine(),
+        "let a = 3; let s = sum; let f = |x| s(x, a, 1d1); f",
+    )
+    .unwrap();
+    assert!(matches!(closure, Value::Closure(_)));
+
+    let encoded = bincode::encode_to_vec(&closure, bincode::config::standard()).unwrap();
+    let (deserialized, _): (Value, _) =
+        bincode::decode_from_slice(&encoded, bincode::config::standard()).unwrap();
+    assert_eq!(deserialized, closure);
+
+    // the closure can be called in a new engine, where its captures are not defined
+    let call = ExpressionCall::new(
+        deserialized.into(),
+        Box::new([Value::Number(4.into()).into()]),
+    );
+    assert_eq!(
+        engine().eval(&call.into()).unwrap(),
+        Value::Number(8.into())
+    )
+}
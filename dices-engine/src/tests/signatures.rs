@@ -0,0 +1,42 @@
+use rand::SeedableRng;
+use rand_xoshiro::Xoshiro256PlusPlus;
+
+use super::engine;
+use crate::{EngineBuilder, IntrisicSignature};
+
+#[test]
+fn print_is_variadic_and_effectful() {
+    let signatures = engine().intrisic_signatures();
+    let print = signatures.iter().find(|s| s.name == "print").unwrap();
+    assert!(print.is_variadic());
+    assert!(print.effectful)
+}
+
+#[test]
+fn pure_intrisic_has_fixed_arity() {
+    let signatures = engine().intrisic_signatures();
+    assert!(signatures.contains(&IntrisicSignature {
+        name: "pow",
+        min_params: 2,
+        max_params: Some(2),
+        effectful: false,
+    }))
+}
+
+#[test]
+fn denied_intrisics_are_not_listed() {
+    let engine = EngineBuilder::new()
+        .with_rng(Xoshiro256PlusPlus::seed_from_u64(42))
+        .deny_intrisic("print")
+        .unwrap()
+        .build();
+    let signatures = engine.intrisic_signatures();
+    assert!(signatures.iter().all(|s| s.name != "print"));
+    assert!(signatures.iter().any(|s| s.name == "pow"));
+
+    let engine = EngineBuilder::new()
+        .with_rng(Xoshiro256PlusPlus::seed_from_u64(42))
+        .without_std()
+        .build();
+    assert!(engine.intrisic_signatures().is_empty())
+}
@@ -0,0 +1,48 @@
+use super::{engine, eval};
+
+/// The undefined names in a command
+fn undefined(cmd: &str) -> Vec<String> {
+    let mut engine = engine();
+    eval(&mut engine, "let STR = 14").unwrap();
+    let exprs = dices_ast::parse_file(cmd).unwrap();
+    assert_eq!(
+        exprs.len(),
+        1,
+        "The test command should be a single expression"
+    );
+    engine
+        .check_refs(&exprs[0])
+        .unwrap()
+        .into_iter()
+        .map(|name| name.to_string())
+        .collect()
+}
+
+#[test]
+fn typo_is_flagged() {
+    assert_eq!(undefined("d20 + STrength"), ["STrength"])
+}
+
+#[test]
+fn context_and_prelude_are_accepted() {
+    assert_eq!(undefined("print(d20 + STR)"), Vec::<String>::new());
+    assert_eq!(undefined("std.math.pow(STR, 2)"), Vec::<String>::new())
+}
+
+#[test]
+fn own_definitions_are_accepted() {
+    assert_eq!(
+        undefined("{ let DEX = 12; DEX + STR }"),
+        Vec::<String>::new()
+    );
+    // but not if used before being defined
+    assert_eq!(undefined("{ let x = DEX; let DEX = 12 }"), ["DEX"]);
+    // or if they were not defined with `let`
+    assert_eq!(undefined("WIS = 3"), ["WIS"])
+}
+
+#[test]
+fn closure_params_and_captures() {
+    assert_eq!(undefined("|x| x + STR"), Vec::<String>::new());
+    assert_eq!(undefined("|x| x + CON + y"), ["CON", "y"])
+}
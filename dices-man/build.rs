@@ -20,13 +20,22 @@ struct ManPage {
     title: String,
     /// The content of the page
     content: String,
+    /// Additional keywords to find the page
+    tags: Vec<String>,
+    /// A short summary, if given in the front matter
+    summary: Option<String>,
 }
 impl ToTokens for ManPage {
     fn to_tokens(&self, tokens: &mut TokenStream) {
         let title = &*self.title;
         let content = &*self.content;
+        let tags = &self.tags;
+        let summary = match &self.summary {
+            Some(summary) => quote!(Some(#summary)),
+            None => quote!(None),
+        };
 
-        quote!(ManPage::new(#title, #content)).to_tokens(tokens)
+        quote!(ManPage::new(#title, #content, &[#(#tags),*], #summary)).to_tokens(tokens)
     }
 }
 
@@ -129,13 +138,23 @@ fn read_item(path: &Path) -> Result<ManItem> {
 #[derive(Deserialize, Default)]
 struct FrontMatter {
     title: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    summary: Option<String>,
 }
 
 fn read_page(path: &Path, content: String) -> Result<ManPage> {
     println!("cargo::rerun-if-changed={}", path.display());
 
     // read the file content
-    let (FrontMatter { title }, content) = regex_captures!(
+    let (
+        FrontMatter {
+            title,
+            tags,
+            summary,
+        },
+        content,
+    ) = regex_captures!(
         r"\A\s*---((?:.|\n)*)---\s*$(?:\r\n|\n)?((?:.|\n)*)\z"m,
         &content
     )
@@ -152,7 +171,12 @@ fn read_page(path: &Path, content: String) -> Result<ManPage> {
             .into_owned()
     });
     let content = content.to_owned();
-    Ok(ManPage { title, content })
+    Ok(ManPage {
+        title,
+        content,
+        tags,
+        summary,
+    })
 }
 
 #[derive(Deserialize)]
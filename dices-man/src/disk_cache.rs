@@ -0,0 +1,92 @@
+//! Cache of the rendered examples on disk
+//!
+//! Rendering a page runs all its examples, and the in memory cache is lost when the process
+//! exits. The disk cache keeps the rendered examples between runs, in a file named after an hash
+//! of the example and of the render options. Each version of the crate uses its own subdirectory,
+//! so an update never reads the renders of the previous one.
+
+use std::{
+    fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        OnceLock,
+    },
+};
+
+use crate::{example::CodeExample, render_example_timed, RenderOptions};
+
+/// A directory where the rendered examples are stored
+#[derive(Debug)]
+pub struct DiskCache {
+    /// The directory of the current version
+    dir: PathBuf,
+    /// How many examples were rendered, instead of being read from the disk
+    renders: AtomicUsize,
+}
+
+impl DiskCache {
+    /// Use `dir` as a cache
+    ///
+    /// The directory is created when the first example is stored.
+    pub fn new(dir: impl AsRef<Path>) -> Self {
+        Self {
+            dir: dir.as_ref().join(env!("CARGO_PKG_VERSION")),
+            renders: AtomicUsize::new(0),
+        }
+    }
+
+    /// How many examples were rendered, instead of being read from the disk
+    pub fn renders(&self) -> usize {
+        self.renders.load(Ordering::Relaxed)
+    }
+
+    /// Render an example, or read it from the disk if it was already rendered
+    ///
+    /// The cache is only an optimization, so failing to read or write it is not an error. The
+    /// examples that ran out of time are not stored, as they might end on a faster run.
+    pub(crate) fn render_example(
+        &self,
+        code: &CodeExample,
+        seed: Option<u64>,
+        options: &RenderOptions,
+    ) -> String {
+        let mut hasher = DefaultHasher::new();
+        for piece in code.iter() {
+            piece.cmd.hash(&mut hasher);
+        }
+        seed.hash(&mut hasher);
+        options.hash(&mut hasher);
+        let path = self.dir.join(format!("{:016x}", hasher.finish()));
+
+        if let Ok(rendered) = fs::read_to_string(&path) {
+            return rendered;
+        }
+        self.renders.fetch_add(1, Ordering::Relaxed);
+        let (rendered, timed_out) = render_example_timed(code, seed, options);
+        if timed_out {
+            return rendered;
+        }
+        // write in a temporary file first, so a partial write is never read
+        let tmp = path.with_extension(format!("{}.tmp", std::process::id()));
+        let _ = fs::create_dir_all(&self.dir)
+            .and_then(|()| fs::write(&tmp, &rendered))
+            .and_then(|()| fs::rename(&tmp, &path));
+        rendered
+    }
+}
+
+static DISK_CACHE: OnceLock<DiskCache> = OnceLock::new();
+
+/// Store the rendered examples in `cache`, for all the pages rendered from now on
+///
+/// The cache can be set only once, if one was already set `cache` is given back.
+pub fn set_disk_cache(cache: DiskCache) -> Result<(), DiskCache> {
+    DISK_CACHE.set(cache)
+}
+
+/// The cache set with [`set_disk_cache`], if any
+pub fn disk_cache() -> Option<&'static DiskCache> {
+    DISK_CACHE.get()
+}
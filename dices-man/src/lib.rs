@@ -9,28 +9,36 @@
 use std::{
     borrow::Cow,
     collections::HashMap,
-    error::Report,
+    error::{Error, Report},
+    fmt::Display,
     hash::{DefaultHasher, Hash, Hasher},
     ops::Deref,
     sync::{Mutex, MutexGuard, OnceLock},
+    time::Duration,
 };
 
 use dices_ast::{
     intrisics::NoInjectedIntrisics,
     value::{Value, ValueNull},
 };
-use dices_engine::Engine;
+use dices_engine::{Engine, SolveError};
 use example::{CodeExample, CodeExampleCommand, CodeExamplePiece};
 use itertools::Itertools;
 use markdown::{
-    mdast::{self, Code, Node},
+    mdast::{self, Code, Node, Paragraph},
     to_mdast, ParseOptions,
 };
 use pretty::DocAllocator;
 use rand::SeedableRng;
 use rand_xoshiro::Xoshiro256PlusPlus;
 
+pub mod disk_cache;
 pub mod example;
+pub mod stubs;
+pub mod text_search;
+
+pub use disk_cache::{set_disk_cache, DiskCache};
+pub use text_search::search_text;
 
 /// Options to render the examples in the manual pages
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -43,6 +51,8 @@ pub struct RenderOptions {
     pub seed: u64,
     /// Width for the rendering
     pub width: usize,
+    /// How long a single command of an example can run, if limited
+    pub timeout: Option<Duration>,
 }
 impl Default for RenderOptions {
     fn default() -> Self {
@@ -51,10 +61,16 @@ impl Default for RenderOptions {
             prompt_cont: Cow::Borrowed("..."),
             seed: 0,
             width: 128,
+            timeout: Some(DEFAULT_EXAMPLE_TIMEOUT),
         }
     }
 }
 
+/// Default time limit for a single command of an example
+///
+/// The examples in the manual are quick, so this is only hit by a broken one.
+pub const DEFAULT_EXAMPLE_TIMEOUT: Duration = Duration::from_secs(5);
+
 /// Parse options used to parse the manual pages markdown
 pub const fn man_parse_options() -> ParseOptions {
     mdast2minimad::md_parse_options()
@@ -72,18 +88,71 @@ pub struct ManPage {
     pub name: &'static str,
     /// The content of the page
     pub content: &'static str,
+    /// Additional keywords to find the page, from the `tags` in the front matter
+    pub tags: &'static [&'static str],
+    /// The summary from the front matter, if given
+    summary: Option<&'static str>,
     /// The markdown ast of the page, if parsed
     ast: OnceLock<Box<AstCache>>,
 }
 impl ManPage {
-    const fn new(name: &'static str, content: &'static str) -> Self {
+    const fn new(
+        name: &'static str,
+        content: &'static str,
+        tags: &'static [&'static str],
+        summary: Option<&'static str>,
+    ) -> Self {
         Self {
             name,
             content,
+            tags,
+            summary,
             ast: OnceLock::new(),
         }
     }
 
+    /// A short summary of the page, without rendering it
+    ///
+    /// This is the `summary` in the front matter if present, or the first paragraph of the page.
+    pub fn summary(&self) -> Option<&'static str> {
+        self.summary.or_else(|| {
+            let mut nodes = vec![self.source()];
+            while let Some(node) = nodes.pop() {
+                match node {
+                    Node::Paragraph(Paragraph {
+                        position: Some(position),
+                        ..
+                    }) => {
+                        return Some(
+                            self.content[position.start.offset..position.end.offset].trim(),
+                        )
+                    }
+                    // headings and code are not part of the summary
+                    Node::Heading(_) | Node::Code(_) => (),
+                    _ => nodes.extend(node.children().into_iter().flatten().rev()),
+                }
+            }
+            None
+        })
+    }
+
+    /// The links to topics of the manual that [`search`] cannot find
+    pub fn broken_links(&self) -> Vec<&str> {
+        let mut broken = vec![];
+        let mut nodes = vec![self.source()];
+        while let Some(node) = nodes.pop() {
+            if let Node::Link(mdast::Link { url, .. }) = node {
+                if let Some(topic) = url.trim().strip_prefix("man:") {
+                    if search(topic).is_none() {
+                        broken.push(topic)
+                    }
+                }
+            }
+            nodes.extend(node.children().into_iter().flatten().rev())
+        }
+        broken
+    }
+
     fn ast_cache(&self) -> &AstCache {
         self.ast.get_or_init(|| {
             Box::new(AstCache {
@@ -125,7 +194,7 @@ fn render_examples(mut ast: Node, options: &RenderOptions) -> Node {
             value,
             position: _,
             lang,
-            meta: _meta,
+            meta,
         }) = node
         else {
             // recover all the childrens
@@ -140,79 +209,175 @@ fn render_examples(mut ast: Node, options: &RenderOptions) -> Node {
         let code: CodeExample = value.parse().expect(
             "The examples in the manual should be all well formatted, thanks to `dices-mantest`",
         );
-        // initialize an engine, deterministic with regard of the seed and the code
-        let mut engine: Engine<Xoshiro256PlusPlus, NoInjectedIntrisics> =
-            Engine::new_with_rng(SeedableRng::seed_from_u64({
-                let mut hasher = DefaultHasher::new();
-                options.seed.hash(&mut hasher);
-                code.hash(&mut hasher);
-                hasher.finish()
-            }));
-        // run all commands and concatenate the results
-        let doc_arena = pretty::Arena::<()>::new();
-        let res_arena = typed_arena::Arena::with_capacity(code.len());
-        let doc = doc_arena.intersperse(
-            (&*code).into_iter().filter_map(
-                |CodeExamplePiece {
-                     cmd:
-                         CodeExampleCommand {
-                             ignore,
-                             command: box command,
-                             src,
-                         },
-                     res: _,
-                 }| {
-                    let res = engine.eval_multiple(command);
-                    if *ignore {
-                        // only assert that the result is ok
-                        if let Err(err) = res {
-                            panic!("An example failed with {err}")
-                        }
-                        None
-                    } else {
-                        // print the command
-                        let command =
-                            doc_arena.intersperse(
-                                src.lines().with_position().map(|(pos, line)| {
-                                    doc_arena
-                                        .text(match pos {
-                                            itertools::Position::First
-                                            | itertools::Position::Only => &*options.prompt,
-                                            itertools::Position::Middle
-                                            | itertools::Position::Last => &*options.prompt_cont,
-                                        })
-                                        .append(line)
-                                }),
-                                doc_arena.hardline(),
-                            );
-                        // move res into the arena
-                        let res = &*res_arena.alloc(res);
-                        // print the result or the error
-                        let command_and_res = match res {
-                            Ok(Value::Null(ValueNull)) => command,
-                            Ok(res) => command.append(doc_arena.hardline()).append(res),
-                            Err(err) => {
-                                let report = Report::new(err).pretty(true);
-                                command
-                                    .append(doc_arena.hardline())
-                                    .append(report.to_string())
-                            }
-                        };
-
-                        Some(command_and_res)
-                    }
-                },
-            ),
-            doc_arena.hardline(),
-        );
-        // print the result
-        value.clear();
-        doc.render_fmt(options.width, value)
-            .expect("Rendering should be infallible")
+        let seed = example_seed(meta.as_deref());
+        *value = match disk_cache::disk_cache() {
+            Some(cache) => cache.render_example(&code, seed, options),
+            None => render_example(&code, seed, options),
+        }
     }
     ast
 }
 
+/// Read the seed given with `seed=` in the meta of a code block
+///
+/// An example with a seed always gives the same rolls, whatever the seed in the render options.
+pub fn example_seed(meta: Option<&str>) -> Option<u64> {
+    meta?
+        .split_whitespace()
+        .find_map(|m| m.strip_prefix("seed="))
+        .map(|seed| {
+            seed.parse()
+                .expect("The seed of an example should be a number")
+        })
+}
+
+/// Render a single example, running its commands
+///
+/// If the example has no seed of its own, the engine is seeded with both the seed in the options
+/// and the commands of the example, so the rendering is deterministic. The written results do not
+/// change the seed, so an example can be written down with its exact output.
+fn render_example(code: &CodeExample, seed: Option<u64>, options: &RenderOptions) -> String {
+    render_example_timed(code, seed, options).0
+}
+
+/// Render a single example, and report if any command ran out of time
+///
+/// The commands that run out of time are rendered with the error, even if ignored.
+fn render_example_timed(
+    code: &CodeExample,
+    seed: Option<u64>,
+    options: &RenderOptions,
+) -> (String, bool) {
+    // initialize an engine, deterministic with regard of the seed and the code
+    let mut engine: Engine<Xoshiro256PlusPlus, NoInjectedIntrisics> =
+        Engine::new_with_rng(SeedableRng::seed_from_u64(seed.unwrap_or_else(|| {
+            let mut hasher = DefaultHasher::new();
+            options.seed.hash(&mut hasher);
+            for piece in code.iter() {
+                piece.cmd.hash(&mut hasher);
+            }
+            hasher.finish()
+        })));
+    engine.set_timeout(options.timeout);
+    let mut timed_out = false;
+    // run all commands and concatenate the results
+    let doc_arena = pretty::Arena::<()>::new();
+    let res_arena = typed_arena::Arena::with_capacity(code.len());
+    let doc = doc_arena.intersperse(
+        code.iter().filter_map(
+            |CodeExamplePiece {
+                 cmd:
+                     CodeExampleCommand {
+                         ignore,
+                         command: box command,
+                         src,
+                     },
+                 res: _,
+             }| {
+                let res = engine.eval_multiple(command);
+                let res_timed_out = matches!(res, Err(SolveError::TimedOut(_)));
+                timed_out |= res_timed_out;
+                // the commands that ran out of time are shown even if ignored
+                if *ignore && !res_timed_out {
+                    // only assert that the result is ok
+                    if let Err(err) = res {
+                        panic!("An example failed with {err}")
+                    }
+                    None
+                } else {
+                    // print the command
+                    let command = doc_arena.intersperse(
+                        src.lines().with_position().map(|(pos, line)| {
+                            doc_arena
+                                .text(match pos {
+                                    itertools::Position::First | itertools::Position::Only => {
+                                        &*options.prompt
+                                    }
+                                    itertools::Position::Middle | itertools::Position::Last => {
+                                        &*options.prompt_cont
+                                    }
+                                })
+                                .append(line)
+                        }),
+                        doc_arena.hardline(),
+                    );
+                    // move res into the arena
+                    let res = &*res_arena.alloc(res);
+                    // print the result or the error
+                    let command_and_res = match res {
+                        Ok(Value::Null(ValueNull)) => command,
+                        Ok(res) => command.append(doc_arena.hardline()).append(res),
+                        Err(err) => {
+                            let report = Report::new(err).pretty(true);
+                            command
+                                .append(doc_arena.hardline())
+                                .append(report.to_string())
+                        }
+                    };
+
+                    Some(command_and_res)
+                }
+            },
+        ),
+        doc_arena.hardline(),
+    );
+    // print the result
+    let mut rendered = String::new();
+    doc.render_fmt(options.width, &mut rendered)
+        .expect("Rendering should be infallible");
+    (rendered, timed_out)
+}
+
+/// The tag marking the examples written with their exact output
+pub const EXPECTED_OUTPUT_TAG: &str = "mantest:expected";
+
+/// An example that does not render as written
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpectedOutputMismatch {
+    /// The output written in the example
+    pub expected: String,
+    /// The output given by rendering the example
+    pub rendered: String,
+}
+impl Display for ExpectedOutputMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "The example does not give the expected output.\nExpected:\n{}\nRendered:\n{}",
+            self.expected, self.rendered
+        )
+    }
+}
+impl Error for ExpectedOutputMismatch {}
+
+/// Check that an example renders exactly as it is written
+///
+/// The ignored commands and results (the lines starting with `#`) are not rendered, so they are
+/// skipped. Whitespace at the end of the lines is not significant.
+pub fn check_expected_output(
+    example: &str,
+    seed: Option<u64>,
+    options: &RenderOptions,
+) -> Result<(), ExpectedOutputMismatch> {
+    fn normalize<'s>(lines: impl Iterator<Item = &'s str>) -> String {
+        lines
+            .map(str::trim_end)
+            .filter(|l| !l.is_empty())
+            .join("\n")
+    }
+
+    let code: CodeExample = example
+        .parse()
+        .expect("The examples should be well formatted");
+    let expected = normalize(example.lines().filter(|l| !l.trim_start().starts_with('#')));
+    let rendered = normalize(render_example(&code, seed, options).lines());
+    if expected == rendered {
+        Ok(())
+    } else {
+        Err(ExpectedOutputMismatch { expected, rendered })
+    }
+}
+
 /// A subdirectory of the manual
 pub struct ManDir {
     /// The name of the subdirectory
@@ -414,15 +579,94 @@ pub fn search(topic: &str) -> Option<ManTopicContent> {
         ManItem::Dir(dir) => ManTopicContent::Index(dir),
     })
 }
+/// A short summary of a topic, if it is a page
+pub fn summary(topic: &str) -> Option<&'static str> {
+    match search(topic)? {
+        ManTopicContent::Page(page) => page.summary(),
+        ManTopicContent::Index(_) => None,
+    }
+}
 pub fn index() -> ManTopicContent {
     search("index").unwrap()
 }
 
+/// A link in the manual to a topic that does not exist
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrokenLink {
+    /// The topic of the page containing the link
+    pub page: String,
+    /// The topic linked
+    pub target: String,
+}
+impl Display for BrokenLink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "The page {} links to {}, that does not exist",
+            self.page, self.target
+        )
+    }
+}
+
+/// Find all the links in the manual to topics that do not exist
+pub fn broken_links() -> Vec<BrokenLink> {
+    all_topics()
+        .into_iter()
+        .filter_map(|topic| match search(&topic)? {
+            ManTopicContent::Page(page) => Some((topic, page)),
+            ManTopicContent::Index(_) => None,
+        })
+        .flat_map(|(topic, page)| {
+            page.broken_links()
+                .into_iter()
+                .map(move |target| BrokenLink {
+                    page: topic.clone(),
+                    target: target.to_owned(),
+                })
+        })
+        .collect()
+}
+
+/// All the topics of the manual, as accepted by [`search`]
+///
+/// The topics are listed depth first, in the order of the indices, so a directory comes right
+/// before its content.
+pub fn all_topics() -> Vec<String> {
+    fn walk(path: &str, dir: &ManDir, topics: &mut Vec<String>) {
+        for (&key, &item) in dir.content.entries() {
+            let topic = if path.is_empty() {
+                key.to_owned()
+            } else {
+                format!("{path}/{key}")
+            };
+            topics.push(topic.clone());
+            if let ManItem::Dir(dir) = item {
+                walk(&topic, dir, topics)
+            }
+        }
+    }
+
+    let mut topics = vec![];
+    walk("", &MANUAL, &mut topics);
+    topics
+}
+
 pub static MANUAL: ManDir = include!(env!("MANUAL_RS"));
 
 #[cfg(test)]
 mod tests;
 
+/// Check that all the links in the manual lead to existing topics
+#[cfg(any(feature = "test_std_handle", test))]
+pub fn manual_links_are_valid() {
+    let broken = broken_links();
+    assert!(
+        broken.is_empty(),
+        "The manual contains broken links:\n{}",
+        broken.iter().join("\n")
+    )
+}
+
 /// Check if the std library is fully documented
 #[cfg(any(feature = "test_std_handle", test))]
 pub fn std_library_is_represented<InjectedIntrisic: dices_ast::intrisics::InjectedIntr>() {
@@ -440,7 +684,14 @@ pub fn std_library_is_represented<InjectedIntrisic: dices_ast::intrisics::Inject
             let path = path.clone() + "/" + &*name;
             // check it is documented
             let Some(topic) = search(&*path) else {
-                panic!("The topic {path} is missing");
+                let stub = stubs::std_stubs::<InjectedIntrisic>()
+                    .into_iter()
+                    .find(|stub| stub.topic == path)
+                    .expect("Every entry of the std library has a stub");
+                panic!(
+                    "The topic {path} is missing. This stub can be used as a start:\n\n{}",
+                    stub.to_markdown()
+                );
             };
             // do not recurse if a page is expaining the whole map
             if topic.is_page() {
@@ -0,0 +1,107 @@
+//! Reference stubs for the standard library, generated from the code
+//!
+//! Every entry of `std` needs a manual page. The stubs give a starting point for the missing
+//! ones, with the information that can be read from the library itself.
+
+use std::{fmt::Write, ops::RangeInclusive};
+
+use dices_ast::{intrisics::InjectedIntr, value::Value};
+
+/// A reference stub for an entry of the standard library
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StdStub {
+    /// The topic the stub is for, like `std/math/pow`
+    pub topic: String,
+    /// What the entry is
+    pub kind: StubKind,
+}
+
+/// The kind of entry a stub is for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StubKind {
+    /// An intrisic, with the number of params it accepts (`None` if it accepts any number)
+    Intrisic {
+        arity: Option<RangeInclusive<usize>>,
+    },
+    /// A module, with the names of its entries
+    Module { entries: Vec<String> },
+    /// Any other value, with the name of its type
+    Value { type_name: &'static str },
+}
+
+impl StdStub {
+    /// The name of the entry
+    pub fn name(&self) -> &str {
+        self.topic.rsplit('/').next().unwrap()
+    }
+
+    /// Render the stub as a manual page, in the same format of the written ones
+    pub fn to_markdown(&self) -> String {
+        let name = self.name();
+        let title = match &self.kind {
+            StubKind::Intrisic { .. } => format!("The `{name}` intrisic"),
+            StubKind::Module { .. } => format!("The `{name}` module"),
+            StubKind::Value { .. } => format!("The `{name}` value"),
+        };
+        let mut page = format!("---\ntitle: \"{title}\"\n---\n# {title}\n\n");
+        match &self.kind {
+            StubKind::Intrisic { arity } => {
+                let params = match arity {
+                    None => "any number of params".to_owned(),
+                    Some(arity) if arity.start() == arity.end() => match arity.start() {
+                        0 => "no params".to_owned(),
+                        1 => "a single param".to_owned(),
+                        n => format!("{n} params"),
+                    },
+                    Some(arity) => format!("from {} to {} params", arity.start(), arity.end()),
+                };
+                writeln!(page, "`{name}` takes {params}.").unwrap();
+            }
+            StubKind::Module { entries } => {
+                writeln!(page, "The `{name}` module contains:").unwrap();
+                for entry in entries {
+                    writeln!(page, "- [`{entry}`](man:{}/{entry})", self.topic).unwrap();
+                }
+            }
+            StubKind::Value { type_name } => writeln!(page, "`{name}` is a {type_name}.").unwrap(),
+        }
+        page.push_str("\nThis page is a stub, and still needs a description.");
+        page
+    }
+}
+
+/// Generate a stub for every entry of the standard library
+pub fn std_stubs<InjectedIntrisic: InjectedIntr>() -> Vec<StdStub> {
+    let mut stubs = vec![];
+    let mut maps = vec![(
+        "std".to_owned(),
+        dices_engine::dices_std::<InjectedIntrisic>(),
+    )];
+    while let Some((topic, map)) = maps.pop() {
+        stubs.push(StdStub {
+            topic: topic.clone(),
+            kind: StubKind::Module {
+                entries: map.iter().map(|(k, _)| (***k).to_owned()).collect(),
+            },
+        });
+        for (name, value) in map {
+            let topic = topic.clone() + "/" + &*name;
+            match value {
+                Value::Map(map) => maps.push((topic, map)),
+                Value::Intrisic(intrisic) => stubs.push(StdStub {
+                    topic,
+                    kind: StubKind::Intrisic {
+                        arity: dices_engine::arity(&intrisic.0),
+                    },
+                }),
+                value => stubs.push(StdStub {
+                    topic,
+                    kind: StubKind::Value {
+                        type_name: value.type_name(),
+                    },
+                }),
+            }
+        }
+    }
+    stubs
+}
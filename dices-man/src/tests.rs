@@ -1,9 +1,16 @@
 //! Test checking the well-formness of the manual
 
-use dices_ast::intrisics::NoInjectedIntrisics;
-use markdown::mdast::{Link, Node};
+use std::time::Duration;
 
-use crate::{search, std_library_is_represented, MANUAL};
+use dices_ast::{intrisics::NoInjectedIntrisics, value::Value};
+use markdown::mdast::Node;
+
+use crate::{
+    all_topics, check_expected_output, example::CodeExample, man_parse_options,
+    manual_links_are_valid, render_example, render_examples, search, search_text,
+    std_library_is_represented, stubs::std_stubs, summary, text_search::Score, DiskCache, ManPage,
+    RenderOptions,
+};
 
 /// The introduction must exist as it is shown when calling `help()`
 #[test]
@@ -20,42 +27,7 @@ fn index_exist() {
 /// Check that the links to manual page are all to existing manual pages
 #[test]
 fn manual_internal_links_are_not_dangling() {
-    let mut dirs = vec![&MANUAL];
-    while let Some(dir) = dirs.pop() {
-        'read_dir: for &item in dir.content.values() {
-            let page = match item {
-                crate::ManItem::Page(page) => page,
-                crate::ManItem::Index(_) => {
-                    continue 'read_dir;
-                }
-                crate::ManItem::Dir(dir) => {
-                    dirs.push(dir);
-                    continue 'read_dir;
-                }
-            };
-            // get the page ast
-            let page = page.source();
-            // visit the entire ast recursively
-            let mut nodes = vec![page];
-            'read_ast: while let Some(node) = nodes.pop() {
-                // add the childrens to visit recursively
-                nodes.extend(node.children().into_iter().flatten());
-                // search only links
-                let Node::Link(Link { url, .. }) = node else {
-                    continue 'read_ast;
-                };
-                let Some(topic) = url.trim().strip_prefix("man:") else {
-                    // not a link to the manual
-                    continue 'read_ast;
-                };
-                // check the topic exist
-                assert!(
-                    search(topic).is_some(),
-                    "The topic {topic} was referenced but does not exist"
-                )
-            }
-        }
-    }
+    manual_links_are_valid()
 }
 
 /// Check that the default std library is fully documented
@@ -63,3 +35,207 @@ fn manual_internal_links_are_not_dangling() {
 fn default_std_library_is_represented() {
     std_library_is_represented::<NoInjectedIntrisics>()
 }
+
+/// Check that a stub can be generated for every entry of the std library
+#[test]
+fn std_stubs_cover_the_std_library() {
+    let stubs = std_stubs::<NoInjectedIntrisics>();
+    let mut maps = vec![(
+        "std".to_owned(),
+        dices_engine::dices_std::<NoInjectedIntrisics>(),
+    )];
+    while let Some((path, map)) = maps.pop() {
+        for (name, value) in map {
+            let path = path.clone() + "/" + &*name;
+            assert!(
+                stubs.iter().any(|stub| stub.topic == path),
+                "The entry {path} has no stub"
+            );
+            if let Value::Map(map) = value {
+                maps.push((path, map))
+            }
+        }
+    }
+}
+
+#[test]
+fn intrisic_stubs_have_the_arity() {
+    let stubs = std_stubs::<NoInjectedIntrisics>();
+    let stub = |topic: &str| {
+        stubs
+            .iter()
+            .find(|stub| stub.topic == topic)
+            .unwrap()
+            .to_markdown()
+    };
+    assert!(stub("std/math/pow").contains("`pow` takes 2 params."));
+    assert!(stub("std/maps/get_path").contains("`get_path` takes from 2 to 3 params."));
+    assert!(stub("std/variadics/sum").contains("`sum` takes any number of params."));
+    assert!(stub("std/math").contains("[`gcd`](man:std/math/gcd)"))
+}
+
+#[test]
+fn expected_output_matches() {
+    assert_eq!(
+        check_expected_output(
+            ">>> 1 + 2\n3\n# >>> let x = 5\n>>> [x, x * 2]\n[5, 10]\n>>> null",
+            None,
+            &RenderOptions::default()
+        ),
+        Ok(())
+    )
+}
+
+#[test]
+fn expected_output_mismatches() {
+    let mismatch =
+        check_expected_output(">>> 1 + 2\n4", None, &RenderOptions::default()).unwrap_err();
+    assert_eq!(mismatch.expected, ">>> 1 + 2\n4");
+    assert_eq!(mismatch.rendered, ">>> 1 + 2\n3")
+}
+
+#[test]
+fn expected_output_is_deterministic() {
+    let rendered = check_expected_output(">>> 10d100\n", None, &RenderOptions::default())
+        .unwrap_err()
+        .rendered;
+    assert_eq!(
+        check_expected_output(&rendered, None, &RenderOptions::default()),
+        Ok(())
+    )
+}
+
+#[test]
+fn examples_with_a_seed() {
+    const PAGE: &str = "```dices seed=4\n>>> 5d20\n```\n\n```dices seed=9\n>>> 5d20\n```";
+    let render = |seed| {
+        let ast = markdown::to_mdast(PAGE, &man_parse_options()).unwrap();
+        let ast = render_examples(
+            ast,
+            &RenderOptions {
+                seed,
+                ..Default::default()
+            },
+        );
+        let mut blocks = vec![];
+        let mut nodes = vec![&ast];
+        while let Some(node) = nodes.pop() {
+            match node {
+                Node::Code(code) => blocks.push(code.value.clone()),
+                _ => nodes.extend(node.children().into_iter().flatten().rev()),
+            }
+        }
+        blocks
+    };
+    let blocks = render(0);
+    // the seed of the block wins over the one in the options
+    assert_eq!(render(42), blocks);
+    assert_eq!(
+        blocks,
+        [4, 9].map(|seed| {
+            let code: CodeExample = ">>> 5d20".parse().unwrap();
+            render_example(&code, Some(seed), &RenderOptions::default())
+        })
+    );
+    assert_ne!(blocks[0], blocks[1])
+}
+
+#[test]
+fn title_hits_outrank_body_hits() {
+    let words = ["pow".to_owned()];
+    let title_hit = Score::of(&words, "The `pow` intrisic", "Raise to a power", &[]);
+    let body_hit = Score::of(&words, "Math", "pow pow pow", &[]);
+    let tag_hit = Score::of(&words, "Math", "Numbers", &["pow"]);
+    assert!(title_hit > body_hit);
+    assert!(body_hit > tag_hit);
+    assert!(tag_hit.is_match());
+    assert!(!Score::of(&words, "Math", "Numbers", &[]).is_match())
+}
+
+#[test]
+fn search_text_ranks_the_manual() {
+    let hits = search_text("Pow");
+    assert_eq!(hits[0].topic, "std/math/pow");
+    assert!(hits.windows(2).all(|w| w[0].score >= w[1].score));
+    assert!(search_text("xyzzy").is_empty())
+}
+
+#[test]
+fn summary_is_the_first_paragraph() {
+    assert_eq!(
+        summary("std/math/gcd"),
+        Some(
+            "`gcd` computes the greatest common divisor of its two arguments. The sign of the \
+             arguments is ignored, so the result is never negative."
+        )
+    );
+    assert_eq!(summary("std/math"), None);
+    assert_eq!(summary("not/a/topic"), None)
+}
+
+#[test]
+fn second_render_hits_the_disk_cache() {
+    let dir = std::env::temp_dir().join(format!("dices-man-cache-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let code: CodeExample = ">>> d100 ^ 5".parse().unwrap();
+    let options = RenderOptions::default();
+
+    let cache = DiskCache::new(&dir);
+    let first = cache.render_example(&code, None, &options);
+    assert_eq!(cache.renders(), 1);
+    assert_eq!(cache.render_example(&code, None, &options), first);
+    assert_eq!(cache.renders(), 1);
+
+    // a new process would find the renders on the disk
+    let restarted = DiskCache::new(&dir);
+    assert_eq!(restarted.render_example(&code, None, &options), first);
+    assert_eq!(restarted.renders(), 0);
+    // but not with other options
+    restarted.render_example(&code, None, &RenderOptions { seed: 1, ..options });
+    assert_eq!(restarted.renders(), 1);
+
+    std::fs::remove_dir_all(&dir).unwrap()
+}
+
+#[test]
+fn slow_examples_are_stopped() {
+    let code: CodeExample = ">>> d6 ^ 1000000000".parse().unwrap();
+    let rendered = render_example(
+        &code,
+        None,
+        &RenderOptions {
+            timeout: Some(Duration::from_millis(50)),
+            ..Default::default()
+        },
+    );
+    assert!(
+        rendered.contains("The evaluation did not end in 50ms"),
+        "The example rendered as:\n{rendered}"
+    )
+}
+
+#[test]
+fn all_topics_lists_the_manual() {
+    let topics = all_topics();
+    for topic in ["index", "introduction", "std", "std/repl", "std/repl/help"] {
+        assert!(topics.iter().any(|t| t == topic), "{topic} is missing")
+    }
+    for topic in &topics {
+        assert!(search(topic).is_some(), "{topic} cannot be found")
+    }
+    // a directory comes before its content
+    let pos = |topic: &str| topics.iter().position(|t| t == topic).unwrap();
+    assert!(pos("std/repl") < pos("std/repl/help"));
+    assert_eq!(topics, all_topics())
+}
+
+#[test]
+fn dangling_links_are_found() {
+    let page = ManPage::new(
+        "Links",
+        "See [`pow`](man:std/math/pow) and [`nope`](man:std/math/nope).",
+        &[],
+        None,
+    );
+    assert_eq!(page.broken_links(), ["std/math/nope"])
+}
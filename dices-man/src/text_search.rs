@@ -0,0 +1,124 @@
+//! Full text search in the manual
+//!
+//! Pages are ranked by where the words of the query appear: a match in the title beats any match in
+//! the body, that beats a match in the tags. Between pages matching in the body, the one where the
+//! words are more frequent comes first.
+
+use std::{cmp::Ordering, sync::OnceLock};
+
+use crate::{ManItem, ManPage, MANUAL};
+
+/// A page found by [`search_text`]
+#[derive(Clone, Copy)]
+pub struct SearchHit {
+    /// The topic of the page, as accepted by [`search`](crate::search)
+    pub topic: &'static str,
+    /// The page found
+    pub page: &'static ManPage,
+    /// How well the page matches the query
+    pub score: Score,
+}
+
+/// How well a page matches a query
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Score {
+    /// Number of query words in the title
+    pub title: usize,
+    /// Frequency of the query words in the body
+    pub body: f64,
+    /// Number of query words in the tags
+    pub tags: usize,
+}
+impl Score {
+    /// Score a page against the words of a query
+    pub fn of(words: &[String], title: &str, content: &str, tags: &[&str]) -> Self {
+        let title: Vec<_> = tokens(title).collect();
+        let body: Vec<_> = tokens(content).collect();
+        let tags: Vec<_> = tags.iter().flat_map(|t| tokens(t)).collect();
+
+        let body_matches = body.iter().filter(|t| words.contains(t)).count();
+        Self {
+            title: words.iter().filter(|w| title.contains(w)).count(),
+            body: if body.is_empty() {
+                0.
+            } else {
+                body_matches as f64 / body.len() as f64
+            },
+            tags: words.iter().filter(|w| tags.contains(w)).count(),
+        }
+    }
+
+    /// If the page matched the query at all
+    pub fn is_match(&self) -> bool {
+        self.title > 0 || self.body > 0. || self.tags > 0
+    }
+}
+impl PartialEq for Score {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+impl Eq for Score {}
+impl PartialOrd for Score {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Score {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.title
+            .cmp(&other.title)
+            .then(self.body.total_cmp(&other.body))
+            .then(self.tags.cmp(&other.tags))
+    }
+}
+
+/// Split a text in lowercase words
+fn tokens(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|ch: char| !(ch.is_alphanumeric() || ch == '_'))
+        .filter(|t| !t.is_empty())
+        .map(str::to_lowercase)
+}
+
+/// All the pages of the manual, with their topic
+fn pages() -> &'static [(String, &'static ManPage)] {
+    static PAGES: OnceLock<Vec<(String, &'static ManPage)>> = OnceLock::new();
+    PAGES.get_or_init(|| {
+        let mut pages = vec![];
+        let mut dirs = vec![(String::new(), &MANUAL)];
+        while let Some((path, dir)) = dirs.pop() {
+            for (&key, &item) in dir.content.entries() {
+                let topic = if path.is_empty() {
+                    key.to_owned()
+                } else {
+                    format!("{path}/{key}")
+                };
+                match item {
+                    ManItem::Page(page) => pages.push((topic, page)),
+                    ManItem::Index(_) => (),
+                    ManItem::Dir(dir) => dirs.push((topic, dir)),
+                }
+            }
+        }
+        pages
+    })
+}
+
+/// Search the manual for the pages containing the words of the query
+///
+/// The pages are returned from the most relevant, and the ones not matching are left out.
+pub fn search_text(query: &str) -> Vec<SearchHit> {
+    let words: Vec<_> = tokens(query).collect();
+    let mut hits: Vec<_> = pages()
+        .iter()
+        .map(|(topic, page)| SearchHit {
+            topic,
+            page,
+            score: Score::of(&words, page.name, page.content, page.tags),
+        })
+        .filter(|hit| hit.score.is_match())
+        .collect();
+    // stable, so pages with the same score are always in the same order
+    hits.sort_by_key(|hit| std::cmp::Reverse(hit.score));
+    hits
+}
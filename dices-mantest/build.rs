@@ -99,6 +99,10 @@ impl ManTests<'_, '_, ManPage> {
             );
 
             let test = &*e.value;
+            let seed = match dices_man::example_seed(e.meta.as_deref()) {
+                Some(seed) => quote! { Some(#seed) },
+                None => quote! { None },
+            };
 
             // example plot
             Some(quote! {
@@ -110,7 +114,8 @@ impl ManTests<'_, '_, ManPage> {
                             #(
                                 #tags
                             ),*
-                        ]
+                        ],
+                        #seed
                     )
                 }
             })
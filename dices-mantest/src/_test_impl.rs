@@ -1,15 +1,19 @@
-use dices_man::example::CodeExample;
+use dices_man::{check_expected_output, example::CodeExample, RenderOptions};
 
 use dices_engine::Engine;
+use rand::SeedableRng;
 
 /// Main testing function
-pub(crate) fn test_inner(test: &str, _tags: &[&str]) {
+pub(crate) fn test_inner(test: &str, tags: &[&str], seed: Option<u64>) {
     // Parse the test
-    let test: CodeExample = test.parse().expect("The test should be parseable");
-    // Create the engine
-    let mut engine: Engine<rand_xoshiro::Xoshiro256PlusPlus, _> = Engine::new();
+    let code: CodeExample = test.parse().expect("The test should be parseable");
+    // Create the engine, with the seed of the example if it has one
+    let mut engine: Engine<rand_xoshiro::Xoshiro256PlusPlus, _> = match seed {
+        Some(seed) => Engine::new_with_rng(SeedableRng::seed_from_u64(seed)),
+        None => Engine::new(),
+    };
     // run the test
-    for (n, piece) in test.iter().enumerate() {
+    for (n, piece) in code.iter().enumerate() {
         let res = engine
             .eval_multiple(&piece.cmd.command)
             .expect("Error in the execution of the doctest!");
@@ -22,4 +26,10 @@ pub(crate) fn test_inner(test: &str, _tags: &[&str]) {
             )
         }
     }
+    // check the exact output, if it was written down
+    if tags.contains(&"expected") {
+        if let Err(mismatch) = check_expected_output(test, seed, &RenderOptions::default()) {
+            panic!("{mismatch}")
+        }
+    }
 }
@@ -3,8 +3,9 @@
 
 use std::{
     error::{Error, Report},
+    fs,
     hash::{DefaultHasher, Hash, Hasher},
-    io::{self, stdin, stdout},
+    io::{self, stdin, stdout, Write},
     path::PathBuf,
     rc::Rc,
 };
@@ -12,16 +13,21 @@ use std::{
 use chrono::Local;
 use clap::{Parser, ValueEnum};
 use derive_more::derive::{Debug, Display, Error, From};
-use dices_ast::value::{Value, ValueNull};
-use dices_engine::Engine;
+use dices_ast::{
+    expression::{Expression, ParseError},
+    value::{Value, ValueNull},
+};
+use dices_engine::{
+    rngs::{NamedRng, UnknownRng},
+    Engine,
+};
 use pretty::Pretty;
-use rand::SeedableRng;
-use rand_xoshiro::Xoshiro256PlusPlus;
 use reedline::{Prompt, PromptEditMode, PromptHistorySearchStatus, PromptViMode, Reedline, Signal};
-use repl_intrisics::{Quitted, REPLIntrisics};
+use repl_intrisics::REPLIntrisics;
 use serde::{Deserialize, Serialize};
 use termimad::{terminal_size, Alignment, MadSkin};
 
+mod pager;
 mod repl_intrisics;
 mod setup;
 
@@ -39,15 +45,23 @@ pub struct ReplCli {
     #[clap(long, short)]
     interactive: bool,
 
+    /// Only parse the command or script (or the standard input, if neither is given), reporting the
+    /// syntax errors without evaluating anything.
+    #[clap(long, conflicts_with = "interactive")]
+    check: bool,
+
     #[clap(
         short,
         long,
         num_args = ..,
-        trailing_var_arg = true,
         allow_hyphen_values = true
     )]
     /// Command to run. If missing, an interactive prompt is open
     run: Option<Vec<String>>,
+
+    /// Script to run, instead of a command. It can start with a shebang like `#!/usr/bin/env dices`
+    #[clap(conflicts_with = "run")]
+    script: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone, Copy, Display, ValueEnum, Serialize, Deserialize)]
@@ -74,11 +88,7 @@ pub enum Graphic {
 }
 impl Default for Graphic {
     fn default() -> Self {
-        if atty::is(atty::Stream::Stdout) {
-            Graphic::Fancy
-        } else {
-            Graphic::None
-        }
+        setup::detect_graphic(|key| std::env::var_os(key), atty::is(atty::Stream::Stdout))
     }
 }
 impl Graphic {
@@ -194,8 +204,12 @@ pub enum ReplFatalError {
     IO(io::Error),
     #[display("Error during execution")]
     Run(dices_engine::EvalStrError<REPLIntrisics>),
+    #[display("Syntax error")]
+    Syntax(ParseError),
     #[display("Error during extraction of the setup")]
     Setup(figment::Error),
+    #[display("Error in the choice of the random number generator")]
+    Rng(UnknownRng),
     #[display("Interrupted.")]
     Interrupted,
 }
@@ -206,49 +220,84 @@ pub fn repl(
         file_setup,
         cli_setup,
         interactive,
+        check,
         run,
+        script,
     }: ReplCli,
 ) -> Result<(), ReplFatalError> {
     let setup::Setup {
         graphic,
         teminal,
         seed,
+        rng,
+        print_to_stderr,
+        size_warning,
+        auto_print,
+        no_color,
     } = setup::Setup::extract_setups(file_setup, cli_setup)?;
+    let auto_print = auto_print.unwrap_or(true);
+    // When running a single command, `print` can be kept apart from the result
+    let print_to_stderr =
+        print_to_stderr.unwrap_or(false) && (run.is_some() || script.is_some()) && !interactive;
 
     // Identify the default graphic if not given
-    let graphic = graphic.unwrap_or_default();
+    let graphic = match graphic {
+        Some(graphic) => graphic,
+        None if no_color.unwrap_or(false) => Graphic::None,
+        None => Graphic::default(),
+    };
 
     // Boxing the graphic
     let graphic = Rc::new(graphic);
     // Creating the skin
     let skin = Rc::new(graphic.skin(teminal));
     // Initializing the engine
-    let engine_builder = dices_engine::EngineBuilder::new()
-        .inject_intrisics_with_data(repl_intrisics::Data::new(graphic.clone(), skin.clone()));
-    let engine_builder = if let Some(seed) = seed {
+    let data = repl_intrisics::Data::new(graphic.clone(), skin.clone());
+    let engine_builder = if print_to_stderr {
+        dices_engine::EngineBuilder::new()
+            .inject_intrisics_with_data(data.with_print_output(io::stderr()))
+            .with_output_writer(io::stderr())
+    } else {
+        dices_engine::EngineBuilder::new().inject_intrisics_with_data(data)
+    };
+    let rng = rng.as_deref().unwrap_or(DEFAULT_RNG);
+    let rng = if let Some(seed) = seed {
         let mut hasher = DefaultHasher::new();
         seed.hash(&mut hasher);
 
-        engine_builder.with_rng(Xoshiro256PlusPlus::seed_from_u64(hasher.finish()))
+        NamedRng::seed_from_u64_named(rng, hasher.finish())?
     } else {
-        engine_builder.with_rng_from_entropy()
+        NamedRng::from_entropy_named(rng)?
     };
-    let mut engine: dices_engine::Engine<Xoshiro256PlusPlus, REPLIntrisics> =
-        engine_builder.build();
+    let mut engine: dices_engine::Engine<NamedRng, REPLIntrisics> =
+        engine_builder.with_rng(rng).build();
 
+    // a script is run like a command given on the command line
+    let run = match script {
+        Some(script) => Some(vec![fs::read_to_string(script)?]),
+        None => run,
+    };
+    if check {
+        let src = match run {
+            Some(run) => run.join(" "),
+            None => io::read_to_string(stdin())?,
+        };
+        engine.parse_str(&src)?;
+        return Ok(());
+    }
     if let Some(run) = run {
         // joining of the shell arguments
         let cmd = run.join(" ");
-        // running in the new engine
-        let value = engine.eval_str(&cmd)?;
-        // printing the result of the init command
-        print_value(
+        // running in the new engine, and printing the result
+        run_command(
+            &mut engine,
             *graphic,
-            &*skin,
-            &value,
+            &skin,
+            &cmd,
             interactive, // skip printing `null` if the console is interactive
-        );
-        println!();
+            auto_print,
+            &mut stdout(),
+        )?;
 
         if !interactive {
             // runned the single command, exiting.
@@ -260,9 +309,19 @@ pub fn repl(
     skin.print_text(graphic.banner());
 
     if atty::is(atty::Stream::Stdin) {
-        interactive_repl(graphic.clone(), skin.clone(), &mut engine)?
+        // the pager needs the terminal for itself
+        engine
+            .injected_intrisics_data_mut()
+            .set_pager(atty::is(atty::Stream::Stdout));
+        interactive_repl(
+            graphic.clone(),
+            skin.clone(),
+            &mut engine,
+            size_warning.unwrap_or(DEFAULT_SIZE_WARNING),
+            auto_print,
+        )?
     } else {
-        detached_repl(graphic.clone(), skin.clone(), &mut engine)?
+        detached_repl(graphic.clone(), skin.clone(), &mut engine, auto_print)?
     };
 
     // Print the out banner
@@ -271,11 +330,23 @@ pub fn repl(
     Ok(())
 }
 
+/// The random number generator used if none is given
+const DEFAULT_RNG: &str = "xoshiro256++";
+
+/// Number of values above which the interactive REPL asks before evaluating a command
+const DEFAULT_SIZE_WARNING: u64 = 100_000;
+
 /// Run the REPL in interactive mode
+///
+/// Commands estimated to build more than `size_warning` values are evaluated only after the user
+/// confirms. A `size_warning` of 0 disables the check. Without `auto_print`, the results of the
+/// commands are not printed.
 pub fn interactive_repl(
     graphic: Rc<Graphic>,
     skin: Rc<MadSkin>,
-    engine: &mut Engine<Xoshiro256PlusPlus, REPLIntrisics>,
+    engine: &mut Engine<NamedRng, REPLIntrisics>,
+    size_warning: u64,
+    auto_print: bool,
 ) -> Result<(), ReplFatalError> {
     // Creating the editor
     let mut line_editor = Reedline::create();
@@ -283,21 +354,29 @@ pub fn interactive_repl(
     loop {
         let sig = line_editor.read_line(&ReplPrompt { graphic: *graphic })?;
         match sig {
-            Signal::Success(line) => match engine.eval_str(&line) {
-                Ok(value) => print_value(*graphic, &*skin, &value, true),
-                Err(err) => {
-                    // need to catch the quitting error
-                    if let Quitted::Yes(value) = engine.injected_intrisics_data().quitted() {
-                        // this is not an error, but the quitting signal
-                        let _ = err;
+            Signal::Success(line) => {
+                if !confirm_size(engine, &line, size_warning)? {
+                    continue;
+                }
+                let outcome = eval_command(engine, &line);
+                engine.injected_intrisics_data_mut().set_last_input(line);
+                match outcome {
+                    Outcome::Value(value) => {
+                        if auto_print {
+                            print_value(*graphic, &*skin, &value, true)
+                        }
+                    }
+                    Outcome::Quit(value) => {
                         // printing the value provided to the `quit` intrisic
-                        print_value(*graphic, &*skin, value, true);
+                        if auto_print {
+                            print_value(*graphic, &*skin, &value, true);
+                        }
                         // stopping the REPL
                         break;
                     }
-                    print_err(*graphic, &*skin, err)
+                    Outcome::Error(err) => print_err(*graphic, &*skin, err),
                 }
-            },
+            }
             Signal::CtrlD => {
                 break;
             }
@@ -307,50 +386,134 @@ pub fn interactive_repl(
     Ok(())
 }
 
+/// Check the estimated size of a command, asking the user to confirm it if too large
+///
+/// Commands that do not parse are let through, so the error is reported when they are evaluated.
+fn confirm_size(
+    engine: &Engine<NamedRng, REPLIntrisics>,
+    line: &str,
+    size_warning: u64,
+) -> io::Result<bool> {
+    if size_warning == 0 {
+        return Ok(true);
+    }
+    let Ok(exprs) = engine.parse_str(line) else {
+        return Ok(true);
+    };
+    let size = exprs
+        .iter()
+        .map(Expression::estimated_size)
+        .fold(0, u64::saturating_add);
+    if size <= size_warning {
+        return Ok(true);
+    }
+    print!("This command could build about {size} values, and take a long time. Evaluate it anyway? [y/N] ");
+    stdout().flush()?;
+    let mut answer = String::new();
+    stdin().read_line(&mut answer)?;
+    Ok(answer.trim().eq_ignore_ascii_case("y"))
+}
+
 /// Run the REPL in detached mode (input from a stream)
+///
+/// Without `auto_print`, the results of the commands are not printed.
 pub fn detached_repl(
     graphic: Rc<Graphic>,
     skin: Rc<MadSkin>,
-    engine: &mut Engine<Xoshiro256PlusPlus, REPLIntrisics>,
+    engine: &mut Engine<NamedRng, REPLIntrisics>,
+    auto_print: bool,
 ) -> Result<(), ReplFatalError> {
     // REPL loop
     for line in stdin().lines() {
         let line = line?;
         println!("{}{}", graphic.prompt(), line);
-        match engine.eval_str(&line) {
-            Ok(value) => print_value(*graphic, &*skin, &value, true),
-            Err(err) => {
-                // need to catch the quitting error
-                if let Quitted::Yes(value) = engine.injected_intrisics_data().quitted() {
-                    // this is not an error, but the quitting signal
-                    let _ = err;
-                    // printing the value provided to the `quit` intrisic
-                    print_value(*graphic, &*skin, value, true);
-                    // stopping the REPL
-                    break;
+        let outcome = eval_command(engine, &line);
+        engine.injected_intrisics_data_mut().set_last_input(line);
+        match outcome {
+            Outcome::Value(value) => {
+                if auto_print {
+                    print_value(*graphic, &*skin, &value, true)
                 }
-                print_err(*graphic, &*skin, err)
             }
+            Outcome::Quit(value) => {
+                // printing the value provided to the `quit` intrisic
+                if auto_print {
+                    print_value(*graphic, &*skin, &value, true);
+                }
+                // stopping the REPL
+                break;
+            }
+            Outcome::Error(err) => print_err(*graphic, &*skin, err),
         }
     }
     Ok(())
 }
 
+/// What came out of a command evaluated by the REPL
+#[derive(Debug)]
+pub enum Outcome {
+    /// The command gave a value
+    Value(Value<REPLIntrisics>),
+    /// The command called `quit`, with this value
+    Quit(Value<REPLIntrisics>),
+    /// The command failed
+    Error(dices_engine::EvalStrError<REPLIntrisics>),
+}
+
+/// Evaluate a command, telling a call to `quit` apart from the errors
+pub fn eval_command(engine: &mut Engine<NamedRng, REPLIntrisics>, cmd: &str) -> Outcome {
+    match engine.eval_str(cmd) {
+        Ok(value) => Outcome::Value(value),
+        // `quit` stops the evaluation with an error, but it is not one
+        Err(err) => match engine.injected_intrisics_data_mut().take_quitted() {
+            Some(value) => Outcome::Quit(value),
+            None => Outcome::Error(err),
+        },
+    }
+}
+
+/// Run a single command, writing its result on `out` if `auto_print` is set
+fn run_command(
+    engine: &mut Engine<NamedRng, REPLIntrisics>,
+    graphic: Graphic,
+    skin: &MadSkin,
+    cmd: &str,
+    skip_nulls: bool,
+    auto_print: bool,
+    out: &mut dyn Write,
+) -> Result<(), ReplFatalError> {
+    let value = engine.eval_str(cmd)?;
+    if !auto_print {
+        return Ok(());
+    }
+    write_value(graphic, skin, &value, skip_nulls, out)?;
+    writeln!(out)?;
+    Ok(())
+}
+
 /// Print a value
-fn print_value(graphic: Graphic, _skin: &MadSkin, value: &Value<REPLIntrisics>, skip_nulls: bool) {
+fn print_value(graphic: Graphic, skin: &MadSkin, value: &Value<REPLIntrisics>, skip_nulls: bool) {
+    write_value(graphic, skin, value, skip_nulls, &mut stdout())
+        .expect("Error in printing the value")
+}
+
+/// Write a value
+fn write_value(
+    graphic: Graphic,
+    _skin: &MadSkin,
+    value: &Value<REPLIntrisics>,
+    skip_nulls: bool,
+    out: &mut dyn Write,
+) -> io::Result<()> {
     if skip_nulls && value == &Value::Null(ValueNull) {
         // do not print null values
-        return;
+        return Ok(());
     }
     if graphic == Graphic::None {
-        println!("{}", value);
-        return;
+        return writeln!(out, "{}", value);
     }
     let arena = pretty::Arena::<()>::new();
-    value
-        .pretty(&arena)
-        .render(terminal_size().0 as _, &mut stdout())
-        .expect("Error in formatting the value");
+    value.pretty(&arena).render(terminal_size().0 as _, out)
 }
 
 /// Print an error
@@ -358,3 +521,142 @@ fn print_err(_graphic: Graphic, _skin: &MadSkin, error: impl Error) {
     let report = Report::new(error).pretty(true);
     eprintln!("{report}")
 }
+
+#[cfg(test)]
+#[test]
+fn print_can_be_separated_from_the_result() {
+    use dices_engine::SharedOutput;
+
+    let side = SharedOutput::default();
+    let mut engine = dices_engine::EngineBuilder::new()
+        .inject_intrisics_with_data(
+            repl_intrisics::Data::new(Rc::new(Graphic::None), Rc::new(MadSkin::no_style()))
+                .with_print_output(side.clone()),
+        )
+        .with_output_writer(side.clone())
+        .with_rng(NamedRng::seed_from_u64_named(DEFAULT_RNG, 42).unwrap())
+        .build();
+    let mut result = vec![];
+    run_command(
+        &mut engine,
+        Graphic::None,
+        &MadSkin::no_style(),
+        "pretty_print(1); print(2); 3",
+        false,
+        true,
+        &mut result,
+    )
+    .unwrap();
+
+    let side = side.take();
+    assert_eq!(
+        side.lines().filter(|l| !l.is_empty()).collect::<Vec<_>>(),
+        ["1", "2"]
+    );
+    assert_eq!(String::from_utf8(result).unwrap().trim(), "3");
+}
+
+#[cfg(test)]
+#[test]
+fn results_are_printed_only_with_auto_print() {
+    use dices_engine::SharedOutput;
+
+    let run = |cmd: &str, auto_print: bool| {
+        let side = SharedOutput::default();
+        let mut engine = dices_engine::EngineBuilder::new()
+            .inject_intrisics_with_data(
+                repl_intrisics::Data::new(Rc::new(Graphic::None), Rc::new(MadSkin::no_style()))
+                    .with_print_output(side.clone()),
+            )
+            .with_rng(NamedRng::seed_from_u64_named(DEFAULT_RNG, 42).unwrap())
+            .build();
+        let mut result = vec![];
+        run_command(
+            &mut engine,
+            Graphic::None,
+            &MadSkin::no_style(),
+            cmd,
+            false,
+            auto_print,
+            &mut result,
+        )
+        .unwrap();
+        let side = side.take();
+        (
+            side.trim().to_owned(),
+            String::from_utf8(result).unwrap().trim().to_owned(),
+        )
+    };
+
+    assert_eq!(run("3", true), ("".to_owned(), "3".to_owned()));
+    assert_eq!(run("3", false), ("".to_owned(), "".to_owned()));
+    assert_eq!(
+        run("pretty_print(3)", false),
+        ("3".to_owned(), "".to_owned())
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn quit_is_an_outcome() {
+    let mut engine = dices_engine::EngineBuilder::new()
+        .inject_intrisics_with_data(repl_intrisics::Data::new(
+            Rc::new(Graphic::None),
+            Rc::new(MadSkin::no_style()),
+        ))
+        .with_rng(NamedRng::seed_from_u64_named(DEFAULT_RNG, 42).unwrap())
+        .build();
+    assert!(matches!(
+        eval_command(&mut engine, "1 + 2"),
+        Outcome::Value(Value::Number(n)) if n == 3
+    ));
+    assert!(matches!(
+        eval_command(&mut engine, "quit(4)"),
+        Outcome::Quit(Value::Number(n)) if n == 4
+    ));
+    assert!(matches!(eval_command(&mut engine, "d0"), Outcome::Error(_)));
+    // the engine can be used again after quitting
+    assert!(matches!(
+        eval_command(&mut engine, "5"),
+        Outcome::Value(Value::Number(n)) if n == 5
+    ));
+}
+
+#[cfg(test)]
+#[test]
+fn check_reports_syntax_errors() {
+    let dir = std::env::temp_dir().join(format!("dices-check-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let valid = dir.join("valid.dices");
+    fs::write(&valid, "#!/usr/bin/env dices\nlet str = 3d6;\nstr + 2").unwrap();
+    let invalid = dir.join("invalid.dices");
+    fs::write(&invalid, "let str = 3d6 +;").unwrap();
+
+    let check = |script: &PathBuf| {
+        repl(ReplCli::parse_from([
+            "dices",
+            "--check",
+            script.to_str().unwrap(),
+        ]))
+        .err()
+    };
+    assert!(check(&valid).is_none());
+    assert!(matches!(check(&invalid), Some(ReplFatalError::Syntax(_))));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(test)]
+#[test]
+fn rng_is_chosen_by_name() {
+    let check = |rng: &str| {
+        repl(ReplCli::parse_from([
+            "dices", "--rng", rng, "--seed", "42", "--check", "--run", "d6",
+        ]))
+        .err()
+    };
+    for rng in NamedRng::NAMES {
+        assert!(check(rng).is_none(), "`{rng}` was not recognized")
+    }
+    assert!(matches!(check("dice_bag"), Some(ReplFatalError::Rng(_))));
+}
@@ -0,0 +1,289 @@
+//! Interactive pager for the manual
+//!
+//! The pager shows a topic of the manual, and lets the user scroll it and move between the topics
+//! it links to. Index pages link to all the entries of their directory.
+
+use std::io::{self, Write};
+
+use dices_man::{ManItem, ManTopicContent, RenderOptions};
+use markdown::mdast::Node;
+use termimad::{
+    crossterm::{
+        cursor::{Hide, MoveTo, Show},
+        event::{self, Event, KeyCode, KeyEventKind},
+        queue,
+        style::Print,
+        terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
+    },
+    Area, FmtText, MadSkin, TextView,
+};
+
+/// Find the topic best matching the one requested
+///
+/// If no topic has that exact name, the page that best matches the text is used, then the index.
+pub fn resolve_topic(topic: &str) -> String {
+    if dices_man::search(topic).is_some() {
+        return topic.to_owned();
+    }
+    dices_man::search_text(topic)
+        .first()
+        .map(|hit| hit.topic.to_owned())
+        .unwrap_or_else(|| "index".to_owned())
+}
+
+/// The topics linked from a topic
+fn links_of(topic: &str) -> Vec<String> {
+    match dices_man::search(topic) {
+        Some(ManTopicContent::Page(page)) => {
+            let mut links = vec![];
+            let mut nodes = vec![page.source()];
+            while let Some(node) = nodes.pop() {
+                if let Node::Link(link) = node {
+                    if let Some(target) = link.url.strip_prefix("man:") {
+                        if !links.iter().any(|l| l == target) {
+                            links.push(target.to_owned())
+                        }
+                    }
+                }
+                nodes.extend(node.children().into_iter().flatten().rev())
+            }
+            links
+        }
+        Some(ManTopicContent::Index(dir)) => {
+            // the entries are relative to the directory, not to its index
+            let dir_topic = match topic.strip_suffix("index") {
+                Some(dir_topic) => dir_topic.trim_end_matches('/'),
+                None => topic,
+            };
+            dir.content
+                .entries()
+                .filter(|(_, item)| !matches!(item, ManItem::Index(_)))
+                .map(|(&key, _)| {
+                    if dir_topic.is_empty() {
+                        key.to_owned()
+                    } else {
+                        format!("{dir_topic}/{key}")
+                    }
+                })
+                .collect()
+        }
+        None => vec![],
+    }
+}
+
+/// Where the user is in the manual
+#[derive(Debug, Clone)]
+pub struct Navigation {
+    /// The topic shown
+    topic: String,
+    /// The topics linked from the one shown
+    links: Vec<String>,
+    /// The link selected, if any
+    selected: Option<usize>,
+    /// The topics visited before, the last one is the most recent
+    history: Vec<String>,
+}
+
+impl Navigation {
+    /// Start navigating from a topic
+    pub fn new(topic: &str) -> Self {
+        let topic = resolve_topic(topic);
+        Self {
+            links: links_of(&topic),
+            topic,
+            selected: None,
+            history: vec![],
+        }
+    }
+
+    /// The topic shown
+    pub fn topic(&self) -> &str {
+        &self.topic
+    }
+
+    /// The content of the topic shown
+    pub fn content(&self) -> ManTopicContent {
+        dices_man::search(&self.topic).unwrap_or_else(dices_man::index)
+    }
+
+    /// The topics that can be reached from the one shown
+    pub fn links(&self) -> &[String] {
+        &self.links
+    }
+
+    /// The link selected, if any
+    pub fn selected(&self) -> Option<&str> {
+        self.selected.map(|idx| &*self.links[idx])
+    }
+
+    /// Select the next link, wrapping around
+    pub fn select_next(&mut self) {
+        if !self.links.is_empty() {
+            self.selected = Some(self.selected.map_or(0, |idx| (idx + 1) % self.links.len()))
+        }
+    }
+
+    /// Select the previous link, wrapping around
+    pub fn select_prev(&mut self) {
+        if !self.links.is_empty() {
+            self.selected = Some(
+                self.selected
+                    .unwrap_or(0)
+                    .checked_sub(1)
+                    .unwrap_or(self.links.len() - 1),
+            )
+        }
+    }
+
+    /// Go to the selected link
+    ///
+    /// Return `false` if no link was selected.
+    pub fn follow(&mut self) -> bool {
+        let Some(target) = self.selected().map(resolve_topic) else {
+            return false;
+        };
+        let previous = std::mem::replace(&mut self.topic, target);
+        self.history.push(previous);
+        self.enter();
+        true
+    }
+
+    /// Go back to the previous topic
+    ///
+    /// Return `false` if this is the first topic visited.
+    pub fn back(&mut self) -> bool {
+        let Some(previous) = self.history.pop() else {
+            return false;
+        };
+        self.topic = previous;
+        self.enter();
+        true
+    }
+
+    /// Update the links after the topic changed
+    fn enter(&mut self) {
+        self.links = links_of(&self.topic);
+        self.selected = None;
+    }
+}
+
+/// Show the manual in the pager, starting from `topic`, until the user quits
+pub fn run(topic: &str, skin: &MadSkin, options: RenderOptions) -> io::Result<()> {
+    let mut out = io::stdout();
+    queue!(out, EnterAlternateScreen, Hide)?;
+    // from here on the terminal is restored whatever happens, even on a panic
+    let _screen = ScreenGuard;
+    terminal::enable_raw_mode()?;
+    pager_loop(Navigation::new(topic), skin, options, &mut out)
+}
+
+/// Gives the terminal back to the REPL when dropped
+struct ScreenGuard;
+impl Drop for ScreenGuard {
+    fn drop(&mut self) {
+        // nothing can be done with the errors, so the terminal is restored as much as possible
+        let _ = terminal::disable_raw_mode();
+        let mut out = io::stdout();
+        let _ = queue!(out, Show, LeaveAlternateScreen);
+        let _ = out.flush();
+    }
+}
+
+fn pager_loop(
+    mut nav: Navigation,
+    skin: &MadSkin,
+    options: RenderOptions,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let mut scroll = 0;
+    // each iteration renders a page, that is kept until the topic or the size of the screen change
+    loop {
+        // the last line is kept for the status
+        let mut area = Area::full_screen();
+        area.height = area.height.saturating_sub(1).max(1);
+
+        let content = nav.content();
+        let content = content.rendered(RenderOptions {
+            width: area.width as _,
+            ..options.clone()
+        });
+        let content =
+            mdast2minimad::to_minimad(&content).expect("All help pages should be convertible");
+        let text = FmtText::from_text(skin, content, Some(area.width as _));
+        let mut view = TextView::from(&area, &text);
+        view.scroll = scroll;
+
+        loop {
+            queue!(out, Clear(ClearType::All))?;
+            view.write_on(out).map_err(io::Error::other)?;
+            let status = match nav.selected() {
+                Some(link) => format!("[{}] -> {link} (Enter to follow)", nav.topic()),
+                None if nav.links().is_empty() => format!("[{}]", nav.topic()),
+                None => format!("[{}] Tab to select a link", nav.topic()),
+            };
+            queue!(
+                out,
+                MoveTo(0, area.height),
+                Print(format!("{status} | Backspace: back, q: quit"))
+            )?;
+            out.flush()?;
+
+            let key = match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => key,
+                // the page must be laid out again
+                Event::Resize(..) => break,
+                _ => continue,
+            };
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Tab => nav.select_next(),
+                KeyCode::BackTab => nav.select_prev(),
+                KeyCode::Enter => {
+                    if nav.follow() {
+                        scroll = 0;
+                        break;
+                    }
+                }
+                KeyCode::Backspace => {
+                    if nav.back() {
+                        scroll = 0;
+                        break;
+                    }
+                }
+                _ => {
+                    view.apply_key_event(key);
+                    scroll = view.scroll
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn following_a_link_resolves_the_topic() {
+    let mut nav = Navigation::new("std/math");
+    assert!(nav.links().iter().any(|l| l == "std/math/pow"));
+    while nav.selected() != Some("std/math/pow") {
+        nav.select_next()
+    }
+    assert!(nav.follow());
+    assert_eq!(nav.topic(), "std/math/pow");
+    assert!(nav.content().is_page());
+    assert!(nav.back());
+    assert_eq!(nav.topic(), "std/math");
+    assert!(!nav.back())
+}
+
+#[cfg(test)]
+#[test]
+fn page_links_point_to_the_manual() {
+    let nav = Navigation::new("types/strings");
+    assert!(nav.links().iter().any(|l| l == "std/conversions/parse"));
+    for link in nav.links() {
+        assert!(
+            dices_man::search(link).is_some(),
+            "`{link}` is not a topic of the manual"
+        )
+    }
+}
@@ -1,7 +1,8 @@
 //! Intrisics for the REPL
 
 use std::{
-    fs, io,
+    fs,
+    io::{self, Write},
     path::Path,
     rc::Rc,
     time::{SystemTime, UNIX_EPOCH},
@@ -9,13 +10,14 @@ use std::{
 
 use derive_more::derive::{Display, Error};
 use dices_ast::{
+    ident::IdentStr,
     intrisics::InjectedIntr,
     value::{Value, ValueList, ValueNull},
 };
 use dices_man::RenderOptions;
 use termimad::{crossterm::terminal, MadSkin};
 
-use crate::{print_value, Graphic};
+use crate::{pager, write_value, Graphic};
 
 pub struct Data {
     // stuff needed to visualize the elements
@@ -24,6 +26,18 @@ pub struct Data {
 
     // mark if the repl was quitted
     quitted: Quitted,
+
+    // the last input evaluated by the repl
+    last_input: Option<String>,
+
+    // where `print` writes to
+    print_output: Box<dyn Write>,
+
+    // if `help` can open the interactive pager
+    pager: bool,
+
+    // the name of the std library, used by `format_source` to reach the intrisics
+    std_name: Box<IdentStr>,
 }
 
 pub enum Quitted {
@@ -37,22 +51,61 @@ impl Data {
             graphic,
             skin,
             quitted: Quitted::No,
+            last_input: None,
+            print_output: Box::new(io::stdout()),
+            pager: false,
+            std_name: IdentStr::new_boxed("std".into()).unwrap(),
+        }
+    }
+
+    /// Set the name of the std library, if the engine uses a different one
+    pub fn with_std_name(self, std_name: Box<IdentStr>) -> Self {
+        Self { std_name, ..self }
+    }
+
+    /// Make `print` write to a different output than stdout
+    pub fn with_print_output(self, output: impl Write + 'static) -> Self {
+        Self {
+            print_output: Box::new(output),
+            ..self
         }
     }
 
     pub fn quitted(&self) -> &Quitted {
         &self.quitted
     }
+
+    /// Take the value given to `quit`, if it was called
+    pub fn take_quitted(&mut self) -> Option<Value<REPLIntrisics>> {
+        match std::mem::replace(&mut self.quitted, Quitted::No) {
+            Quitted::No => None,
+            Quitted::Yes(value) => Some(value),
+        }
+    }
+
+    /// Let `help` open the manual in the interactive pager, instead of printing it
+    pub fn set_pager(&mut self, pager: bool) {
+        self.pager = pager
+    }
+
+    /// Record the last input evaluated by the repl
+    pub fn set_last_input(&mut self, input: String) {
+        self.last_input = Some(input)
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum REPLIntrisics {
-    /// Print a value
-    Print,
+    /// Print a value, with the same graphic used for the results
+    ///
+    /// The plain `print` of the standard library writes the values as text instead
+    PrettyPrint,
     /// Quit the repl
     Quit,
-    /// Print a manual page
+    /// Show a manual page, in the pager if interactive
     Help,
+    /// Search the manual, listing the pages found
+    Search,
 
     /// Get the system time
     Time,
@@ -61,6 +114,9 @@ pub enum REPLIntrisics {
     FileRead,
     /// Write a string to a file
     FileWrite,
+
+    /// Reformat a source in the canonical style
+    FormatSource,
 }
 #[derive(Debug, Display, Error)]
 pub enum REPLIntrisicsError {
@@ -69,6 +125,13 @@ pub enum REPLIntrisicsError {
 
     #[display("`file_read` must be called with a single string parameter")]
     FileReadUsage,
+    #[display("Error while printing")]
+    PrintError(io::Error),
+    #[display("Error in the manual pager")]
+    PagerError(io::Error),
+    #[display("`search` must be called with a single string parameter")]
+    SearchUsage,
+
     #[display("Error while reading file")]
     FileReadError(io::Error),
 
@@ -76,6 +139,13 @@ pub enum REPLIntrisicsError {
     FileWriteUsage,
     #[display("Error while writing file")]
     FileWriteError(io::Error),
+
+    #[display("`format_source` must be called with a single string parameter, or none")]
+    FormatSourceUsage,
+    #[display("There is no previous input to format")]
+    FormatSourceNoInput,
+    #[display("Cannot parse the source to format")]
+    FormatSourceParse(dices_ast::expression::ParseError),
 }
 
 macro_rules! repetitive_impls {
@@ -113,24 +183,32 @@ impl InjectedIntr for REPLIntrisics {
     type Error = REPLIntrisicsError;
 
     repetitive_impls! {
-        Print <=> "print",
+        PrettyPrint <=> "pretty_print",
         Quit <=> "quit",
         Help <=> "help",
+        Search <=> "search",
         Time <=> "time",
         FileRead <=> "file_read",
-        FileWrite <=> "file_write"
+        FileWrite <=> "file_write",
+        FormatSource <=> "format_source"
     }
 
     fn std_paths(&self) -> &[&[&'static str]] {
         match self {
-            REPLIntrisics::Print => {
-                &[&["prelude", "print"] as &[&str], &["repl", "print"]] as &[&[&str]]
-            }
+            REPLIntrisics::PrettyPrint => &[
+                &["prelude", "pretty_print"] as &[&str],
+                &["repl", "pretty_print"],
+            ],
             REPLIntrisics::Quit => &[&["prelude", "quit"] as &[&str], &["repl", "quit"]],
             REPLIntrisics::Help => &[&["prelude", "help"] as &[&str], &["repl", "help"]],
+            REPLIntrisics::Search => &[&["prelude", "search"] as &[&str], &["repl", "search"]],
             REPLIntrisics::Time => &[&["prelude", "time"] as &[&str], &["sys", "time"]],
             REPLIntrisics::FileRead => &[&["sys", "files", "read"] as &[&str]],
             REPLIntrisics::FileWrite => &[&["sys", "files", "write"] as &[&str]],
+            REPLIntrisics::FormatSource => &[
+                &["prelude", "format_source"] as &[&str],
+                &["repl", "format_source"],
+            ],
         }
     }
 
@@ -140,10 +218,17 @@ impl InjectedIntr for REPLIntrisics {
         params: Box<[Value<Self>]>,
     ) -> Result<Value<Self>, Self::Error> {
         match self {
-            REPLIntrisics::Print => {
+            REPLIntrisics::PrettyPrint => {
                 for value in params.iter() {
-                    print_value(*data.graphic, &data.skin, value, false);
-                    println!()
+                    write_value(
+                        *data.graphic,
+                        &data.skin,
+                        value,
+                        false,
+                        &mut data.print_output,
+                    )
+                    .and_then(|()| writeln!(data.print_output))
+                    .map_err(REPLIntrisicsError::PrintError)?;
                 }
                 Ok(Value::Null(ValueNull))
             }
@@ -162,17 +247,24 @@ impl InjectedIntr for REPLIntrisics {
                     [Value::String(s)] => &*s,
                     _ => HELP_PAGE_FOR_HELP,
                 };
-                // search the manual. If absent, find the index.
-                let content = dices_man::search(topic).unwrap_or_else(dices_man::index);
+                // search the manual. If absent, try the page that best matches the text, then the index.
+                let topic = pager::resolve_topic(topic);
                 // render the content, running the examples with the current prompt
-                let content = content.rendered(RenderOptions {
+                let options = RenderOptions {
                     prompt: data.graphic.prompt().to_owned().into(),
                     prompt_cont: data.graphic.prompt_cont().to_owned().into(),
                     width: terminal::size()
                         .map(|(w, _)| w as _)
                         .unwrap_or(RenderOptions::default().width),
                     ..Default::default()
-                });
+                };
+                if data.pager {
+                    pager::run(&topic, &data.skin, options)
+                        .map_err(REPLIntrisicsError::PagerError)?;
+                    return Ok(Value::Null(ValueNull));
+                }
+                let content = dices_man::search(&topic).unwrap_or_else(dices_man::index);
+                let content = content.rendered(options);
                 // convert the content into a minimad text
                 let content = mdast2minimad::to_minimad(&*content)
                     .expect("All help pages should be convertible");
@@ -187,6 +279,30 @@ impl InjectedIntr for REPLIntrisics {
                 );
                 Ok(Value::Null(ValueNull))
             }
+            REPLIntrisics::Search => {
+                let query = match &*params {
+                    [Value::String(query)] => &**query,
+                    _ => return Err(REPLIntrisicsError::SearchUsage),
+                };
+                for hit in dices_man::search_text(query)
+                    .into_iter()
+                    .take(SEARCH_RESULTS)
+                {
+                    let snippet = snippet(hit.page.summary().unwrap_or(hit.page.name));
+                    if let Graphic::None = *data.graphic {
+                        writeln!(data.print_output, "{}: {snippet}", hit.topic)
+                    } else {
+                        write!(
+                            data.print_output,
+                            "{}",
+                            data.skin
+                                .text(&format!("* **{}**: {snippet}", hit.topic), None)
+                        )
+                    }
+                    .map_err(REPLIntrisicsError::PrintError)?;
+                }
+                Ok(Value::Null(ValueNull))
+            }
             REPLIntrisics::Time => Ok(Value::Number(
                 SystemTime::now()
                     .duration_since(UNIX_EPOCH)
@@ -212,10 +328,39 @@ impl InjectedIntr for REPLIntrisics {
                     .map_err(REPLIntrisicsError::FileWriteError)?;
                 Ok(Value::Null(ValueNull))
             }
+            REPLIntrisics::FormatSource => {
+                let source = match &*params {
+                    [] => data
+                        .last_input
+                        .as_deref()
+                        .ok_or(REPLIntrisicsError::FormatSourceNoInput)?,
+                    [Value::String(source)] => &**source,
+                    _ => return Err(REPLIntrisicsError::FormatSourceUsage),
+                };
+                let formatted = dices_ast::expression::format_source(source, &data.std_name)
+                    .map_err(REPLIntrisicsError::FormatSourceParse)?;
+                Ok(Value::String(formatted.into()))
+            }
         }
     }
 }
 
+/// How many pages `search` lists at most
+const SEARCH_RESULTS: usize = 10;
+
+/// Shorten a summary to a single line, to list it in the results of `search`
+fn snippet(summary: &str) -> String {
+    const MAX_LEN: usize = 80;
+
+    let line = summary.lines().next().unwrap_or_default().trim();
+    if line.chars().count() <= MAX_LEN {
+        line.to_owned()
+    } else {
+        let cut: String = line.chars().take(MAX_LEN - 1).collect();
+        format!("{}…", cut.trim_end())
+    }
+}
+
 /// The page for help about `help`
 const HELP_PAGE_FOR_HELP: &str = "std/repl/help";
 
@@ -246,3 +391,78 @@ fn all_names_roundtrip() {
         assert_eq!(intrisic, named, "Intrisic `{name}` did not roundtrip")
     }
 }
+
+#[cfg(test)]
+#[test]
+fn prints_are_told_apart() {
+    use dices_ast::intrisics::Intrisic;
+
+    let pretty = Intrisic::Injected(REPLIntrisics::PrettyPrint);
+    assert_eq!(
+        Intrisic::<REPLIntrisics>::named("pretty_print"),
+        Some(pretty)
+    );
+    assert_eq!(
+        Intrisic::<REPLIntrisics>::from_id("pretty_print"),
+        Some(pretty)
+    );
+    // `print` is the plain one, also when read from values encoded before the REPL one was renamed
+    assert_eq!(
+        Intrisic::<REPLIntrisics>::named("print"),
+        Some(Intrisic::Print)
+    );
+    assert_eq!(
+        Intrisic::<REPLIntrisics>::from_id("print"),
+        Some(Intrisic::Print)
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn calls_are_counted() {
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256PlusPlus;
+
+    let mut engine = dices_engine::EngineBuilder::new()
+        .inject_intrisics_with_data::<REPLIntrisics>(Data::new(
+            Rc::new(Graphic::None),
+            Rc::new(MadSkin::no_style()),
+        ))
+        .with_rng(Xoshiro256PlusPlus::seed_from_u64(42))
+        .with_intrisic_stats()
+        .build();
+    engine
+        .eval_str(r#"pretty_print(1); help("help"); pretty_print(2)"#)
+        .unwrap();
+
+    let stats = engine.intrisic_stats().unwrap();
+    assert_eq!(stats.get("pretty_print"), Some(&2));
+    assert_eq!(stats.get("help"), Some(&1));
+    assert_eq!(stats.get("quit"), None);
+}
+
+#[cfg(test)]
+#[test]
+fn search_lists_the_topics() {
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256PlusPlus;
+
+    use dices_engine::SharedOutput;
+
+    let output = SharedOutput::default();
+    let mut engine = dices_engine::EngineBuilder::new()
+        .inject_intrisics_with_data::<REPLIntrisics>(
+            Data::new(Rc::new(Graphic::None), Rc::new(MadSkin::no_style()))
+                .with_print_output(output.clone()),
+        )
+        .with_rng(Xoshiro256PlusPlus::seed_from_u64(42))
+        .build();
+    engine.eval_str(r#"search("exponent")"#).unwrap();
+
+    let output = output.take();
+    assert!(
+        output.lines().any(|l| l.starts_with("std/math/pow: ")),
+        "`std/math/pow` was not found:\n{output}"
+    );
+    assert!(output.lines().count() <= SEARCH_RESULTS)
+}
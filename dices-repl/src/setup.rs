@@ -1,6 +1,9 @@
 //! The setup for the CLI REPL
 
-use std::{ffi::OsString, path::PathBuf};
+use std::{
+    ffi::{OsStr, OsString},
+    path::PathBuf,
+};
 
 use clap::Args;
 use figment::{
@@ -27,6 +30,31 @@ pub struct Setup {
     #[clap(long, short)]
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub(crate) seed: Option<OsString>,
+
+    /// The random number generator to use (one of xoshiro256++, xoshiro256**, chacha8, chacha20, pcg)
+    #[clap(long)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) rng: Option<String>,
+
+    /// Write the output of `print` on stderr when running a single command, leaving only the result on stdout
+    #[clap(long, num_args = 0..=1, default_missing_value = "true")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) print_to_stderr: Option<bool>,
+
+    /// Ask for confirmation before evaluating commands that could build more than this many values (0 never asks)
+    #[clap(long)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) size_warning: Option<u64>,
+
+    /// Print the result of each command (if false, only `print` writes anything)
+    #[clap(long, num_args = 0..=1, default_missing_value = "true")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) auto_print: Option<bool>,
+
+    /// Never use colors or emojis, unless a graphic is given explicitly
+    #[clap(long, num_args = 0..=1, default_missing_value = "true")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) no_color: Option<bool>,
 }
 
 impl Setup {
@@ -53,3 +81,54 @@ impl Setup {
         figment.extract()
     }
 }
+
+/// Choose the graphic when none is given
+///
+/// A non empty `NO_COLOR` disables the graphic, while a `CLICOLOR_FORCE` different from `0`
+/// enables it even if the output is not a terminal. Otherwise, the graphic is used only on a
+/// terminal.
+pub(crate) fn detect_graphic(env: impl Fn(&str) -> Option<OsString>, is_terminal: bool) -> Graphic {
+    if env("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+        Graphic::None
+    } else if is_terminal
+        || env("CLICOLOR_FORCE").is_some_and(|v| !v.is_empty() && v != OsStr::new("0"))
+    {
+        Graphic::Fancy
+    } else {
+        Graphic::None
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn no_color_disables_the_graphic() {
+    let env = |vars: &'static [(&'static str, &'static str)]| {
+        move |key: &str| {
+            vars.iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, v)| OsString::from(v))
+        }
+    };
+    assert_eq!(detect_graphic(env(&[]), true), Graphic::Fancy);
+    assert_eq!(detect_graphic(env(&[]), false), Graphic::None);
+    assert_eq!(
+        detect_graphic(env(&[("NO_COLOR", "1")]), true),
+        Graphic::None
+    );
+    assert_eq!(
+        detect_graphic(env(&[("NO_COLOR", "")]), true),
+        Graphic::Fancy
+    );
+    assert_eq!(
+        detect_graphic(env(&[("CLICOLOR_FORCE", "1")]), false),
+        Graphic::Fancy
+    );
+    assert_eq!(
+        detect_graphic(env(&[("CLICOLOR_FORCE", "0")]), false),
+        Graphic::None
+    );
+    assert_eq!(
+        detect_graphic(env(&[("NO_COLOR", "1"), ("CLICOLOR_FORCE", "1")]), true),
+        Graphic::None
+    );
+}